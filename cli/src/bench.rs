@@ -0,0 +1,294 @@
+use std::error::Error;
+use std::fmt;
+use std::fs::File;
+use std::io::{BufRead, BufWriter, Write};
+use std::path::Path;
+use std::time::Instant;
+
+use clap::ValueEnum;
+use serde::Serialize;
+
+use rustdoku_sudoku::grid::Grid;
+use rustdoku_sudoku::logic;
+use rustdoku_sudoku::solver::alx_solve;
+
+use crate::input;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Solver {
+    /// Dancing links, stopping at the second solution like a uniqueness check
+    Alx,
+    /// Human techniques only, which leaves the hardest sudokus unsolved
+    Logic,
+}
+
+impl fmt::Display for Solver {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.to_possible_value().unwrap().get_name())
+    }
+}
+
+impl Solver {
+    /// Solves the grid, returning whether it was solved.
+    fn solve(self, grid: &Grid) -> bool {
+        match self {
+            Solver::Alx => !alx_solve(grid, 2).is_empty(),
+            Solver::Logic => logic::solve(grid).is_complete(),
+        }
+    }
+}
+
+pub struct Options {
+    pub warmup: usize,
+    pub repetitions: usize,
+    /// How many of the slowest sudokus to list
+    pub slowest: usize,
+    /// Benchmarks only the first sudokus of the file
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Serialize)]
+struct Report {
+    version: &'static str,
+    file: String,
+    sudokus: usize,
+    warmup: usize,
+    repetitions: usize,
+    solvers: Vec<SolverReport>,
+}
+
+#[derive(Debug, Serialize)]
+struct SolverReport {
+    solver: Solver,
+    solved: usize,
+    /// Sudokus per second in the fastest repetition
+    throughput: f64,
+    /// Latencies in microseconds, the fastest of the repetitions for each sudoku
+    mean_us: f64,
+    p50_us: f64,
+    p99_us: f64,
+    max_us: f64,
+    slowest: Vec<Slowest>,
+}
+
+#[derive(Debug, Serialize)]
+struct Slowest {
+    line: usize,
+    sudoku: String,
+    time_us: f64,
+}
+
+/// Rounds microseconds to a tenth, which is as precise as timing single sudokus gets.
+fn micros(seconds: f64) -> f64 {
+    (seconds * 10000000.0).round() / 10.0
+}
+
+/// The nearest-rank percentile of sorted times.
+fn percentile(sorted: &[f64], percentile: f64) -> f64 {
+    let rank = (percentile / 100.0 * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+/// Runs the solver over every sudoku, one at a time so the latencies aren't skewed by other threads.
+fn bench(solver: Solver, sudokus: &[(usize, Grid)], options: &Options) -> SolverReport {
+    for _ in 0..options.warmup {
+        sudokus.iter().for_each(|(_, grid)| {
+            solver.solve(grid);
+        });
+    }
+    let mut times = vec![f64::INFINITY; sudokus.len()];
+    let mut fastest = f64::INFINITY;
+    let mut solved = 0;
+    for _ in 0..options.repetitions {
+        let start = Instant::now();
+        solved = 0;
+        for ((_, grid), time) in sudokus.iter().zip(&mut times) {
+            let start = Instant::now();
+            solved += solver.solve(grid) as usize;
+            *time = time.min(start.elapsed().as_secs_f64());
+        }
+        fastest = fastest.min(start.elapsed().as_secs_f64());
+    }
+    let mut sorted = times.clone();
+    sorted.sort_by(f64::total_cmp);
+    let mut slowest = (0..sudokus.len()).collect::<Vec<usize>>();
+    slowest.sort_by(|a, b| times[*b].total_cmp(&times[*a]));
+    SolverReport {
+        solver,
+        solved,
+        throughput: (sudokus.len() as f64 / fastest * 10.0).round() / 10.0,
+        mean_us: micros(times.iter().sum::<f64>() / times.len() as f64),
+        p50_us: micros(percentile(&sorted, 50.0)),
+        p99_us: micros(percentile(&sorted, 99.0)),
+        max_us: micros(percentile(&sorted, 100.0)),
+        slowest: slowest
+            .into_iter()
+            .take(options.slowest)
+            .map(|index| Slowest {
+                line: sudokus[index].0,
+                sudoku: sudokus[index].1.to_string(),
+                time_us: micros(times[index]),
+            })
+            .collect(),
+    }
+}
+
+fn print(report: &Report) {
+    println!(
+        "Benchmarked {} sudoku{} from {} with {} warmup{} and {} repetition{}\n",
+        report.sudokus,
+        if report.sudokus == 1 { "" } else { "s" },
+        report.file,
+        report.warmup,
+        if report.warmup == 1 { "" } else { "s" },
+        report.repetitions,
+        if report.repetitions == 1 { "" } else { "s" }
+    );
+    println!(
+        "{:<8} {:>8} {:>12} {:>10} {:>10} {:>10} {:>10}",
+        "solver", "solved", "sudokus/s", "mean μs", "p50 μs", "p99 μs", "max μs"
+    );
+    for solver in &report.solvers {
+        println!(
+            "{:<8} {:>8} {:>12.1} {:>10.1} {:>10.1} {:>10.1} {:>10.1}",
+            solver.solver.to_string(),
+            solver.solved,
+            solver.throughput,
+            solver.mean_us,
+            solver.p50_us,
+            solver.p99_us,
+            solver.max_us
+        );
+    }
+    for solver in report.solvers.iter().filter(|solver| !solver.slowest.is_empty()) {
+        println!("\nSlowest for {}:", solver.solver);
+        for slowest in &solver.slowest {
+            println!(
+                "  line {:>6} {:>10.1}μs  {}",
+                slowest.line, slowest.time_us, slowest.sudoku
+            );
+        }
+    }
+}
+
+/// Benchmarks the solvers side by side over the sudokus of the file, printing a table and optionally writing the
+/// report as JSON.
+pub fn run(
+    path: &Path,
+    solvers: &[Solver],
+    options: &Options,
+    json: Option<&Path>,
+) -> Result<(), Box<dyn Error + Sync + Send>> {
+    if options.repetitions == 0 {
+        return Err("At least one repetition is required".into());
+    }
+    let mut sudokus = vec![];
+    for (index, line) in input::open(Some(path))?.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        if options.limit.is_some_and(|limit| sudokus.len() >= limit) {
+            break;
+        }
+        match line.parse::<Grid>() {
+            Ok(grid) => sudokus.push((index + 1, grid)),
+            Err(error) => eprintln!("Line {}: {}", index + 1, error),
+        }
+    }
+    if sudokus.is_empty() {
+        return Err(format!("No sudokus to benchmark in {}", path.display()).into());
+    }
+    let report = Report {
+        version: env!("CARGO_PKG_VERSION"),
+        file: path.display().to_string(),
+        sudokus: sudokus.len(),
+        warmup: options.warmup,
+        repetitions: options.repetitions,
+        solvers: solvers.iter().map(|solver| bench(*solver, &sudokus, options)).collect(),
+    };
+    print(&report);
+    if let Some(json) = json {
+        let mut out = BufWriter::new(File::create(json)?);
+        serde_json::to_writer_pretty(&mut out, &report)?;
+        writeln!(out)?;
+        out.flush()?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SUDOKU: &str = "060000300400700000000000080000008012500600000000000050082000700000500600000010000";
+
+    #[test]
+    fn percentiles() {
+        let percentiles = |sorted: &[f64]| [50.0, 99.0, 100.0].map(|p| percentile(sorted, p));
+        assert_eq!(percentiles(&[5.0]), [5.0, 5.0, 5.0]);
+        assert_eq!(percentiles(&[1.0, 2.0]), [1.0, 2.0, 2.0]);
+        let hundred = (1..=100).map(f64::from).collect::<Vec<f64>>();
+        assert_eq!(percentiles(&hundred), [50.0, 99.0, 100.0]);
+    }
+
+    #[test]
+    fn rounding() {
+        assert_eq!(micros(0.0), 0.0);
+        assert_eq!(micros(0.0000012345), 1.2);
+        assert_eq!(micros(0.00000126), 1.3);
+        assert_eq!(micros(1.5), 1500000.0);
+    }
+
+    #[test]
+    fn benchmarking() {
+        let options = Options {
+            warmup: 0,
+            repetitions: 2,
+            slowest: 3,
+            limit: None,
+        };
+        let report = bench(Solver::Alx, &[(4, SUDOKU.parse().unwrap())], &options);
+        assert_eq!(report.solved, 1);
+        assert_eq!(report.p50_us, report.max_us);
+        assert_eq!(report.slowest.len(), 1);
+        assert_eq!((report.slowest[0].line, report.slowest[0].sudoku.as_str()), (4, SUDOKU));
+    }
+
+    /// The report is compared between commits, so its fields can't be renamed.
+    #[test]
+    fn json_report() {
+        let report = Report {
+            version: "1.0.0",
+            file: "sudokus.txt".to_owned(),
+            sudokus: 1,
+            warmup: 1,
+            repetitions: 3,
+            solvers: vec![SolverReport {
+                solver: Solver::Logic,
+                solved: 1,
+                throughput: 2000.0,
+                mean_us: 500.0,
+                p50_us: 500.0,
+                p99_us: 500.0,
+                max_us: 500.0,
+                slowest: vec![Slowest {
+                    line: 1,
+                    sudoku: SUDOKU.to_owned(),
+                    time_us: 500.0,
+                }],
+            }],
+        };
+        assert_eq!(
+            serde_json::to_string(&report).unwrap(),
+            format!(
+                "{{\"version\":\"1.0.0\",\"file\":\"sudokus.txt\",\"sudokus\":1,\"warmup\":1,\"repetitions\":3,\
+                 \"solvers\":[{{\"solver\":\"logic\",\"solved\":1,\"throughput\":2000.0,\"mean_us\":500.0,\
+                 \"p50_us\":500.0,\"p99_us\":500.0,\"max_us\":500.0,\"slowest\":[{{\"line\":1,\"sudoku\":\"{}\",\
+                 \"time_us\":500.0}}]}}]}}",
+                SUDOKU
+            )
+        );
+    }
+}
@@ -0,0 +1,517 @@
+use std::error::Error;
+use std::fmt::Write as _;
+use std::fs;
+use std::io::BufRead;
+use std::path::{Path, PathBuf};
+
+use clap::ValueEnum;
+use rayon::prelude::*;
+
+use rustdoku_sudoku::coordinates::{CellIndex, Digit};
+use rustdoku_sudoku::grid::Grid;
+use rustdoku_sudoku::logic::rate;
+use rustdoku_sudoku::render::{self, Anchor, Drawing, Marking, Shape, Style};
+use rustdoku_sudoku::solver::alx_solve;
+
+/// A4 in points, the unit of PDF and of the SVG pages.
+const WIDTH: f32 = 595.0;
+const HEIGHT: f32 = 842.0;
+const MARGIN: f32 = 40.0;
+/// Room for the title above the sudokus and the page number below them.
+const HEADER: f32 = 36.0;
+const FOOTER: f32 = 20.0;
+/// Room for the label above each sudoku.
+const LABEL: f32 = 18.0;
+/// The answers are printed small, this many to a page.
+const ANSWERS_PER_PAGE: usize = 12;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum PageFormat {
+    /// One SVG file per page
+    Svg,
+    /// A single PDF document
+    Pdf,
+}
+
+pub struct Options {
+    pub per_page: usize,
+    pub title: String,
+    /// Draws the candidates of empty cells
+    pub candidates: bool,
+    /// Draws the diagonals of X-sudokus
+    pub diagonals: bool,
+    /// Adds the answer key after the sudokus
+    pub answers: bool,
+    pub verbose: bool,
+}
+
+struct Puzzle {
+    grid: Grid,
+    label: String,
+    /// The puzzle filled in with its solution, missing unless the solution is unique
+    answer: Option<Grid>,
+}
+
+impl Puzzle {
+    fn new(number: usize, mut grid: Grid, candidates: bool) -> Puzzle {
+        let solutions = alx_solve(&grid, 2);
+        let answer = match solutions.as_slice() {
+            [solution] => {
+                let mut answer = grid;
+                for cell in CellIndex::all().filter(|cell| grid.get(*cell) == 0) {
+                    answer.set_unchecked(cell, Digit::new(solution.get(cell))).unwrap();
+                }
+                Some(answer)
+            }
+            _ => None,
+        };
+        let rating = rate(&grid);
+        let label = match rating.hardest {
+            _ if answer.is_none() => format!("{}. No unique solution", number),
+            _ if rating.needs_guessing => format!("{}. Score {:.1}, needs guessing", number, rating.score),
+            Some(hardest) => format!("{}. Score {:.1}, {}", number, rating.score, hardest),
+            None => format!("{}. Already solved", number),
+        };
+        if candidates {
+            grid.recompute_all_candidates();
+        }
+        Puzzle { grid, label, answer }
+    }
+}
+
+/// Lays the sudokus out on a page in a grid of slots, each with its label above it.
+fn page(title: &str, sudokus: &[(&str, &Grid)], per_page: usize, style: &Style) -> Drawing {
+    let mut page = Drawing::new(WIDTH, HEIGHT);
+    page.text(MARGIN, MARGIN + 18.0, 18.0, title, true);
+    let columns = match per_page {
+        1 | 2 => 1,
+        3..=6 => 2,
+        7..=12 => 3,
+        _ => 4,
+    };
+    let rows = per_page.div_ceil(columns);
+    let slot_width = (WIDTH - 2.0 * MARGIN) / columns as f32;
+    let slot_height = (HEIGHT - 2.0 * MARGIN - HEADER - FOOTER) / rows as f32;
+    let size = (slot_width * 0.9).min(slot_height - LABEL - 12.0);
+    let label_size = (size / 18.0).clamp(7.0, 12.0);
+    for (index, (label, grid)) in sudokus.iter().enumerate() {
+        let x = MARGIN + (index % columns) as f32 * slot_width + (slot_width - size) / 2.0;
+        let y = MARGIN + HEADER + (index / columns) as f32 * slot_height;
+        page.text(x, y + LABEL - 6.0, label_size, label, false);
+        page.place(
+            &render::draw(grid, render::SIZE, style),
+            x,
+            y + LABEL,
+            size / render::SIZE,
+        );
+    }
+    page
+}
+
+/// Numbers the pages at the bottom once it's known how many there are.
+fn number_pages(pages: &mut [Drawing]) {
+    let count = pages.len();
+    for (index, page) in pages.iter_mut().enumerate() {
+        page.text(
+            MARGIN,
+            HEIGHT - MARGIN,
+            9.0,
+            &format!("Page {} of {}", index + 1, count),
+            false,
+        );
+    }
+}
+
+/// Escapes text for a PDF string in WinAnsiEncoding, which covers Latin-1. Other characters become question marks.
+fn pdf_string(text: &str) -> String {
+    let mut string = String::with_capacity(text.len() + 2);
+    string.push('(');
+    for character in text.chars() {
+        match character {
+            '(' | ')' | '\\' => {
+                string.push('\\');
+                string.push(character);
+            }
+            ' '..='~' => string.push(character),
+            '\u{a0}'..='\u{ff}' => write!(string, "\\{:03o}", character as u32).unwrap(),
+            _ => string.push('?'),
+        }
+    }
+    string.push(')');
+    string
+}
+
+/// The width of text in Helvetica. Only digits are centered, and every digit is 0.556 em wide in both weights.
+fn text_width(text: &str, size: f32) -> f32 {
+    text.chars().count() as f32 * 0.556 * size
+}
+
+/// Turns the shapes into a PDF content stream, flipping the y axis as PDF has its origin at the bottom left.
+fn content(page: &Drawing) -> String {
+    let mut content = String::from("2 J\n");
+    let y = |y: f32| page.height - y;
+    for shape in &page.shapes {
+        match shape {
+            Shape::Line {
+                from,
+                to,
+                width,
+                gray,
+                dashed,
+            } => writeln!(
+                content,
+                "{:.2} w {:.2} G {} 0 d {:.2} {:.2} m {:.2} {:.2} l S",
+                width,
+                gray,
+                if *dashed {
+                    format!("[{0:.2} {0:.2}]", width * 3.0)
+                } else {
+                    "[]".to_owned()
+                },
+                from.0,
+                y(from.1),
+                to.0,
+                y(to.1)
+            ),
+            Shape::Rect {
+                x,
+                y: top,
+                width,
+                height,
+                gray,
+            } => writeln!(
+                content,
+                "{:.2} g {:.2} {:.2} {:.2} {:.2} re f",
+                gray,
+                x,
+                y(top + height),
+                width,
+                height
+            ),
+            Shape::Text {
+                x,
+                y: baseline,
+                size,
+                text,
+                anchor,
+                bold,
+                gray,
+            } => writeln!(
+                content,
+                "{:.2} g BT /{} {:.2} Tf {:.2} {:.2} Td {} Tj ET",
+                gray,
+                if *bold { "F2" } else { "F1" },
+                size,
+                match anchor {
+                    Anchor::Start => *x,
+                    Anchor::Middle => x - text_width(text, *size) / 2.0,
+                },
+                y(*baseline),
+                pdf_string(text)
+            ),
+        }
+        .unwrap();
+    }
+    content
+}
+
+/// Writes the pages as a PDF document using the standard Helvetica fonts, which every reader has built in.
+fn pdf(pages: &[Drawing]) -> Vec<u8> {
+    let fonts = "/Type /Font /Subtype /Type1 /Encoding /WinAnsiEncoding /BaseFont";
+    let mut objects = vec![
+        "<< /Type /Catalog /Pages 2 0 R >>".to_owned(),
+        format!(
+            "<< /Type /Pages /Kids [{}] /Count {} >>",
+            (0..pages.len())
+                .map(|index| format!("{} 0 R", 5 + index * 2))
+                .collect::<Vec<String>>()
+                .join(" "),
+            pages.len()
+        ),
+        format!("<< {} /Helvetica >>", fonts),
+        format!("<< {} /Helvetica-Bold >>", fonts),
+    ];
+    for (index, page) in pages.iter().enumerate() {
+        let content = content(page);
+        objects.push(format!(
+            "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {} {}] /Resources << /Font << /F1 3 0 R /F2 4 0 R >> >> \
+             /Contents {} 0 R >>",
+            page.width,
+            page.height,
+            6 + index * 2
+        ));
+        objects.push(format!("<< /Length {} >>\nstream\n{}endstream", content.len(), content));
+    }
+    let mut pdf = b"%PDF-1.4\n%\xe2\xe3\xcf\xd3\n".to_vec();
+    let mut offsets = vec![];
+    for (index, object) in objects.iter().enumerate() {
+        offsets.push(pdf.len());
+        pdf.extend(format!("{} 0 obj\n{}\nendobj\n", index + 1, object).into_bytes());
+    }
+    let xref = pdf.len();
+    let mut trailer = format!("xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1);
+    for offset in offsets {
+        writeln!(trailer, "{:010} 00000 n ", offset).unwrap();
+    }
+    write!(
+        trailer,
+        "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF\n",
+        objects.len() + 1,
+        xref
+    )
+    .unwrap();
+    pdf.extend(trailer.into_bytes());
+    pdf
+}
+
+/// Where each SVG page goes: the output itself for a single page, otherwise numbered files next to it.
+fn svg_paths(output: &Path, pages: usize) -> Vec<PathBuf> {
+    if pages == 1 {
+        return vec![output.to_owned()];
+    }
+    let stem = output.file_stem().unwrap_or_default().to_string_lossy();
+    (1..=pages)
+        .map(|page| output.with_file_name(format!("{}-{}.svg", stem, page)))
+        .collect()
+}
+
+fn format_of(output: &Path, format: Option<PageFormat>) -> Result<PageFormat, String> {
+    match format {
+        Some(format) => Ok(format),
+        None => match output.extension().and_then(|extension| extension.to_str()) {
+            Some(extension) if extension.eq_ignore_ascii_case("svg") => Ok(PageFormat::Svg),
+            Some(extension) if extension.eq_ignore_ascii_case("pdf") => Ok(PageFormat::Pdf),
+            _ => Err(format!(
+                "Can't tell the format of {} from its extension, pass one with --format",
+                output.display()
+            )),
+        },
+    }
+}
+
+/// Lays the sudokus out on printable pages with their difficulty, followed by an answer key, and writes them as SVG or
+/// PDF.
+pub fn run(
+    reader: impl BufRead,
+    output: &Path,
+    format: Option<PageFormat>,
+    options: &Options,
+) -> Result<(), Box<dyn Error + Sync + Send>> {
+    let format = format_of(output, format)?;
+    if options.per_page == 0 {
+        return Err("At least one sudoku per page is required".into());
+    }
+    let mut grids = vec![];
+    for (index, line) in reader.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let grid: Grid = line.parse().map_err(|error| format!("Line {}: {}", index + 1, error))?;
+        grids.push(grid);
+    }
+    if grids.is_empty() {
+        return Err("No sudokus to render".into());
+    }
+    let puzzles = grids
+        .into_par_iter()
+        .enumerate()
+        .map(|(index, grid)| Puzzle::new(index + 1, grid, options.candidates))
+        .collect::<Vec<Puzzle>>();
+    let markings = if options.diagonals {
+        vec![Marking::Diagonals]
+    } else {
+        vec![]
+    };
+    let style = Style {
+        candidates: options.candidates,
+        markings: markings.clone(),
+    };
+    let mut pages = puzzles
+        .chunks(options.per_page)
+        .map(|chunk| {
+            let sudokus = chunk
+                .iter()
+                .map(|puzzle| (puzzle.label.as_str(), &puzzle.grid))
+                .collect::<Vec<(&str, &Grid)>>();
+            page(&options.title, &sudokus, options.per_page, &style)
+        })
+        .collect::<Vec<Drawing>>();
+    if options.answers {
+        let answers = puzzles
+            .iter()
+            .enumerate()
+            .filter_map(|(index, puzzle)| Some((format!("{}.", index + 1), puzzle.answer.as_ref()?)))
+            .collect::<Vec<(String, &Grid)>>();
+        let style = Style {
+            candidates: false,
+            markings,
+        };
+        let title = format!("{}: Answers", options.title);
+        pages.extend(answers.chunks(ANSWERS_PER_PAGE).map(|chunk| {
+            let sudokus = chunk
+                .iter()
+                .map(|(label, grid)| (label.as_str(), *grid))
+                .collect::<Vec<(&str, &Grid)>>();
+            page(&title, &sudokus, ANSWERS_PER_PAGE, &style)
+        }));
+    }
+    number_pages(&mut pages);
+    let paths = match format {
+        PageFormat::Svg => {
+            let paths = svg_paths(output, pages.len());
+            for (page, path) in pages.iter().zip(&paths) {
+                fs::write(path, page.to_svg())?;
+            }
+            paths
+        }
+        PageFormat::Pdf => {
+            fs::write(output, pdf(&pages))?;
+            vec![output.to_owned()]
+        }
+    };
+    if options.verbose {
+        eprintln!(
+            "Rendered {} sudoku{} on {} page{} into {}",
+            puzzles.len(),
+            if puzzles.len() == 1 { "" } else { "s" },
+            pages.len(),
+            if pages.len() == 1 { "" } else { "s" },
+            paths
+                .iter()
+                .map(|path| path.display().to_string())
+                .collect::<Vec<String>>()
+                .join(", ")
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SUDOKU: &str = "060000300400700000000000080000008012500600000000000050082000700000500600000010000";
+
+    fn find(bytes: &[u8], needle: &[u8]) -> Option<usize> {
+        bytes.windows(needle.len()).position(|window| window == needle)
+    }
+
+    fn number(bytes: &[u8]) -> usize {
+        let digits = bytes.iter().take_while(|byte| byte.is_ascii_digit()).count();
+        std::str::from_utf8(&bytes[..digits]).unwrap().parse().unwrap()
+    }
+
+    fn pages(count: usize) -> Vec<Drawing> {
+        let grid: Grid = SUDOKU.parse().unwrap();
+        let style = Style {
+            candidates: true,
+            markings: vec![],
+        };
+        let mut pages = (0..count)
+            .map(|_| page("Booklet (1)", &[("1. Café \\ tea", &grid)], 1, &style))
+            .collect::<Vec<Drawing>>();
+        number_pages(&mut pages);
+        pages
+    }
+
+    #[test]
+    fn pdf_offsets() {
+        let pdf = pdf(&pages(2));
+        assert!(pdf.ends_with(b"%%EOF\n"));
+        let startxref = find(&pdf, b"startxref\n").unwrap() + "startxref\n".len();
+        let xref = number(&pdf[startxref..]);
+        assert!(pdf[xref..].starts_with(b"xref\n0 "));
+        let count = number(&pdf[xref + "xref\n0 ".len()..]);
+        let entries = xref + format!("xref\n0 {}\n", count).len();
+        assert_eq!(count, 4 + 2 * 2 + 1);
+        for object in 1..count {
+            let entry = &pdf[entries + object * 20..entries + (object + 1) * 20];
+            assert!(entry.ends_with(b" 00000 n \n"));
+            let offset = number(entry);
+            assert!(pdf[offset..].starts_with(format!("{} 0 obj\n", object).as_bytes()));
+        }
+    }
+
+    #[test]
+    fn pdf_lengths() {
+        let pdf = pdf(&pages(2));
+        let mut rest = &pdf[..];
+        let mut streams = 0;
+        while let Some(start) = find(rest, b"/Length ") {
+            rest = &rest[start + "/Length ".len()..];
+            let length = number(rest);
+            let stream = find(rest, b"stream\n").unwrap() + "stream\n".len();
+            assert!(rest[stream + length..].starts_with(b"endstream"));
+            streams += 1;
+        }
+        assert_eq!(streams, 2);
+    }
+
+    #[test]
+    fn pdf_strings() {
+        assert_eq!(pdf_string("Booklet (1)"), r"(Booklet \(1\))");
+        assert_eq!(pdf_string(r"a\b"), r"(a\\b)");
+        assert_eq!(pdf_string("Café ☕"), r"(Caf\351 ?)");
+        assert_eq!(pdf_string("\u{a0}ÿ"), r"(\240\377)");
+    }
+
+    #[test]
+    fn svg_naming() {
+        let output = Path::new("out").join("booklet.svg");
+        assert_eq!(svg_paths(&output, 1), vec![output.clone()]);
+        assert_eq!(
+            svg_paths(&output, 3),
+            (1..=3)
+                .map(|page| Path::new("out").join(format!("booklet-{}.svg", page)))
+                .collect::<Vec<PathBuf>>()
+        );
+        assert_eq!(
+            svg_paths(Path::new("booklet"), 2),
+            vec![PathBuf::from("booklet-1.svg"), PathBuf::from("booklet-2.svg")]
+        );
+    }
+
+    #[test]
+    fn formats() {
+        assert_eq!(format_of(Path::new("booklet.PDF"), None), Ok(PageFormat::Pdf));
+        assert_eq!(format_of(Path::new("booklet.svg"), None), Ok(PageFormat::Svg));
+        assert_eq!(
+            format_of(Path::new("booklet.pdf"), Some(PageFormat::Svg)),
+            Ok(PageFormat::Svg)
+        );
+        assert!(format_of(Path::new("booklet"), None).is_err());
+    }
+
+    #[test]
+    fn svg_pages() {
+        let solution = alx_solve(&SUDOKU.parse().unwrap(), 1)[0].to_string();
+        let grid = solution
+            .chars()
+            .enumerate()
+            .map(|(index, digit)| if index % 10 == 0 { '0' } else { digit })
+            .collect::<String>();
+        let input = format!("{}\n\n{}\n{}\n", grid, grid, solution);
+        let directory = std::env::temp_dir().join(format!("rustdoku-booklet-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        let options = Options {
+            per_page: 2,
+            title: "Booklet".to_owned(),
+            candidates: false,
+            diagonals: false,
+            answers: true,
+            verbose: false,
+        };
+        run(input.as_bytes(), &directory.join("book.svg"), None, &options).unwrap();
+        let mut files = fs::read_dir(&directory)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect::<Vec<String>>();
+        files.sort();
+        assert_eq!(files, ["book-1.svg", "book-2.svg", "book-3.svg"]);
+        let last = fs::read_to_string(directory.join("book-3.svg")).unwrap();
+        assert!(last.contains("Booklet: Answers"));
+        assert!(last.contains("Page 3 of 3"));
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -0,0 +1,361 @@
+use std::collections::HashSet;
+use std::error::Error;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufWriter, Write};
+use std::path::PathBuf;
+use std::time::Instant;
+
+use rayon::prelude::*;
+
+use rustdoku_sudoku::canonical::canonicalize;
+use rustdoku_sudoku::generator;
+use rustdoku_sudoku::grid::Grid;
+use rustdoku_sudoku::logic::rate;
+use rustdoku_sudoku::solver::redundant_givens;
+use rustdoku_sudoku::transform::Symmetry;
+
+pub struct Options {
+    pub givens: usize,
+    pub count: usize,
+    /// Skips sudokus equivalent to an already generated one
+    pub distinct: bool,
+    pub symmetry: Option<Symmetry>,
+    /// Skips sudokus with givens that could be removed
+    pub minimal: bool,
+    /// The band of difficulty scores to keep, inclusive
+    pub min_score: Option<f32>,
+    pub max_score: Option<f32>,
+    /// File to stream the sudokus into, standard output when missing
+    pub output: Option<PathBuf>,
+    /// Keeps the sudokus already in the output file and generates only the missing ones
+    pub resume: bool,
+    /// How many sudokus in a row may be discarded before giving up on filters that can't be met
+    pub max_attempts: usize,
+    pub verbose: bool,
+}
+
+/// Why a generated sudoku was left out.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Discard {
+    NotMinimal,
+    Difficulty,
+}
+
+/// How many generated sudokus were kept and left out so far.
+#[derive(Debug, Default)]
+struct Progress {
+    generated: usize,
+    duplicates: usize,
+    not_minimal: usize,
+    out_of_band: usize,
+    /// Sudokus discarded since the last kept one
+    streak: usize,
+}
+
+impl Progress {
+    fn discarded(&self) -> usize {
+        self.duplicates + self.not_minimal + self.out_of_band
+    }
+}
+
+/// Generates a sudoku and checks it against the filters, returning it with its canonical form when distinct sudokus
+/// are wanted. Duplicates are left for the caller, who sees the sudokus in order.
+fn candidate(options: &Options) -> Result<(Grid, Option<Grid>), Discard> {
+    let grid = match options.symmetry {
+        Some(symmetry) => generator::generate_symmetric(options.givens, symmetry),
+        None => generator::generate(options.givens),
+    };
+    if options.minimal && !redundant_givens(&grid).is_empty() {
+        return Err(Discard::NotMinimal);
+    }
+    if options.min_score.is_some() || options.max_score.is_some() {
+        let score = rate(&grid).score;
+        if options.min_score.is_some_and(|min| score < min) || options.max_score.is_some_and(|max| score > max) {
+            return Err(Discard::Difficulty);
+        }
+    }
+    Ok((grid, options.distinct.then(|| canonicalize(&grid))))
+}
+
+/// Opens the output for writing, first reading back the sudokus already in it when resuming.
+fn open(options: &Options, seen: &mut HashSet<Grid>) -> Result<(Box<dyn Write>, usize), Box<dyn Error + Sync + Send>> {
+    let Some(path) = &options.output else {
+        return Ok((Box::new(BufWriter::new(io::stdout())), 0));
+    };
+    if !options.resume || !path.exists() {
+        return Ok((Box::new(BufWriter::new(File::create(path)?)), 0));
+    }
+    let mut existing = fs::read_to_string(path)?;
+    // An interrupted run can leave the last line unfinished. It's kept when it still holds a whole sudoku, which only
+    // misses its newline, and dropped otherwise.
+    let finished = existing.rfind('\n').map_or(0, |index| index + 1);
+    let tail = existing[finished..].trim();
+    let complete = !tail.is_empty() && tail.parse::<Grid>().is_ok();
+    if !complete {
+        if !tail.is_empty() {
+            eprintln!("Dropping the unfinished last line of {}: {}", path.display(), tail);
+        }
+        existing.truncate(finished);
+    }
+    let mut count = 0;
+    for (index, line) in existing.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let grid: Grid = line
+            .parse()
+            .map_err(|error| format!("Line {} of {}: {}", index + 1, path.display(), error))?;
+        if options.distinct {
+            seen.insert(canonicalize(&grid));
+        }
+        count += 1;
+    }
+    let mut file = OpenOptions::new().append(true).open(path)?;
+    file.set_len(existing.len() as u64)?;
+    if complete {
+        file.write_all(b"\n")?;
+    }
+    Ok((Box::new(BufWriter::new(file)), count))
+}
+
+/// Generates sudokus with `next` in parallel batches until there are `count` of them, writing each batch as soon as
+/// it's done so an interrupted run can be resumed. Fails once `max_attempts` sudokus in a row are discarded, as the
+/// filters likely can't be met.
+fn fill(
+    options: &Options,
+    next: impl Fn() -> Result<(Grid, Option<Grid>), Discard> + Sync,
+    out: &mut dyn Write,
+    seen: &mut HashSet<Grid>,
+    progress: &mut Progress,
+) -> Result<(), Box<dyn Error + Sync + Send>> {
+    let show_progress = options.output.is_some();
+    let start = Instant::now();
+    while progress.generated < options.count {
+        // A batch never makes more sudokus than are still missing, so none that pass are thrown away
+        let batch_size = rayon::current_num_threads().min(options.count - progress.generated);
+        let batch = (0..batch_size)
+            .into_par_iter()
+            .map(|_| next())
+            .collect::<Vec<Result<(Grid, Option<Grid>), Discard>>>();
+        for candidate in batch {
+            if progress.streak == options.max_attempts {
+                break;
+            }
+            progress.streak += 1;
+            match candidate {
+                Ok((_, Some(canonical))) if seen.contains(&canonical) => progress.duplicates += 1,
+                Ok((grid, canonical)) => {
+                    seen.extend(canonical);
+                    writeln!(out, "{}", grid)?;
+                    progress.generated += 1;
+                    progress.streak = 0;
+                }
+                Err(Discard::NotMinimal) => progress.not_minimal += 1,
+                Err(Discard::Difficulty) => progress.out_of_band += 1,
+            }
+        }
+        out.flush()?;
+        if show_progress {
+            eprint!(
+                "\rGenerated {}/{}, discarded {} in {:.1}s",
+                progress.generated,
+                options.count,
+                progress.discarded(),
+                start.elapsed().as_secs_f32()
+            );
+        }
+        if progress.streak == options.max_attempts {
+            if show_progress {
+                eprintln!();
+            }
+            return Err(format!(
+                "Gave up after discarding {} sudokus in a row, the filters may be impossible to meet",
+                options.max_attempts
+            )
+            .into());
+        }
+    }
+    if show_progress {
+        eprintln!();
+    }
+    Ok(())
+}
+
+/// Generates the sudokus into the output, showing progress on standard error when writing to a file.
+pub fn run(options: &Options) -> Result<(), Box<dyn Error + Sync + Send>> {
+    if let Some(symmetry) = options.symmetry.filter(|symmetry| !symmetry.allows(options.givens)) {
+        return Err(format!(
+            "Sudokus with {} symmetry can't have {} givens",
+            symmetry, options.givens
+        )
+        .into());
+    }
+    let mut seen = HashSet::new();
+    let (mut out, existing) = open(options, &mut seen)?;
+    let mut progress = Progress {
+        generated: existing,
+        ..Progress::default()
+    };
+    if options.verbose {
+        eprintln!(
+            "Generating {} unique sudoku{} with {} givens{}",
+            options.count,
+            if options.count == 1 { "" } else { "s" },
+            options.givens,
+            if existing > 0 {
+                format!(", {} already generated", existing)
+            } else {
+                String::new()
+            }
+        );
+    }
+    let start = Instant::now();
+    fill(options, || candidate(options), out.as_mut(), &mut seen, &mut progress)?;
+    if options.verbose {
+        let duration = start.elapsed().as_secs_f32();
+        let count = progress.generated - existing;
+        eprintln!(
+            "Generated {} sudoku{} in {}s, ~{}μs per sudoku, discarding {} duplicate{}, {} not minimal and {} outside \
+             the difficulty band",
+            count,
+            if count == 1 { "" } else { "s" },
+            duration,
+            duration / (count.max(1) as f32) * 1000000.0,
+            progress.duplicates,
+            if progress.duplicates == 1 { "" } else { "s" },
+            progress.not_minimal,
+            progress.out_of_band
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SUDOKUS: [&str; 2] = [
+        "060000300400700000000000080000008012500600000000000050082000700000500600000010000",
+        "000000010400000000020000000000050407008000300001090000300400200050100000000806000",
+    ];
+
+    fn options(output: Option<PathBuf>) -> Options {
+        Options {
+            givens: 28,
+            count: 3,
+            distinct: true,
+            symmetry: None,
+            minimal: false,
+            min_score: None,
+            max_score: None,
+            output,
+            resume: true,
+            max_attempts: 5,
+            verbose: false,
+        }
+    }
+
+    fn grid(index: usize) -> Grid {
+        SUDOKUS[index].parse().unwrap()
+    }
+
+    #[test]
+    fn resuming() {
+        let path = std::env::temp_dir().join(format!("rustdoku-generate-{}.txt", std::process::id()));
+        fs::write(
+            &path,
+            format!("{}\n\n{}\n{}", SUDOKUS[0], SUDOKUS[1], &SUDOKUS[0][..40]),
+        )
+        .unwrap();
+        let options = options(Some(path.clone()));
+        let mut seen = HashSet::new();
+        let (mut out, count) = open(&options, &mut seen).unwrap();
+        assert_eq!(count, 2);
+        assert!(seen.contains(&canonicalize(&grid(0))) && seen.contains(&canonicalize(&grid(1))));
+        writeln!(out, "{}", SUDOKUS[1]).unwrap();
+        drop(out);
+        let lines = [SUDOKUS[0], "", SUDOKUS[1], SUDOKUS[1], ""].join("\n");
+        assert_eq!(fs::read_to_string(&path).unwrap(), lines);
+
+        fs::write(&path, format!("{}\n{}", SUDOKUS[0], SUDOKUS[1])).unwrap();
+        let (mut out, count) = open(&options, &mut HashSet::new()).unwrap();
+        assert_eq!(count, 2);
+        writeln!(out, "{}", SUDOKUS[0]).unwrap();
+        drop(out);
+        let lines = [SUDOKUS[0], SUDOKUS[1], SUDOKUS[0], ""].join("\n");
+        assert_eq!(fs::read_to_string(&path).unwrap(), lines);
+
+        fs::write(&path, format!("{}\n{}\n", SUDOKUS[0], &SUDOKUS[1][..80])).unwrap();
+        assert!(open(&options, &mut HashSet::new()).is_err());
+
+        let options = Options {
+            resume: false,
+            ..options
+        };
+        let (_, count) = open(&options, &mut HashSet::new()).unwrap();
+        assert_eq!(count, 0);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "");
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn duplicates() {
+        let options = options(None);
+        let mut out = vec![];
+        let mut seen = HashSet::new();
+        let mut progress = Progress::default();
+        let next = || Ok((grid(0), Some(grid(0))));
+        let result = fill(&options, next, &mut out, &mut seen, &mut progress);
+        assert!(result.unwrap_err().to_string().contains("5 sudokus in a row"));
+        assert_eq!(String::from_utf8(out).unwrap(), format!("{}\n", SUDOKUS[0]));
+        assert_eq!((progress.generated, progress.duplicates, progress.streak), (1, 5, 5));
+    }
+
+    #[test]
+    fn batches() {
+        let options = options(None);
+        let tries = std::sync::atomic::AtomicUsize::new(0);
+        let next = || {
+            tries.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            Ok((grid(0), None))
+        };
+        let mut out = vec![];
+        let pool = rayon::ThreadPoolBuilder::new().num_threads(8).build().unwrap();
+        pool.install(|| fill(&options, next, &mut out, &mut HashSet::new(), &mut Progress::default()))
+            .unwrap();
+        assert_eq!(String::from_utf8(out).unwrap().lines().count(), 3);
+        assert_eq!(tries.into_inner(), 3);
+    }
+
+    #[test]
+    fn streaks() {
+        let options = Options {
+            count: 2,
+            ..options(None)
+        };
+        let mut out = vec![];
+        let mut progress = Progress::default();
+        let tries = std::sync::atomic::AtomicUsize::new(0);
+        // Only every fourth sudoku makes it through, which resets the streak before it reaches the limit. A single
+        // thread keeps the batches in the order the sudokus are made.
+        let next = || match tries.fetch_add(1, std::sync::atomic::Ordering::SeqCst) % 4 {
+            3 => Ok((grid(1), None)),
+            1 => Err(Discard::NotMinimal),
+            _ => Err(Discard::Difficulty),
+        };
+        let pool = rayon::ThreadPoolBuilder::new().num_threads(1).build().unwrap();
+        pool.install(|| fill(&options, next, &mut out, &mut HashSet::new(), &mut progress))
+            .unwrap();
+        assert_eq!(String::from_utf8(out).unwrap().lines().count(), 2);
+        assert_eq!(
+            (progress.generated, progress.not_minimal, progress.out_of_band),
+            (2, 2, 4)
+        );
+        assert_eq!(progress.streak, 0);
+
+        let mut progress = Progress::default();
+        let next = || Err(Discard::Difficulty);
+        assert!(fill(&options, next, &mut vec![], &mut HashSet::new(), &mut progress).is_err());
+        assert_eq!((progress.generated, progress.out_of_band), (0, 5));
+    }
+}
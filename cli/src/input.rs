@@ -0,0 +1,104 @@
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::path::Path;
+
+use rayon::prelude::*;
+
+/// How many lines are kept in memory per stage of `par_map_lines`.
+const CHUNK: usize = 8192;
+
+/// Opens a puzzle file, or standard input when the path is `-` or missing.
+pub fn open(path: Option<&Path>) -> io::Result<Box<dyn BufRead + Send>> {
+    match path {
+        Some(path) if path != Path::new("-") => Ok(Box::new(BufReader::new(File::open(path)?))),
+        _ => Ok(Box::new(BufReader::new(io::stdin()))),
+    }
+}
+
+/// Turns a line read without its `\n` into a string, dropping the `\r` of a `\r\n` ending like `BufRead::lines`.
+fn decode(mut line: Vec<u8>) -> String {
+    if line.last() == Some(&b'\r') {
+        line.pop();
+    }
+    String::from_utf8(line).unwrap_or_else(|error| String::from_utf8_lossy(error.as_bytes()).into_owned())
+}
+
+/// Maps the non-empty lines of the reader in parallel and hands the results to `output` in input order, returning the
+/// number of lines mapped. The closures get the line number starting from 1.
+///
+/// Bytes that aren't valid UTF-8 are replaced with `U+FFFD`, so such a line fails to parse and gets reported like any
+/// other line that doesn't, instead of stopping the whole input.
+///
+/// Lines are processed a chunk at a time while the previous chunk is written and the next one read, so memory use
+/// stays bounded no matter how long the input is.
+pub fn par_map_lines<R, T, M, O>(reader: R, map: M, mut output: O) -> io::Result<usize>
+where
+    R: BufRead + Send,
+    T: Send,
+    M: Fn(usize, &str) -> T + Sync,
+    O: FnMut(T) -> io::Result<()> + Send,
+{
+    let mut lines = reader
+        .split(b'\n')
+        .map(|line| line.map(decode))
+        .enumerate()
+        .filter(|(_, line)| line.as_ref().map_or(true, |line| !line.trim().is_empty()));
+    let mut read = move || -> io::Result<Vec<(usize, String)>> {
+        lines
+            .by_ref()
+            .take(CHUNK)
+            .map(|(index, line)| Ok((index + 1, line?)))
+            .collect()
+    };
+    let mut count = 0;
+    let mut chunk = read()?;
+    let mut results = vec![];
+    while !chunk.is_empty() {
+        count += chunk.len();
+        let (mapped, next) = rayon::join(
+            || {
+                chunk
+                    .par_iter()
+                    .map(|(line, text)| map(*line, text))
+                    .collect::<Vec<T>>()
+            },
+            || {
+                results.drain(..).try_for_each(&mut output)?;
+                read()
+            },
+        );
+        results = mapped;
+        chunk = next?;
+    }
+    results.into_iter().try_for_each(output)?;
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn invalid_utf8() {
+        let input = b"first\r\n\n\xff\xfe\nlast".as_slice();
+        let mut lines = vec![];
+        let count = par_map_lines(
+            input,
+            |line, text| (line, text.to_owned()),
+            |mapped| {
+                lines.push(mapped);
+                Ok(())
+            },
+        )
+        .unwrap();
+        assert_eq!(count, 3);
+        assert_eq!(
+            lines,
+            [
+                (1, "first".to_owned()),
+                (3, "\u{fffd}\u{fffd}".to_owned()),
+                (4, "last".to_owned())
+            ]
+        );
+    }
+}
@@ -1,13 +1,30 @@
-use std::path::PathBuf;
+mod bench;
+mod booklet;
+mod generate;
+mod input;
+mod output;
+mod play;
+mod serve;
+mod terminal;
+
+use std::io::{self, BufRead, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::time::Duration;
 use std::{error::Error, time::Instant};
 
-use clap::{arg, command, ArgGroup, Parser, Subcommand};
+use clap::{ArgGroup, Parser, Subcommand};
 #[cfg(not(target_family = "wasm"))]
 use mimalloc::MiMalloc;
-use rayon::prelude::*;
 
+use bench::Solver;
+use booklet::PageFormat;
+use output::{Analysis, Analyzed, OutputFormat, Rated, Report, Solved};
+use rustdoku_sudoku::formats::{Format, FormatError};
+use rustdoku_sudoku::grid::Grid;
+use rustdoku_sudoku::logic::{self, Rating};
 use rustdoku_sudoku::solver::alx_solve;
-use rustdoku_sudoku::{generator, grid::Grid};
+use rustdoku_sudoku::transform::{random_equivalent, Symmetry, Transform};
 
 #[cfg(not(target_family = "wasm"))]
 #[global_allocator]
@@ -15,85 +32,216 @@ static GLOBAL: MiMalloc = MiMalloc;
 
 type SolveError = Box<dyn Error + Sync + Send>;
 
-fn solve_file(path: &PathBuf, verbose: bool) -> Result<(), SolveError> {
-    let sudoku_file = std::fs::read_to_string(path)?;
-    let count = sudoku_file.lines().count();
-    if verbose {
-        println!(
-            "Solving {} sudoku{} from '{}'",
-            count,
-            if count == 1 { "" } else { "s" },
-            path.display()
-        );
-    }
+/// Maps every line of the input to a report in parallel and writes the reports in input order. Lines that fail to
+/// parse are reported on standard error and fail the whole run once every line is done.
+///
+/// Without a format the reports are written as csv with the statistics following them on standard output. Once a
+/// format is asked for the statistics go to standard error instead, to keep the output machine readable.
+fn process<T: Report>(
+    reader: impl BufRead + Send,
+    map: impl Fn(usize, &str) -> T + Sync,
+    format: Option<OutputFormat>,
+    verbose: bool,
+    action: &str,
+) -> Result<(), SolveError> {
+    let explicit = format.is_some();
+    let format = format.unwrap_or(OutputFormat::Csv);
+    let mut stdout = BufWriter::new(io::stdout());
+    let mut failed = 0;
     let start = Instant::now();
-    let results: Vec<Result<(Grid, Vec<Grid>), SolveError>> = sudoku_file
-        .par_lines()
-        .map(|sudoku: &str| -> Result<(Grid, Vec<Grid>), SolveError> {
-            let grid: Grid = sudoku.parse()?;
-            Ok((grid, alx_solve(&grid, 0)))
-        })
-        .collect();
+    let result = input::par_map_lines(reader, map, |report| {
+        if let Some(error) = report.error() {
+            eprintln!("Line {}: {}", report.line(), error);
+            failed += 1;
+        }
+        report.write(format, &mut stdout)
+    })
+    .and_then(|count| stdout.flush().map(|_| count));
+    let count = match result {
+        // Whoever reads the output has stopped, like `head` does
+        Err(error) if error.kind() == io::ErrorKind::BrokenPipe => return Ok(()),
+        result => result?,
+    };
     let duration = start.elapsed().as_secs_f32();
-    for result in &results {
-        match result {
-            Ok((grid, solutions)) => {
-                for sudoku in solutions {
-                    println!("{},{}", grid, sudoku);
-                }
-            }
-            Err(error) => println!("{}", error),
-        };
-    }
     if verbose {
-        println!(
-            "Solved {} sudoku{} in {}s, ~{}μs per sudoku",
+        let statistics = format!(
+            "{} {} sudoku{} in {}s, ~{}μs per sudoku",
+            action,
             count,
             if count == 1 { "" } else { "s" },
             duration,
             duration / (count as f32) * 1000000.0
         );
+        if explicit {
+            eprintln!("{}", statistics);
+        } else {
+            println!("{}", statistics);
+        }
+    }
+    if failed > 0 {
+        return Err(format!(
+            "{} of {} sudoku{} failed to parse",
+            failed,
+            count,
+            if count == 1 { "" } else { "s" }
+        )
+        .into());
     }
     Ok(())
 }
 
-fn solve_single(sudoku: &str) -> Result<(), Box<dyn Error + Sync + Send>> {
+/// Prints the solutions of a single sudoku, one per line, stopping after `limit` of them unless it's 0.
+fn solve_single(sudoku: &str, limit: usize) -> Result<(), SolveError> {
     let grid: Grid = sudoku.parse()?;
-    let grids = alx_solve(&grid, 0);
-    for grid in grids {
-        println!("{}", grid);
+    for solution in alx_solve(&grid, limit) {
+        println!("{}", solution);
     }
     Ok(())
 }
 
-fn generate(givens: u8, count: usize, verbose: bool) {
-    if verbose {
-        println!(
-            "Generating {} unique sudoku{} with {} givens",
+/// Analyzes every line of the input and fails unless each sudoku parses, has a unique solution and appears only once,
+/// and with `minimal` has no redundant givens. The summary goes to standard output for pretty output and to standard
+/// error otherwise, to keep the output machine readable.
+fn analyze(reader: impl BufRead + Send, format: OutputFormat, minimal: bool) -> Result<(), SolveError> {
+    let mut stdout = BufWriter::new(io::stdout());
+    let mut analysis = Analysis::new(minimal);
+    let result = input::par_map_lines(reader, Analyzed::analyze, |mut analyzed| {
+        analysis.add(&mut analyzed);
+        if let Some(error) = &analyzed.error {
+            match analyzed.column {
+                Some(column) => eprintln!("Line {}, column {}: {}", analyzed.line, column, error),
+                None => eprintln!("Line {}: {}", analyzed.line, error),
+            }
+        }
+        analyzed.write(format, &mut stdout)
+    })
+    .and_then(|count| stdout.flush().map(|_| count));
+    let count = match result {
+        Err(error) if error.kind() == io::ErrorKind::BrokenPipe => return Ok(()),
+        result => result?,
+    };
+    match format {
+        OutputFormat::Pretty => {
+            println!();
+            analysis.summary.write(&mut io::stdout())?;
+        }
+        _ => analysis.summary.write(&mut io::stderr())?,
+    }
+    let failures = &analysis.failures;
+    if failures.total() > 0 {
+        return Err(format!(
+            "{} of {} sudoku{} failed the analysis: {}",
+            failures.total(),
             count,
             if count == 1 { "" } else { "s" },
-            givens
-        );
+            failures
+        )
+        .into());
     }
-    let start = Instant::now();
-    let generated = (0..count)
-        .into_par_iter()
-        .map(|_| generator::generate(givens as usize))
-        .collect::<Vec<Grid>>();
-    let duration = start.elapsed().as_secs_f32();
-    for sudoku in &generated {
-        println!("{}", sudoku);
+    Ok(())
+}
+
+fn format_of(path: &Path, format: Option<Format>) -> Result<Format, FormatError> {
+    match format {
+        Some(format) => Ok(format),
+        None => Format::from_extension(&path.extension().unwrap_or_default().to_string_lossy()),
+    }
+}
+
+fn convert(
+    input: &Path,
+    output: Option<&Path>,
+    from: Option<Format>,
+    to: Option<Format>,
+    verbose: bool,
+) -> Result<(), Box<dyn Error + Sync + Send>> {
+    let from = format_of(input, from)?;
+    let to = match output {
+        Some(output) => format_of(output, to)?,
+        None => to.ok_or("Output format is required when writing to standard output")?,
+    };
+    let puzzles = from.read(&std::fs::read_to_string(input)?)?;
+    let converted = to.write(&puzzles)?;
+    match output {
+        Some(output) => std::fs::write(output, converted)?,
+        None => print!("{}", converted),
     }
     if verbose {
-        println!(
-            "Generated {} unique sudoku{} with {} givens in {}s, ~{}μs per sudoku",
-            count,
-            if count == 1 { "" } else { "s" },
-            givens,
-            duration,
-            duration / (count as f32) * 1000000.0
+        eprintln!(
+            "Converted {} sudoku{} from {} to {}",
+            puzzles.len(),
+            if puzzles.len() == 1 { "" } else { "s" },
+            from,
+            to
         );
     }
+    Ok(())
+}
+
+fn transform(
+    sudoku: &str,
+    transforms: &[Transform],
+    random: bool,
+    count: usize,
+) -> Result<(), Box<dyn Error + Sync + Send>> {
+    let grid: Grid = sudoku.parse()?;
+    let grid = transforms
+        .iter()
+        .try_fold(grid, |grid, transform| transform.apply(&grid))?;
+    if random {
+        for _ in 0..count {
+            println!("{}", random_equivalent(&grid));
+        }
+    } else {
+        println!("{}", grid);
+    }
+    Ok(())
+}
+
+/// Parses the sudoku with the candidates allowed by its values.
+fn parse_with_candidates(sudoku: &str) -> Result<Grid, SolveError> {
+    let mut grid: Grid = sudoku.parse()?;
+    grid.recompute_all_candidates();
+    Ok(grid)
+}
+
+fn hint(sudoku: &str) -> Result<(), SolveError> {
+    let grid = parse_with_candidates(sudoku)?;
+    println!("{}", grid.to_pencilmark_string());
+    match logic::next_step(&grid) {
+        Some(step) => println!("{}", step),
+        None if grid.is_complete() => println!("The sudoku is already solved"),
+        None => println!("No logical step found, the sudoku needs guessing"),
+    }
+    Ok(())
+}
+
+fn explain(sudoku: &str) -> Result<(), SolveError> {
+    let mut grid = parse_with_candidates(sudoku)?;
+    let solution = logic::solve(&grid);
+    for (index, step) in solution.steps.iter().enumerate() {
+        println!("{}", grid.to_pencilmark_string());
+        println!("Step {}: {}\n", index + 1, step);
+        step.apply(&mut grid)?;
+    }
+    println!("{}", grid.to_pretty_string());
+    let rating = Rating::of(&solution);
+    match rating.hardest {
+        _ if rating.needs_guessing => println!(
+            "Stuck after {} step{}, the rest needs guessing",
+            solution.steps.len(),
+            if solution.steps.len() == 1 { "" } else { "s" }
+        ),
+        Some(hardest) => println!(
+            "Solved in {} step{} with a score of {:.1}, the hardest technique being {}",
+            solution.steps.len(),
+            if solution.steps.len() == 1 { "" } else { "s" },
+            rating.score,
+            hardest
+        ),
+        None => println!("The sudoku is already solved"),
+    }
+    Ok(())
 }
 
 #[derive(Parser)]
@@ -108,18 +256,58 @@ struct Cli {
 
 #[derive(Subcommand)]
 enum Commands {
-    /// Solve sudokus
-    #[command(group(ArgGroup::new("input").required(true).args(["sudoku", "path"])))]
+    /// Solve sudokus, one per line from standard input unless a sudoku or a file is given
+    #[command(group(ArgGroup::new("input").args(["sudoku", "path"])))]
     Solve {
         /// Solves a single sudoku
         #[arg(short, long)]
         sudoku: Option<String>,
 
-        /// Solves sudokus from a file
+        /// Solves sudokus from a file, or from standard input when '-'
+        #[arg(short, long)]
+        path: Option<PathBuf>,
+
+        /// Output format. Without it a single sudoku prints only its solutions and a file prints csv
+        #[arg(short, long, value_enum)]
+        format: Option<OutputFormat>,
+
+        /// Lists up to this many solutions of sudokus with several, searching for no more than that, or every solution
+        /// with 0. Defaults to 2 for files and formatted output, and to every solution of a single sudoku
+        #[arg(long, value_parser = clap::value_parser ! (u16).range(0..=1000))]
+        max_solutions: Option<u16>,
+    },
+    /// Rate the difficulty of sudokus by solving them with human techniques, one per line from standard input unless
+    /// a sudoku or a file is given
+    #[command(group(ArgGroup::new("input").args(["sudoku", "path"])))]
+    Rate {
+        /// Rates a single sudoku
+        #[arg(short, long)]
+        sudoku: Option<String>,
+
+        /// Rates sudokus from a file, or from standard input when '-'
+        #[arg(short, long)]
+        path: Option<PathBuf>,
+
+        /// Output format
+        #[arg(short, long, value_enum, default_value_t = OutputFormat::Csv)]
+        format: OutputFormat,
+    },
+    /// Check a file of sudokus before importing it: parse errors, solution counts, redundant givens, symmetry and
+    /// duplicates, one per line from standard input unless a file is given
+    Analyze {
+        /// Analyzes sudokus from a file, or from standard input when '-'
         #[arg(short, long)]
         path: Option<PathBuf>,
+
+        /// Output format
+        #[arg(short, long, value_enum, default_value_t = OutputFormat::Pretty)]
+        format: OutputFormat,
+
+        /// Fails on sudokus with redundant givens too
+        #[arg(short, long)]
+        minimal: bool,
     },
-    /// Generate sudokus
+    /// Generate sudokus, streaming them into a file with progress when one is given
     Generate {
         /// How many givens to generate
         #[arg(short, long, default_value_t = 28, value_parser = clap::value_parser ! (u8).range(17..81))]
@@ -128,25 +316,326 @@ enum Commands {
         /// How many sudokus to generate
         #[arg(short, long, default_value_t = 1)]
         count: usize,
+
+        /// Skips sudokus equivalent to an already generated one
+        #[arg(short, long)]
+        distinct: bool,
+
+        /// Generates only sudokus whose givens have this symmetry: rotational, quarter-turn, horizontal, vertical,
+        /// diagonal or anti-diagonal
+        #[arg(short, long)]
+        symmetry: Option<Symmetry>,
+
+        /// Skips sudokus with givens that could be removed
+        #[arg(short, long)]
+        minimal: bool,
+
+        /// Skips sudokus rated easier than this score
+        #[arg(long)]
+        min_score: Option<f32>,
+
+        /// Skips sudokus rated harder than this score
+        #[arg(long)]
+        max_score: Option<f32>,
+
+        /// File to write the sudokus into as they're generated
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+
+        /// Keeps the sudokus already in the output file, counting them towards the total
+        #[arg(short, long, requires = "output")]
+        resume: bool,
+
+        /// Gives up after discarding this many sudokus in a row
+        #[arg(long, default_value_t = 1000, value_parser = clap::value_parser ! (u64).range(1..))]
+        max_attempts: u64,
     },
-}
+    /// Convert sudokus between the plain, sdk, ss, opensudoku and hodoku formats
+    Convert {
+        /// File to convert
+        #[arg(short, long)]
+        input: PathBuf,
+
+        /// File to write, printed when missing
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+
+        /// Format of the input, guessed from the file extension by default
+        #[arg(short, long)]
+        from: Option<Format>,
+
+        /// Format of the output, guessed from the file extension by default
+        #[arg(short, long)]
+        to: Option<Format>,
+    },
+    /// Show the next logical step of a sudoku
+    Hint {
+        /// Sudoku to give a hint for
+        sudoku: String,
+    },
+    /// Show every logical step solving a sudoku
+    Explain {
+        /// Sudoku to explain
+        sudoku: String,
+    },
+    /// Benchmark solvers side by side over a file of sudokus, one per line
+    Bench {
+        /// File to benchmark
+        #[arg(short, long, default_value = "sudoku17")]
+        path: PathBuf,
+
+        /// Solvers to compare
+        #[arg(short, long, value_enum, default_values_t = [Solver::Alx])]
+        solver: Vec<Solver>,
+
+        /// Untimed runs over the file before measuring
+        #[arg(short, long, default_value_t = 1)]
+        warmup: usize,
+
+        /// Timed runs over the file, the fastest time of each sudoku counting
+        #[arg(short, long, default_value_t = 3)]
+        repetitions: usize,
+
+        /// How many of the slowest sudokus to list
+        #[arg(long, default_value_t = 10)]
+        slowest: usize,
+
+        /// Benchmarks only this many sudokus from the start of the file
+        #[arg(short, long)]
+        limit: Option<usize>,
+
+        /// Writes the report as JSON to this file
+        #[arg(short, long)]
+        json: Option<PathBuf>,
+    },
+    /// Lay out sudokus on printable pages with their difficulty and an answer key, one per line from standard input
+    /// unless a file is given
+    Render {
+        /// Renders sudokus from a file, or from standard input when '-'
+        #[arg(short, long)]
+        path: Option<PathBuf>,
+
+        /// File to write, with SVG pages going into numbered files next to it when there are several
+        #[arg(short, long)]
+        output: PathBuf,
+
+        /// Format of the output, guessed from the file extension by default
+        #[arg(short, long, value_enum)]
+        format: Option<PageFormat>,
+
+        /// How many sudokus to put on each page
+        #[arg(short = 'n', long, default_value_t = 4, value_parser = clap::value_parser ! (u8).range(1..17))]
+        per_page: u8,
+
+        /// Title at the top of every page
+        #[arg(short, long, default_value = "Sudoku")]
+        title: String,
+
+        /// Draws the candidates of the empty cells
+        #[arg(short, long)]
+        candidates: bool,
+
+        /// Draws the diagonals of X-sudokus
+        #[arg(short, long)]
+        diagonals: bool,
+
+        /// Leaves out the answer key
+        #[arg(long)]
+        no_answers: bool,
+    },
+    /// Play a sudoku in the terminal
+    Play {
+        /// Sudoku to play, generated when missing
+        #[arg(short, long)]
+        sudoku: Option<String>,
+
+        /// How many givens to generate
+        #[arg(short, long, default_value_t = 28, value_parser = clap::value_parser ! (u8).range(17..81))]
+        givens: u8,
+    },
+    /// Serve solve, generate, rate, hint, validate and canonicalize as a JSON API over HTTP, each a POST endpoint
+    /// named after it
+    Serve {
+        /// Port to listen on
+        #[arg(short, long, default_value_t = 8080)]
+        port: u16,
+
+        /// Address to listen on
+        #[arg(short, long, default_value = "127.0.0.1")]
+        address: String,
+
+        /// How many requests are handled at once, the number of CPUs by default
+        #[arg(short, long)]
+        threads: Option<usize>,
+
+        /// Largest accepted request body in bytes
+        #[arg(short, long, default_value_t = 65536)]
+        max_body: usize,
+
+        /// How many answers are computed at once before refusing requests, twice the number of threads by default
+        #[arg(long)]
+        max_jobs: Option<usize>,
+
+        /// Seconds allowed for each request as a whole, from reading it to writing the answer
+        #[arg(long, default_value_t = 10.0)]
+        timeout: f64,
+    },
+    /// Apply validity preserving transformations to a sudoku
+    Transform {
+        /// Sudoku to transform
+        #[arg(short, long)]
+        sudoku: String,
 
-fn main() -> Result<(), Box<dyn Error + Sync + Send>> {
-    let cli = Cli::parse();
+        /// Transformations applied in order: rotate, mirror-horizontal, mirror-vertical, transpose, digits=912345678,
+        /// bands=0,2, stacks=0,2, rows=0,2 or columns=0,2
+        #[arg(short, long)]
+        transform: Vec<Transform>,
+
+        /// Prints random equivalent sudokus of the transformed sudoku
+        #[arg(short, long)]
+        random: bool,
+
+        /// How many random sudokus to print
+        #[arg(short, long, default_value_t = 1, requires = "random")]
+        count: usize,
+    },
+}
 
+fn run(cli: Cli) -> Result<(), Box<dyn Error + Sync + Send>> {
     match cli.command {
-        Commands::Solve { sudoku, path } => {
-            if let Some(sudoku) = sudoku {
-                solve_single(&sudoku)
-            } else if let Some(path) = path {
-                solve_file(&path, cli.verbose)
-            } else {
-                Ok(())
+        Commands::Solve {
+            sudoku,
+            path,
+            format,
+            max_solutions,
+        } => {
+            // Streamed lines are searched for two solutions unless asked otherwise, or a line with a huge number of
+            // solutions would hold up the whole stream while its solutions pile up in memory
+            let listed = max_solutions.map_or(2, usize::from);
+            let solve = |line, sudoku: &str| Solved::solve(line, sudoku, listed);
+            match (sudoku, format) {
+                (Some(sudoku), None) => solve_single(&sudoku, max_solutions.map_or(0, usize::from)),
+                (Some(sudoku), format) => process(sudoku.as_bytes(), solve, format, cli.verbose, "Solved"),
+                (None, format) => process(input::open(path.as_deref())?, solve, format, cli.verbose, "Solved"),
             }
         }
-        Commands::Generate { givens, count } => {
-            generate(givens, count, cli.verbose);
+        Commands::Rate { sudoku, path, format } => match sudoku {
+            Some(sudoku) => process(sudoku.as_bytes(), Rated::rate, Some(format), cli.verbose, "Rated"),
+            None => process(
+                input::open(path.as_deref())?,
+                Rated::rate,
+                Some(format),
+                cli.verbose,
+                "Rated",
+            ),
+        },
+        Commands::Analyze { path, format, minimal } => analyze(input::open(path.as_deref())?, format, minimal),
+        Commands::Generate {
+            givens,
+            count,
+            distinct,
+            symmetry,
+            minimal,
+            min_score,
+            max_score,
+            output,
+            resume,
+            max_attempts,
+        } => generate::run(&generate::Options {
+            givens: givens as usize,
+            count,
+            distinct,
+            symmetry,
+            minimal,
+            min_score,
+            max_score,
+            output,
+            resume,
+            max_attempts: max_attempts as usize,
+            verbose: cli.verbose,
+        }),
+        Commands::Convert {
+            input,
+            output,
+            from,
+            to,
+        } => convert(&input, output.as_deref(), from, to, cli.verbose),
+        Commands::Transform {
+            sudoku,
+            transform: transforms,
+            random,
+            count,
+        } => transform(&sudoku, &transforms, random, count),
+        Commands::Hint { sudoku } => hint(&sudoku),
+        Commands::Explain { sudoku } => explain(&sudoku),
+        Commands::Bench {
+            path,
+            solver,
+            warmup,
+            repetitions,
+            slowest,
+            limit,
+            json,
+        } => {
+            let options = bench::Options {
+                warmup,
+                repetitions,
+                slowest,
+                limit,
+            };
+            bench::run(&path, &solver, &options, json.as_deref())
+        }
+        Commands::Render {
+            path,
+            output,
+            format,
+            per_page,
+            title,
+            candidates,
+            diagonals,
+            no_answers,
+        } => booklet::run(
+            input::open(path.as_deref())?,
+            &output,
+            format,
+            &booklet::Options {
+                per_page: per_page as usize,
+                title,
+                candidates,
+                diagonals,
+                answers: !no_answers,
+                verbose: cli.verbose,
+            },
+        ),
+        Commands::Play { sudoku, givens } => play::run(sudoku.as_deref(), givens as usize),
+        Commands::Serve {
+            port,
+            address,
+            threads,
+            max_body,
+            max_jobs,
+            timeout,
+        } => {
+            let threads = threads.unwrap_or_else(rayon::current_num_threads).max(1);
+            let config = serve::Config {
+                threads,
+                max_body,
+                max_jobs: max_jobs.unwrap_or(threads * 2).max(1),
+                timeout: Duration::try_from_secs_f64(timeout)?,
+                verbose: cli.verbose,
+            };
+            serve::run(&address, port, config)?;
             Ok(())
         }
     }
 }
+
+fn main() -> ExitCode {
+    match run(Cli::parse()) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("Error: {}", error);
+            ExitCode::FAILURE
+        }
+    }
+}
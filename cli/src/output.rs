@@ -0,0 +1,660 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::io::{self, Write};
+use std::time::Instant;
+
+use clap::ValueEnum;
+use serde::{Serialize, Serializer};
+
+use rustdoku_sudoku::canonical::canonicalize;
+use rustdoku_sudoku::coordinates::CellIndex;
+use rustdoku_sudoku::grid::{Grid, ParseError};
+use rustdoku_sudoku::logic::{rate, Rating, Technique};
+use rustdoku_sudoku::solver::{alx_solve, redundant_givens};
+use rustdoku_sudoku::transform::Symmetry;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    /// `puzzle,solution` for every listed solution
+    Csv,
+    /// One JSON object per puzzle
+    Jsonl,
+    /// Boxed boards for reading in a terminal
+    Pretty,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Status {
+    Unique,
+    Multiple,
+    None,
+    ParseError,
+}
+
+impl Status {
+    /// The status of a puzzle with the given number of solutions.
+    pub fn of(solutions: usize) -> Status {
+        match solutions {
+            0 => Status::None,
+            1 => Status::Unique,
+            _ => Status::Multiple,
+        }
+    }
+}
+
+/// The outcome of processing a single line of input.
+pub trait Report: Send {
+    /// The parse error of the line, if any
+    fn error(&self) -> Option<&str>;
+
+    fn line(&self) -> usize;
+
+    /// Writes the report to `out`. Parse errors are reported on standard error by the caller in every format.
+    fn write(&self, format: OutputFormat, out: &mut impl Write) -> io::Result<()>;
+}
+
+/// The outcome of solving a single line of input.
+#[derive(Debug, Serialize)]
+pub struct Solved {
+    pub line: usize,
+    /// The parsed puzzle, or the line itself when it doesn't parse
+    pub puzzle: String,
+    #[serde(skip)]
+    pub grid: Option<Grid>,
+    pub status: Status,
+    /// Solutions found before the search stopped, which is at least 2 for multiple solutions
+    pub solution_count: usize,
+    /// The first solutions found, up to the requested number
+    #[serde(serialize_with = "to_strings")]
+    pub solutions: Vec<Grid>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Time spent parsing and solving in microseconds, rounded to a tenth
+    pub time_us: f64,
+}
+
+impl Solved {
+    /// Solves the sudoku, keeping every solution when `max_solutions` is 0 and at most that many otherwise. A limited
+    /// search stops after two solutions unless more are wanted, which is enough to tell a unique solution from several,
+    /// so the count of a sudoku with multiple solutions is capped at the limit.
+    pub fn solve(line: usize, sudoku: &str, max_solutions: usize) -> Self {
+        let start = Instant::now();
+        let limit = if max_solutions == 0 { 0 } else { max_solutions.max(2) };
+        let (grid, result) = match sudoku.parse::<Grid>() {
+            Ok(grid) => (Some(grid), Ok(alx_solve(&grid, limit))),
+            Err(error) => (None, Err(error.to_string())),
+        };
+        let time_us = (start.elapsed().as_secs_f64() * 10000000.0).round() / 10.0;
+        let (status, solution_count, mut solutions, error) = match result {
+            Ok(solutions) => (Status::of(solutions.len()), solutions.len(), solutions, None),
+            Err(error) => (Status::ParseError, 0, vec![], Some(error)),
+        };
+        if max_solutions > 0 {
+            solutions.truncate(max_solutions);
+        }
+        Solved {
+            line,
+            puzzle: grid.map_or_else(|| sudoku.trim().to_owned(), |grid| grid.to_string()),
+            grid,
+            status,
+            solution_count,
+            solutions,
+            error,
+            time_us,
+        }
+    }
+}
+
+impl Report for Solved {
+    fn error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
+
+    fn line(&self) -> usize {
+        self.line
+    }
+
+    fn write(&self, format: OutputFormat, out: &mut impl Write) -> io::Result<()> {
+        match format {
+            OutputFormat::Csv => {
+                for solution in &self.solutions {
+                    writeln!(out, "{},{}", self.puzzle, solution)?;
+                }
+            }
+            OutputFormat::Jsonl => {
+                serde_json::to_writer(&mut *out, self)?;
+                writeln!(out)?;
+            }
+            OutputFormat::Pretty => {
+                let Some(grid) = self.grid else {
+                    return Ok(());
+                };
+                let status = match self.status {
+                    Status::Unique => "unique solution",
+                    Status::Multiple => "multiple solutions",
+                    _ => "no solution",
+                };
+                writeln!(out, "Line {}: {} in {:.0}μs", self.line, status, self.time_us)?;
+                writeln!(out, "{}", grid.to_pretty_string())?;
+                for (index, solution) in self.solutions.iter().enumerate() {
+                    writeln!(out, "Solution {}:\n{}", index + 1, solution.to_pretty_string())?;
+                }
+                writeln!(out)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The difficulty rating of a single line of input.
+#[derive(Debug, Serialize)]
+pub struct Rated {
+    pub line: usize,
+    /// The parsed puzzle, or the line itself when it doesn't parse
+    pub puzzle: String,
+    #[serde(flatten)]
+    pub rating: Option<RatingReport>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RatingReport {
+    pub score: f32,
+    #[serde(serialize_with = "to_string")]
+    pub hardest: Option<Technique>,
+    pub needs_guessing: bool,
+    /// Steps taken with each technique, from the easiest technique to the hardest
+    #[serde(serialize_with = "to_string_keys")]
+    pub counts: BTreeMap<Technique, usize>,
+}
+
+impl From<Rating> for RatingReport {
+    fn from(rating: Rating) -> Self {
+        RatingReport {
+            score: rating.score,
+            hardest: rating.hardest,
+            needs_guessing: rating.needs_guessing,
+            counts: rating.counts,
+        }
+    }
+}
+
+impl Rated {
+    pub fn rate(line: usize, sudoku: &str) -> Self {
+        match sudoku.parse::<Grid>() {
+            Ok(grid) => Rated {
+                line,
+                puzzle: grid.to_string(),
+                rating: Some(rate(&grid).into()),
+                error: None,
+            },
+            Err(error) => Rated {
+                line,
+                puzzle: sudoku.trim().to_owned(),
+                rating: None,
+                error: Some(error.to_string()),
+            },
+        }
+    }
+}
+
+impl Report for Rated {
+    fn error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
+
+    fn line(&self) -> usize {
+        self.line
+    }
+
+    fn write(&self, format: OutputFormat, out: &mut impl Write) -> io::Result<()> {
+        match (format, &self.rating) {
+            (OutputFormat::Jsonl, _) => {
+                serde_json::to_writer(&mut *out, self)?;
+                writeln!(out)
+            }
+            (_, None) => Ok(()),
+            (OutputFormat::Csv, Some(rating)) => writeln!(
+                out,
+                "{},{:.1},{},{},{}",
+                self.puzzle,
+                rating.score,
+                rating
+                    .hardest
+                    .map(|technique| technique.to_string())
+                    .unwrap_or_default(),
+                rating.needs_guessing,
+                rating
+                    .counts
+                    .iter()
+                    .map(|(technique, count)| format!("{}={}", technique, count))
+                    .collect::<Vec<String>>()
+                    .join(";")
+            ),
+            (OutputFormat::Pretty, Some(rating)) => {
+                writeln!(
+                    out,
+                    "Line {}: {}\nScore {:.1}, hardest technique {}{}",
+                    self.line,
+                    self.puzzle,
+                    rating.score,
+                    rating
+                        .hardest
+                        .map_or("none".to_owned(), |technique| technique.to_string()),
+                    if rating.needs_guessing { ", needs guessing" } else { "" }
+                )?;
+                for (technique, count) in &rating.counts {
+                    writeln!(out, "  {:<14} {}", technique.to_string(), count)?;
+                }
+                writeln!(out)
+            }
+        }
+    }
+}
+
+/// The checks of a single line of input, for vetting puzzle collections.
+#[derive(Debug, Serialize)]
+pub struct Analyzed {
+    pub line: usize,
+    /// The parsed puzzle, or the line itself when it doesn't parse
+    pub puzzle: String,
+    pub status: Status,
+    /// Where in the line parsing failed, counting characters from 1
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub column: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub givens: usize,
+    #[serde(serialize_with = "to_strings")]
+    pub symmetries: Vec<Symmetry>,
+    /// Givens whose removal keeps the solution unique, only looked for in sudokus with a unique solution
+    #[serde(serialize_with = "to_strings")]
+    pub redundant: Vec<CellIndex>,
+    /// The first earlier line with an equivalent sudoku, filled in by the caller who sees the lines in order
+    pub duplicate_of: Option<usize>,
+    #[serde(skip)]
+    pub canonical: Option<Grid>,
+}
+
+/// The character position of the parse error within the line, if it points at one.
+fn error_column(sudoku: &str, error: &ParseError) -> Option<usize> {
+    let index = match *error {
+        ParseError::InvalidDigit(_, index) => index,
+        ParseError::InvalidSudoku(cell) => {
+            sudoku
+                .char_indices()
+                .filter(|(_, c)| c.is_ascii_digit() || *c == '.')
+                .nth(cell)?
+                .0
+        }
+        _ => return None,
+    };
+    Some(sudoku[..index].chars().count() + 1)
+}
+
+impl Analyzed {
+    pub fn analyze(line: usize, sudoku: &str) -> Self {
+        let grid = match sudoku.parse::<Grid>() {
+            Ok(grid) => grid,
+            Err(error) => {
+                let parse_error = error.downcast_ref::<ParseError>();
+                let message = match parse_error {
+                    Some(ParseError::InvalidSudoku(cell)) => format!(
+                        "The given in {} contradicts the givens before it",
+                        CellIndex::new(*cell).unwrap()
+                    ),
+                    _ => error.to_string(),
+                };
+                return Analyzed {
+                    line,
+                    puzzle: sudoku.trim().to_owned(),
+                    status: Status::ParseError,
+                    column: parse_error.and_then(|error| error_column(sudoku, error)),
+                    error: Some(message),
+                    givens: 0,
+                    symmetries: vec![],
+                    redundant: vec![],
+                    duplicate_of: None,
+                    canonical: None,
+                };
+            }
+        };
+        let status = Status::of(alx_solve(&grid, 2).len());
+        let redundant = match status {
+            Status::Unique => redundant_givens(&grid),
+            _ => vec![],
+        };
+        Analyzed {
+            line,
+            puzzle: grid.to_string(),
+            status,
+            column: None,
+            error: None,
+            givens: grid.cells().filter(|value| *value > 0).count(),
+            symmetries: Symmetry::of(&grid),
+            redundant,
+            duplicate_of: None,
+            canonical: Some(canonicalize(&grid)),
+        }
+    }
+}
+
+impl Report for Analyzed {
+    fn error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
+
+    fn line(&self) -> usize {
+        self.line
+    }
+
+    fn write(&self, format: OutputFormat, out: &mut impl Write) -> io::Result<()> {
+        match format {
+            OutputFormat::Jsonl => {
+                serde_json::to_writer(&mut *out, self)?;
+                writeln!(out)
+            }
+            OutputFormat::Csv => writeln!(
+                out,
+                "{},{},{},{},{},{},{}",
+                self.line,
+                self.puzzle,
+                serde_json::to_value(self.status)?.as_str().unwrap_or_default(),
+                self.givens,
+                self.symmetries
+                    .iter()
+                    .map(Symmetry::to_string)
+                    .collect::<Vec<String>>()
+                    .join(";"),
+                self.redundant
+                    .iter()
+                    .map(CellIndex::to_string)
+                    .collect::<Vec<String>>()
+                    .join(";"),
+                self.duplicate_of.map(|line| line.to_string()).unwrap_or_default()
+            ),
+            OutputFormat::Pretty => {
+                if self.error.is_some() {
+                    return Ok(());
+                }
+                let mut notes = vec![
+                    match self.status {
+                        Status::Unique => "unique solution".to_owned(),
+                        Status::Multiple => "multiple solutions".to_owned(),
+                        _ => "no solution".to_owned(),
+                    },
+                    format!("{} givens", self.givens),
+                ];
+                notes.push(match self.symmetries.as_slice() {
+                    [] => "no symmetry".to_owned(),
+                    symmetries => format!(
+                        "{} symmetry",
+                        symmetries
+                            .iter()
+                            .map(Symmetry::to_string)
+                            .collect::<Vec<String>>()
+                            .join(", ")
+                    ),
+                });
+                if !self.redundant.is_empty() {
+                    notes.push(format!(
+                        "not minimal, redundant givens in {}",
+                        self.redundant
+                            .iter()
+                            .map(CellIndex::to_string)
+                            .collect::<Vec<String>>()
+                            .join(", ")
+                    ));
+                }
+                if let Some(line) = self.duplicate_of {
+                    notes.push(format!("duplicate of line {}", line));
+                }
+                writeln!(out, "Line {}: {}", self.line, notes.join(", "))
+            }
+        }
+    }
+}
+
+/// Counts over every analyzed line, printed as a table once the analysis is done.
+#[derive(Debug, Default)]
+pub struct Summary {
+    pub sudokus: usize,
+    pub parse_errors: usize,
+    pub unique: usize,
+    pub none: usize,
+    pub multiple: usize,
+    pub not_minimal: usize,
+    pub duplicates: usize,
+    pub symmetric: BTreeMap<Symmetry, usize>,
+    pub asymmetric: usize,
+    /// The fewest and most givens of the sudokus that parsed
+    pub givens: Option<(usize, usize)>,
+    pub total_givens: usize,
+}
+
+impl Summary {
+    pub fn add(&mut self, analyzed: &Analyzed) {
+        self.sudokus += 1;
+        match analyzed.status {
+            Status::ParseError => {
+                self.parse_errors += 1;
+                return;
+            }
+            Status::Unique => self.unique += 1,
+            Status::None => self.none += 1,
+            Status::Multiple => self.multiple += 1,
+        }
+        self.not_minimal += !analyzed.redundant.is_empty() as usize;
+        self.duplicates += analyzed.duplicate_of.is_some() as usize;
+        for symmetry in &analyzed.symmetries {
+            *self.symmetric.entry(*symmetry).or_default() += 1;
+        }
+        self.asymmetric += analyzed.symmetries.is_empty() as usize;
+        self.givens = Some(match self.givens {
+            Some((fewest, most)) => (fewest.min(analyzed.givens), most.max(analyzed.givens)),
+            None => (analyzed.givens, analyzed.givens),
+        });
+        self.total_givens += analyzed.givens;
+    }
+
+    pub fn write(&self, out: &mut impl Write) -> io::Result<()> {
+        let rows = [
+            ("Sudokus", self.sudokus),
+            ("Parse errors", self.parse_errors),
+            ("Unique solution", self.unique),
+            ("No solution", self.none),
+            ("Multiple solutions", self.multiple),
+            ("Not minimal", self.not_minimal),
+            ("Duplicates", self.duplicates),
+        ];
+        for (name, count) in rows {
+            writeln!(out, "{:<24} {:>8}", name, count)?;
+        }
+        if let Some((fewest, most)) = self.givens {
+            let parsed = self.sudokus - self.parse_errors;
+            writeln!(
+                out,
+                "{:<24} {:>8.1} ({} to {})",
+                "Givens on average",
+                self.total_givens as f64 / parsed as f64,
+                fewest,
+                most
+            )?;
+        }
+        for (symmetry, count) in &self.symmetric {
+            writeln!(out, "{:<24} {:>8}", format!("Symmetry {}", symmetry), count)?;
+        }
+        writeln!(out, "{:<24} {:>8}", "No symmetry", self.asymmetric)
+    }
+}
+
+/// The lines that failed the analysis, each counted under the first check it failed.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Failures {
+    pub parse_errors: usize,
+    pub not_unique: usize,
+    pub duplicates: usize,
+    pub not_minimal: usize,
+}
+
+impl Failures {
+    pub fn total(&self) -> usize {
+        self.parse_errors + self.not_unique + self.duplicates + self.not_minimal
+    }
+}
+
+/// Lists the reasons with their counts, such as `1 parse error, 2 duplicates`.
+impl fmt::Display for Failures {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let reasons = [
+            (self.parse_errors, "parse error", "parse errors"),
+            (
+                self.not_unique,
+                "without a unique solution",
+                "without a unique solution",
+            ),
+            (self.duplicates, "duplicate", "duplicates"),
+            (self.not_minimal, "not minimal", "not minimal"),
+        ];
+        let reasons = reasons
+            .iter()
+            .filter(|(count, _, _)| *count > 0)
+            .map(|(count, one, many)| format!("{} {}", count, if *count == 1 { one } else { many }))
+            .collect::<Vec<String>>();
+        write!(f, "{}", reasons.join(", "))
+    }
+}
+
+/// Follows the analyzed lines in order, marking duplicates of earlier lines and counting them into the summary.
+#[derive(Debug, Default)]
+pub struct Analysis {
+    /// Fails sudokus with redundant givens
+    pub minimal: bool,
+    /// The first line of every canonical sudoku so far
+    seen: HashMap<Grid, usize>,
+    pub summary: Summary,
+    pub failures: Failures,
+}
+
+impl Analysis {
+    pub fn new(minimal: bool) -> Self {
+        Analysis {
+            minimal,
+            ..Default::default()
+        }
+    }
+
+    /// Fills in the line's `duplicate_of` and counts it. A sudoku is fit for a collection when it parses, has a unique
+    /// solution, isn't a duplicate and, with `minimal`, has no redundant givens.
+    pub fn add(&mut self, analyzed: &mut Analyzed) {
+        if let Some(canonical) = analyzed.canonical {
+            let first = *self.seen.entry(canonical).or_insert(analyzed.line);
+            analyzed.duplicate_of = (first != analyzed.line).then_some(first);
+        }
+        self.summary.add(analyzed);
+        let failures = &mut self.failures;
+        match analyzed.status {
+            Status::ParseError => failures.parse_errors += 1,
+            Status::None | Status::Multiple => failures.not_unique += 1,
+            Status::Unique if analyzed.duplicate_of.is_some() => failures.duplicates += 1,
+            Status::Unique if self.minimal && !analyzed.redundant.is_empty() => failures.not_minimal += 1,
+            Status::Unique => {}
+        }
+    }
+}
+
+fn to_string<T: ToString, S: Serializer>(value: &Option<T>, serializer: S) -> Result<S::Ok, S::Error> {
+    match value {
+        Some(value) => serializer.serialize_some(&value.to_string()),
+        None => serializer.serialize_none(),
+    }
+}
+
+fn to_string_keys<K: ToString, S: Serializer>(map: &BTreeMap<K, usize>, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_map(map.iter().map(|(key, value)| (key.to_string(), value)))
+}
+
+fn to_strings<T: ToString, S: Serializer>(values: &[T], serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_seq(values.iter().map(T::to_string))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rustdoku_sudoku::transform::Transform;
+
+    const SUDOKU: &str = "060000300400700000000000080000008012500600000000000050082000700000500600000010000";
+
+    fn analyze(lines: &[&str], minimal: bool) -> (Analysis, Vec<Analyzed>) {
+        let mut analysis = Analysis::new(minimal);
+        let analyzed = lines
+            .iter()
+            .enumerate()
+            .map(|(index, sudoku)| {
+                let mut analyzed = Analyzed::analyze(index + 1, sudoku);
+                analysis.add(&mut analyzed);
+                analyzed
+            })
+            .collect();
+        (analysis, analyzed)
+    }
+
+    #[test]
+    fn solution_limits() {
+        let empty = "0".repeat(81);
+        let solved = Solved::solve(1, &empty, 2);
+        assert_eq!((solved.status, solved.solution_count), (Status::Multiple, 2));
+        assert_eq!(solved.solutions.len(), 2);
+        let solved = Solved::solve(1, &empty, 1);
+        assert_eq!(
+            (solved.status, solved.solution_count, solved.solutions.len()),
+            (Status::Multiple, 2, 1)
+        );
+        let solved = Solved::solve(1, SUDOKU, 0);
+        assert_eq!((solved.status, solved.solution_count), (Status::Unique, 1));
+    }
+
+    #[test]
+    fn parse_errors() {
+        let (analysis, analyzed) = analyze(&[&SUDOKU.replacen('6', "x", 1), &format!("11{}", &SUDOKU[2..])], false);
+        assert_eq!((analyzed[0].status, analyzed[0].column), (Status::ParseError, Some(2)));
+        assert_eq!(analyzed[1].column, Some(2));
+        assert_eq!(
+            analyzed[1].error.as_deref(),
+            Some("The given in r1c2 contradicts the givens before it")
+        );
+        assert_eq!(analysis.failures.parse_errors, 2);
+        assert_eq!(analysis.summary.parse_errors, 2);
+    }
+
+    #[test]
+    fn duplicates_and_minimality() {
+        let rotated = Transform::Rotate.apply(&SUDOKU.parse().unwrap()).unwrap().to_string();
+        let solution = alx_solve(&SUDOKU.parse().unwrap(), 1)[0].to_string();
+        let not_minimal = format!("{}{}", &solution[..1], &SUDOKU[1..]);
+        let lines = [SUDOKU, &"0".repeat(81), &rotated, &not_minimal];
+
+        let (analysis, analyzed) = analyze(&lines, false);
+        assert_eq!(analyzed[2].duplicate_of, Some(1));
+        assert_eq!(analyzed[3].duplicate_of, None);
+        assert_eq!(analyzed[3].redundant.len(), 1);
+        assert_eq!(
+            analysis.failures,
+            Failures {
+                not_unique: 1,
+                duplicates: 1,
+                ..Default::default()
+            }
+        );
+        assert_eq!(
+            analysis.failures.to_string(),
+            "1 without a unique solution, 1 duplicate"
+        );
+        assert_eq!((analysis.summary.duplicates, analysis.summary.not_minimal), (1, 1));
+
+        let (analysis, _) = analyze(&[&not_minimal], true);
+        assert_eq!((analysis.failures.total(), analysis.failures.not_minimal), (1, 1));
+    }
+}
@@ -0,0 +1,564 @@
+use std::error::Error;
+use std::fmt::Write;
+use std::mem;
+
+use rustdoku_sudoku::candidates::Candidates;
+use rustdoku_sudoku::coordinates::{CellIndex, Digit};
+use rustdoku_sudoku::generator;
+use rustdoku_sudoku::grid::{Grid, SetError};
+use rustdoku_sudoku::logic;
+use rustdoku_sudoku::session::{Move, Session};
+use rustdoku_sudoku::solver::alx_solve;
+
+use crate::terminal::{Key, Terminal};
+
+const RESET: &str = "\x1b[0m";
+const BOLD: &str = "\x1b[1m";
+const DIM: &str = "\x1b[2m";
+const REVERSE: &str = "\x1b[7m";
+const RED: &str = "\x1b[31m";
+const CYAN: &str = "\x1b[36m";
+const HIGHLIGHT: &str = "\x1b[43;30m";
+
+const HELP: [&str; 16] = [
+    "arrows   move",
+    "1-9      value or candidate",
+    "0 del    clear the value",
+    "tab      values / candidates",
+    "a        assisted mode",
+    "p        fill pencilmarks",
+    "u r      undo / redo",
+    "h        hint",
+    "c        check",
+    "s        solve",
+    "g        generate",
+    "+ -      givens to generate",
+    "q esc    quit",
+    "",
+    "Assisted mode refuses values",
+    "clashing with their peers.",
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum InputType {
+    Values,
+    Candidates,
+}
+
+/// An undo step: moves played in the session, taken back together, or the player's own notes before they were
+/// edited, which the session doesn't hold.
+#[derive(Clone, Copy, Debug)]
+enum Edit {
+    Moves(usize),
+    Pencilmarks([Candidates; 81]),
+}
+
+/// The state of the terminal game, mirroring the web game.
+struct Game {
+    session: Session,
+    /// The player's own notes, used instead of the grid candidates unless assisted
+    pencilmarks: [Candidates; 81],
+    /// The edits that can be undone, the last one first
+    edits: Vec<Edit>,
+    /// The edits that were undone and can be redone, the last one first
+    undone: Vec<Edit>,
+    cursor: CellIndex,
+    input_type: InputType,
+    assisted: bool,
+    givens: usize,
+    /// Cells pointed out by the last hint or check
+    highlighted: Vec<CellIndex>,
+    message: String,
+}
+
+impl Game {
+    fn new(puzzle: Grid, givens: usize) -> Self {
+        Game {
+            session: Session::new(puzzle),
+            pencilmarks: [Candidates::new(false); 81],
+            edits: vec![],
+            undone: vec![],
+            cursor: CellIndex::new(0).unwrap(),
+            input_type: InputType::Values,
+            assisted: false,
+            givens,
+            highlighted: vec![],
+            message: String::new(),
+        }
+    }
+
+    fn grid(&self) -> &Grid {
+        self.session.grid()
+    }
+
+    /// Plays a move, telling the player why it was refused.
+    fn play(&mut self, mv: Move) {
+        let result = self.session.play(mv);
+        if result.is_ok() {
+            self.record(Edit::Moves(1));
+        }
+        match result {
+            Ok(()) if self.grid().is_complete() && self.grid().is_valid() => {
+                self.message = "Solved, well done!".to_owned()
+            }
+            Ok(()) => {}
+            Err(SetError::Frozen) => self.message = format!("{} is a given", self.cursor),
+            Err(SetError::Conflict { with }) => {
+                self.message = format!("{} is already placed in {}", self.grid().get(with), with)
+            }
+            Err(SetError::NoCandidatesLeft { cell }) => {
+                self.message = format!("That would leave {} without candidates", cell)
+            }
+        }
+    }
+
+    fn record(&mut self, edit: Edit) {
+        self.edits.push(edit);
+        self.undone.clear();
+    }
+
+    /// Changes the player's own notes as one edit that can be undone.
+    fn edit_pencilmarks(&mut self, change: impl FnOnce(&mut [Candidates; 81])) {
+        let before = self.pencilmarks;
+        change(&mut self.pencilmarks);
+        if self.pencilmarks != before {
+            self.record(Edit::Pencilmarks(before));
+        }
+    }
+
+    fn move_cursor(&mut self, rows: usize, columns: usize) {
+        self.cursor =
+            CellIndex::from_coords((self.cursor.row() + rows) % 9, (self.cursor.column() + columns) % 9).unwrap();
+    }
+
+    fn enter(&mut self, digit: Digit) {
+        let cell = self.cursor;
+        match self.input_type {
+            InputType::Values => self.play(Move::Set {
+                cell,
+                value: Some(digit),
+                checked: self.assisted,
+            }),
+            InputType::Candidates if self.assisted => self.play(Move::ToggleCandidate { cell, digit }),
+            InputType::Candidates => self.edit_pencilmarks(|pencilmarks| pencilmarks[cell.index()].toggle_digit(digit)),
+        }
+    }
+
+    fn clear(&mut self) {
+        let cell = self.cursor;
+        if self.grid().get(cell) > 0 {
+            self.play(Move::Set {
+                cell,
+                value: None,
+                checked: self.assisted,
+            });
+        } else if !self.assisted {
+            self.edit_pencilmarks(|pencilmarks| pencilmarks[cell.index()].unset_all());
+        }
+    }
+
+    fn fill_pencilmarks(&mut self) {
+        if self.assisted {
+            self.play(Move::FillCandidates);
+        } else {
+            let grid = *self.grid();
+            self.edit_pencilmarks(|pencilmarks| grid.fill_pencilmarks(pencilmarks));
+        }
+    }
+
+    fn undo(&mut self) {
+        match self.edits.pop() {
+            Some(edit) => {
+                let edit = self.revert(edit, false);
+                self.undone.push(edit);
+            }
+            None => self.message = "Nothing to undo".to_owned(),
+        }
+    }
+
+    fn redo(&mut self) {
+        match self.undone.pop() {
+            Some(edit) => {
+                let edit = self.revert(edit, true);
+                self.edits.push(edit);
+            }
+            None => self.message = "Nothing to redo".to_owned(),
+        }
+    }
+
+    /// Takes back an edit, or plays it again when `redo` is set, returning the edit that goes the other way.
+    fn revert(&mut self, edit: Edit, redo: bool) -> Edit {
+        match edit {
+            Edit::Moves(count) => {
+                for _ in 0..count {
+                    if redo {
+                        self.session.redo();
+                    } else {
+                        self.session.undo();
+                    }
+                }
+                edit
+            }
+            Edit::Pencilmarks(pencilmarks) => Edit::Pencilmarks(mem::replace(&mut self.pencilmarks, pencilmarks)),
+        }
+    }
+
+    fn hint(&mut self) {
+        if !self.grid().conflicts().is_empty() {
+            self.check();
+            return;
+        }
+        let mut grid = *self.grid();
+        grid.recompute_all_candidates();
+        match logic::next_step(&grid) {
+            Some(step) => {
+                self.highlighted = step
+                    .cells
+                    .iter()
+                    .copied()
+                    .chain(step.placements.iter().map(|(cell, _)| *cell))
+                    .chain(step.eliminations.iter().map(|(cell, _)| *cell))
+                    .collect();
+                self.message = step.to_string();
+            }
+            None if grid.is_complete() => self.message = "The sudoku is already solved".to_owned(),
+            None => self.message = "No logical step found, the sudoku needs guessing".to_owned(),
+        }
+    }
+
+    fn check(&mut self) {
+        let grid = *self.grid();
+        let conflicts = grid.conflicts();
+        if let Some(conflict) = conflicts.first() {
+            self.highlighted = conflicts
+                .iter()
+                .flat_map(|conflict| [conflict.cells.0, conflict.cells.1])
+                .collect();
+            self.message = format!(
+                "Cells {} and {} clash in {}",
+                conflict.cells.0, conflict.cells.1, conflict.unit
+            );
+            return;
+        }
+        let mut givens = Grid::new();
+        for cell in CellIndex::all().filter(|cell| grid.frozen(*cell)) {
+            givens.set_unchecked(cell, Digit::new(grid.get(cell))).unwrap();
+        }
+        let mistakes = match alx_solve(&givens, 2).as_slice() {
+            [solution] => grid.mistakes(solution),
+            [] => {
+                self.message = "The givens have no solution, so mistakes can't be told".to_owned();
+                return;
+            }
+            _ => {
+                self.message = "The givens have several solutions, so mistakes can't be told".to_owned();
+                return;
+            }
+        };
+        self.message = match mistakes.as_slice() {
+            [] if grid.is_complete() => "Solved, well done!".to_owned(),
+            [] => "No mistakes so far".to_owned(),
+            [mistake] => format!("{} is a mistake", mistake),
+            mistakes => format!("{} mistakes", mistakes.len()),
+        };
+        self.highlighted = mistakes;
+    }
+
+    fn solve(&mut self) {
+        if !self.grid().conflicts().is_empty() {
+            self.check();
+            return;
+        }
+        let grid = *self.grid();
+        let solutions = alx_solve(&grid, 2);
+        match solutions.as_slice() {
+            [] => self.message = "No solution found, check for mistakes".to_owned(),
+            [solution] => {
+                let empty = CellIndex::all()
+                    .filter(|cell| grid.get(*cell) == 0)
+                    .collect::<Vec<CellIndex>>();
+                for cell in &empty {
+                    self.session.set(*cell, Digit::new(solution.get(*cell)), false).unwrap();
+                }
+                self.record(Edit::Moves(empty.len()));
+                self.message = "Solved".to_owned();
+            }
+            _ => self.message = "Multiple solutions found".to_owned(),
+        }
+    }
+
+    fn generate(&mut self) {
+        self.session = Session::new(generator::generate(self.givens));
+        self.pencilmarks = [Candidates::new(false); 81];
+        self.edits.clear();
+        self.undone.clear();
+        self.message = format!("Generated a sudoku with {} givens", self.givens);
+    }
+
+    /// Handles a key, returning false when the game should end.
+    fn handle(&mut self, key: Key) -> bool {
+        self.message.clear();
+        if !matches!(key, Key::Char('h' | 'c')) {
+            self.highlighted.clear();
+        }
+        match key {
+            Key::Up => self.move_cursor(8, 0),
+            Key::Down => self.move_cursor(1, 0),
+            Key::Left => self.move_cursor(0, 8),
+            Key::Right => self.move_cursor(0, 1),
+            Key::Char(digit @ '1'..='9') => self.enter(Digit::new(digit as u8 - b'0').unwrap()),
+            Key::Char('0' | '.' | ' ') | Key::Backspace | Key::Delete => self.clear(),
+            Key::Tab => {
+                self.input_type = match self.input_type {
+                    InputType::Values => InputType::Candidates,
+                    InputType::Candidates => InputType::Values,
+                }
+            }
+            Key::Char('a') => self.assisted = !self.assisted,
+            Key::Char('p') => self.fill_pencilmarks(),
+            Key::Char('u') => self.undo(),
+            Key::Char('r') => self.redo(),
+            Key::Char('h') => self.hint(),
+            Key::Char('c') => self.check(),
+            Key::Char('s') => self.solve(),
+            Key::Char('g') => self.generate(),
+            Key::Char('+') => self.givens = (self.givens + 1).min(80),
+            Key::Char('-') => self.givens = (self.givens - 1).max(17),
+            Key::Char('q') | Key::Escape | Key::Interrupt => return false,
+            _ => {}
+        }
+        true
+    }
+
+    /// Draws the board with the help next to it and the message below.
+    fn render(&self) -> String {
+        let grid = self.grid();
+        let conflicts = grid
+            .conflicts()
+            .iter()
+            .flat_map(|conflict| [conflict.cells.0, conflict.cells.1])
+            .collect::<Vec<CellIndex>>();
+        let status = [
+            format!("{}Rustdoku{}", BOLD, RESET),
+            String::new(),
+            format!(
+                "Input: {:<10}  Assisted: {}",
+                match self.input_type {
+                    InputType::Values => "values",
+                    InputType::Candidates => "candidates",
+                },
+                if self.assisted { "on" } else { "off" }
+            ),
+            format!(
+                "Generate: {:<7}  Filled: {}",
+                self.givens,
+                grid.cells().filter(|value| *value > 0).count()
+            ),
+            String::new(),
+        ];
+        let mut side = status.iter().map(String::as_str).chain(HELP);
+        let border = "+-------------+-------------+-------------+";
+        let mut frame = String::new();
+        for row in 0..9 {
+            if row % 3 == 0 {
+                writeln!(frame, "{}  {}", border, side.next().unwrap_or_default()).unwrap();
+            }
+            for line in 0..3 {
+                for column in 0..9 {
+                    frame.push_str(match column {
+                        0 => "| ",
+                        3 | 6 => " | ",
+                        _ => " ",
+                    });
+                    let cell = CellIndex::from_coords(row, column).unwrap();
+                    frame.push_str(&self.render_cell(cell, line, conflicts.contains(&cell)));
+                }
+                writeln!(frame, " |  {}", side.next().unwrap_or_default()).unwrap();
+            }
+        }
+        writeln!(frame, "{}  {}", border, side.next().unwrap_or_default()).unwrap();
+        writeln!(frame, "{}", self.message).unwrap();
+        frame
+    }
+
+    /// Renders one of the three lines of a cell: the value on the middle line, or a row of the candidate grid.
+    fn render_cell(&self, cell: CellIndex, line: usize, conflict: bool) -> String {
+        let grid = self.grid();
+        let value = grid.get(cell);
+        let text = if value > 0 {
+            if line == 1 {
+                format!(" {} ", value)
+            } else {
+                "   ".to_owned()
+            }
+        } else {
+            let candidates = if self.assisted {
+                *grid.candidates(cell)
+            } else {
+                self.pencilmarks[cell.index()]
+            };
+            Digit::all()
+                .skip(line * 3)
+                .take(3)
+                .map(|digit| {
+                    if candidates.get_digit(digit) {
+                        char::from(b'0' + digit.get())
+                    } else {
+                        ' '
+                    }
+                })
+                .collect()
+        };
+        let mut style = String::new();
+        if cell == self.cursor {
+            style.push_str(REVERSE);
+        } else if self.highlighted.contains(&cell) {
+            style.push_str(HIGHLIGHT);
+        }
+        style.push_str(match value {
+            _ if conflict => RED,
+            0 => DIM,
+            _ if grid.frozen(cell) => BOLD,
+            _ => CYAN,
+        });
+        format!("{}{}{}", style, text, RESET)
+    }
+}
+
+/// Plays the sudoku, or a generated one with the given number of givens, in the terminal until the player quits.
+pub fn run(sudoku: Option<&str>, givens: usize) -> Result<(), Box<dyn Error + Sync + Send>> {
+    let puzzle = match sudoku {
+        Some(sudoku) => sudoku.parse()?,
+        None => generator::generate(givens),
+    };
+    let mut game = Game::new(puzzle, givens);
+    let mut terminal = Terminal::new()?;
+    loop {
+        terminal.draw(&game.render())?;
+        if !game.handle(terminal.read_key()?) {
+            return Ok(());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SUDOKU: &str = "060000300400700000000000080000008012500600000000000050082000700000500600000010000";
+
+    fn game() -> Game {
+        Game::new(SUDOKU.parse().unwrap(), 28)
+    }
+
+    fn cell(notation: &str) -> CellIndex {
+        notation.parse().unwrap()
+    }
+
+    #[test]
+    fn moving() {
+        let mut game = game();
+        assert!(game.handle(Key::Up));
+        assert_eq!(game.cursor, cell("r9c1"));
+        game.handle(Key::Left);
+        game.handle(Key::Left);
+        assert_eq!(game.cursor, cell("r9c8"));
+        game.handle(Key::Down);
+        game.handle(Key::Right);
+        assert_eq!(game.cursor, cell("r1c9"));
+        assert!(!game.handle(Key::Char('q')));
+    }
+
+    #[test]
+    fn entering_and_undoing() {
+        let mut game = game();
+        game.handle(Key::Char('9'));
+        assert_eq!(game.grid().get(cell("r1c1")), 9);
+        game.handle(Key::Tab);
+        game.handle(Key::Right);
+        game.handle(Key::Right);
+        game.handle(Key::Char('5'));
+        assert!(game.pencilmarks[cell("r1c3").index()].get(4));
+        assert_eq!(game.grid().get(cell("r1c3")), 0);
+
+        game.handle(Key::Char('u'));
+        assert!(!game.pencilmarks[cell("r1c3").index()].get(4));
+        assert_eq!(game.grid().get(cell("r1c1")), 9);
+        game.handle(Key::Char('u'));
+        assert_eq!(game.grid().get(cell("r1c1")), 0);
+        game.handle(Key::Char('u'));
+        assert_eq!(game.message, "Nothing to undo");
+        game.handle(Key::Char('r'));
+        assert_eq!(game.grid().get(cell("r1c1")), 9);
+        game.handle(Key::Char('r'));
+        assert!(game.pencilmarks[cell("r1c3").index()].get(4));
+
+        game.handle(Key::Char('p'));
+        game.handle(Key::Char('0'));
+        assert!(game.pencilmarks[cell("r1c3").index()].none());
+        game.handle(Key::Char('u'));
+        game.handle(Key::Char('u'));
+        assert!(game.pencilmarks[cell("r1c3").index()].get(4));
+        assert_eq!(game.pencilmarks[cell("r2c2").index()].count(), 0);
+    }
+
+    #[test]
+    fn assisted_refusal() {
+        let mut game = game();
+        game.handle(Key::Char('a'));
+        game.handle(Key::Char('6'));
+        assert_eq!(game.message, "6 is already placed in r1c2");
+        assert_eq!(game.grid().get(cell("r1c1")), 0);
+        game.handle(Key::Right);
+        game.handle(Key::Char('1'));
+        assert_eq!(game.message, "r1c2 is a given");
+
+        game.handle(Key::Char('a'));
+        game.handle(Key::Left);
+        game.handle(Key::Char('6'));
+        assert_eq!(game.grid().get(cell("r1c1")), 6);
+    }
+
+    #[test]
+    fn checking() {
+        let mut game = game();
+        game.handle(Key::Char('c'));
+        assert_eq!(game.message, "No mistakes so far");
+        game.handle(Key::Char('1'));
+        game.handle(Key::Char('c'));
+        assert_eq!(game.message, "r1c1 is a mistake");
+        assert_eq!(game.highlighted, vec![cell("r1c1")]);
+
+        game.handle(Key::Char('6'));
+        game.handle(Key::Char('c'));
+        assert_eq!(game.message, "Cells r1c1 and r1c2 clash in row 1");
+
+        let mut game = Game::new(Grid::new(), 28);
+        game.handle(Key::Char('1'));
+        game.handle(Key::Char('c'));
+        assert_eq!(
+            game.message,
+            "The givens have several solutions, so mistakes can't be told"
+        );
+    }
+
+    #[test]
+    fn solving() {
+        let mut game = game();
+        game.handle(Key::Char('6'));
+        game.handle(Key::Char('s'));
+        assert_eq!(game.message, "Cells r1c1 and r1c2 clash in row 1");
+        assert_eq!(game.grid().get(cell("r1c3")), 0);
+
+        game.handle(Key::Char('u'));
+        game.handle(Key::Char('s'));
+        assert_eq!(game.message, "Solved");
+        assert!(game.grid().is_complete());
+
+        game.handle(Key::Char('u'));
+        assert_eq!(game.grid().get(cell("r1c3")), 0);
+        assert_eq!(game.grid().cells().filter(|value| *value > 0).count(), 17);
+        game.handle(Key::Char('r'));
+        assert!(game.grid().is_complete());
+    }
+}
@@ -0,0 +1,572 @@
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use rayon::prelude::*;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::output::{RatingReport, Status};
+use rustdoku_sudoku::canonical::canonicalize;
+use rustdoku_sudoku::generator;
+use rustdoku_sudoku::grid::Grid;
+use rustdoku_sudoku::logic::{self, rate};
+use rustdoku_sudoku::solver::alx_solve;
+
+/// Longest accepted request head, the request line and headers together.
+const MAX_HEAD: usize = 8192;
+/// Most solutions a single solve request can ask for.
+const MAX_SOLUTIONS: usize = 1000;
+/// Most sudokus a single generate request can ask for.
+const MAX_GENERATED: usize = 100;
+/// Fewest givens a generate request can ask for, as lower counts are rarely reached by carving.
+const MIN_GIVENS: usize = 22;
+/// Solved grids carved for a whole generate request before it fails, shared by all of its sudokus, which bounds its
+/// time whatever the count and even for unlucky grids.
+const MAX_CARVE_ATTEMPTS: usize = 5000;
+
+pub struct Config {
+    /// How many connections are handled at once
+    pub threads: usize,
+    /// Largest accepted request body in bytes
+    pub max_body: usize,
+    /// How many answers are computed at once, counting those still running for requests that timed out
+    pub max_jobs: usize,
+    /// Time allowed for the whole request, from reading it to writing the answer back
+    pub timeout: Duration,
+    /// Logs every request on standard error
+    pub verbose: bool,
+}
+
+#[derive(Debug)]
+struct Response {
+    status: u16,
+    body: String,
+}
+
+impl Response {
+    fn error(status: u16, message: impl ToString) -> Response {
+        Response {
+            status,
+            body: json!({ "error": message.to_string() }).to_string(),
+        }
+    }
+
+    fn reason(&self) -> &'static str {
+        match self.status {
+            200 => "OK",
+            400 => "Bad Request",
+            404 => "Not Found",
+            405 => "Method Not Allowed",
+            408 => "Request Timeout",
+            411 => "Length Required",
+            413 => "Content Too Large",
+            431 => "Request Header Fields Too Large",
+            503 => "Service Unavailable",
+            _ => "Internal Server Error",
+        }
+    }
+}
+
+struct Request {
+    method: String,
+    path: String,
+    body: Vec<u8>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SudokuRequest {
+    sudoku: String,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SolveRequest {
+    sudoku: String,
+    /// Stops after this many solutions
+    #[serde(default = "default_limit")]
+    limit: usize,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct GenerateRequest {
+    #[serde(default = "default_givens")]
+    givens: usize,
+    #[serde(default = "default_count")]
+    count: usize,
+}
+
+fn default_limit() -> usize {
+    2
+}
+
+fn default_givens() -> usize {
+    28
+}
+
+fn default_count() -> usize {
+    1
+}
+
+#[derive(Serialize)]
+struct Hint {
+    technique: String,
+    description: String,
+    cells: Vec<String>,
+    placements: Vec<String>,
+    eliminations: Vec<String>,
+}
+
+type Handler = Box<dyn FnOnce() -> Response + Send>;
+
+fn parse(sudoku: &str) -> Result<Grid, String> {
+    sudoku.parse().map_err(|error| format!("{}", error))
+}
+
+fn solve(request: SolveRequest) -> Result<Value, String> {
+    if !(1..=MAX_SOLUTIONS).contains(&request.limit) {
+        return Err(format!("Limit must be between 1 and {}", MAX_SOLUTIONS));
+    }
+    let grid = parse(&request.sudoku)?;
+    let solutions = alx_solve(&grid, request.limit);
+    Ok(json!({
+        "sudoku": grid.to_string(),
+        "status": Status::of(solutions.len()),
+        "solutions": solutions.iter().map(Grid::to_string).collect::<Vec<String>>(),
+    }))
+}
+
+fn generate(request: GenerateRequest) -> Result<Value, String> {
+    if !(MIN_GIVENS..=81).contains(&request.givens) {
+        return Err(format!("Givens must be between {} and 81", MIN_GIVENS));
+    }
+    if !(1..=MAX_GENERATED).contains(&request.count) {
+        return Err(format!("Count must be between 1 and {}", MAX_GENERATED));
+    }
+    let sudokus = generate_batch(request.givens, request.count, MAX_CARVE_ATTEMPTS)
+        .ok_or_else(|| format!("Couldn't get down to {} givens, try more givens", request.givens))?;
+    Ok(json!({ "sudokus": sudokus }))
+}
+
+/// Generates `count` sudokus in parallel, carving at most `attempts` solved grids between all of them.
+fn generate_batch(givens: usize, count: usize, attempts: usize) -> Option<Vec<String>> {
+    let attempts = AtomicUsize::new(attempts);
+    (0..count)
+        .into_par_iter()
+        .map(|_| {
+            while attempts
+                .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |left| left.checked_sub(1))
+                .is_ok()
+            {
+                if let Some(grid) = generator::try_generate(givens, 1) {
+                    return Some(grid.to_string());
+                }
+            }
+            None
+        })
+        .collect()
+}
+
+fn rate_sudoku(request: SudokuRequest) -> Result<RatingReport, String> {
+    let grid = parse(&request.sudoku)?;
+    Ok(rate(&grid).into())
+}
+
+fn hint(request: SudokuRequest) -> Result<Value, String> {
+    let mut grid = parse(&request.sudoku)?;
+    grid.recompute_all_candidates();
+    let hint = logic::next_step(&grid).map(|step| Hint {
+        technique: step.technique.to_string(),
+        description: step.to_string(),
+        cells: step.cells.iter().map(|cell| cell.to_string()).collect(),
+        placements: step
+            .placements
+            .iter()
+            .map(|(cell, digit)| format!("{}={}", cell, digit))
+            .collect(),
+        eliminations: step
+            .eliminations
+            .iter()
+            .map(|(cell, digit)| format!("{}<>{}", cell, digit))
+            .collect(),
+    });
+    Ok(json!({ "solved": grid.is_complete(), "hint": hint }))
+}
+
+/// Unlike the other endpoints, sudokus that don't parse are a normal answer rather than a bad request.
+fn validate(request: SudokuRequest) -> Result<Value, String> {
+    match parse(&request.sudoku) {
+        Ok(grid) => {
+            let status = Status::of(alx_solve(&grid, 2).len());
+            Ok(json!({ "valid": status == Status::Unique, "status": status }))
+        }
+        Err(error) => Ok(json!({ "valid": false, "status": Status::ParseError, "error": error })),
+    }
+}
+
+fn canonicalize_sudoku(request: SudokuRequest) -> Result<Value, String> {
+    let grid = parse(&request.sudoku)?;
+    Ok(json!({ "canonical": canonicalize(&grid).to_string() }))
+}
+
+/// Decodes the body for the endpoint and defers running it, so it can run with a timeout.
+fn endpoint<T: DeserializeOwned + Send + 'static, R: Serialize + 'static>(
+    body: &[u8],
+    handler: fn(T) -> Result<R, String>,
+) -> Result<Handler, Response> {
+    let request = serde_json::from_slice(body).map_err(|error| Response::error(400, error))?;
+    Ok(Box::new(move || {
+        match handler(request).map(|body| serde_json::to_string(&body)) {
+            Ok(Ok(body)) => Response { status: 200, body },
+            Ok(Err(error)) => Response::error(500, error),
+            Err(error) => Response::error(400, error),
+        }
+    }))
+}
+
+fn route(request: &Request) -> Result<Handler, Response> {
+    let path = request.path.split('?').next().unwrap_or_default();
+    let body = &request.body;
+    match path {
+        "/solve" | "/generate" | "/rate" | "/hint" | "/validate" | "/canonicalize" if request.method != "POST" => {
+            Err(Response::error(405, "Only POST is supported"))
+        }
+        "/solve" => endpoint(body, solve),
+        "/generate" => endpoint(body, generate),
+        "/rate" => endpoint(body, rate_sudoku),
+        "/hint" => endpoint(body, hint),
+        "/validate" => endpoint(body, validate),
+        "/canonicalize" => endpoint(body, canonicalize_sudoku),
+        _ => Err(Response::error(404, format!("No endpoint at {}", path))),
+    }
+}
+
+/// Counts the handlers on the rayon pool. Handlers keep running after their request times out, so without a limit
+/// slow requests could pile up faster than they finish.
+struct Jobs {
+    running: AtomicUsize,
+    limit: usize,
+}
+
+/// A running handler, counted until dropped.
+struct Job(Arc<Jobs>);
+
+impl Jobs {
+    fn new(limit: usize) -> Arc<Jobs> {
+        Arc::new(Jobs {
+            running: AtomicUsize::new(0),
+            limit,
+        })
+    }
+
+    /// Counts a new handler, or returns `None` when the limit is reached.
+    fn start(self: &Arc<Self>) -> Option<Job> {
+        self.running
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |running| {
+                (running < self.limit).then_some(running + 1)
+            })
+            .ok()
+            .map(|_| Job(self.clone()))
+    }
+}
+
+impl Drop for Job {
+    fn drop(&mut self) {
+        self.0.running.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Runs the handler on the rayon pool and gives up waiting at the deadline. The computation itself can't be
+/// interrupted, so every endpoint bounds its own work: `/solve` and `/validate` by their solution limit, `/generate`
+/// by its carving attempts, and `/rate`, `/hint` and `/canonicalize` by the size of the grid, as every logical step
+/// removes at least one candidate and canonicalizing tries a fixed set of transformations.
+///
+/// The handlers only get grids that parsed without conflicts, and don't panic on those, whether they have a solution
+/// or not. Panics abort in release builds, so one would take the whole server down rather than fail the request.
+fn run_with_deadline(handler: Handler, jobs: &Arc<Jobs>, deadline: Instant) -> Response {
+    let Some(job) = jobs.start() else {
+        return Response::error(503, "Too many requests in progress, try again later");
+    };
+    let (sender, receiver) = mpsc::channel();
+    rayon::spawn(move || {
+        let response = handler();
+        drop(job);
+        let _ = sender.send(response);
+    });
+    receiver
+        .recv_timeout(deadline.saturating_duration_since(Instant::now()))
+        .unwrap_or_else(|_| Response::error(503, "The request took too long"))
+}
+
+/// Reads from the connection until the deadline of the whole request, however slowly the bytes trickle in.
+struct DeadlineReader {
+    stream: TcpStream,
+    deadline: Instant,
+}
+
+impl Read for DeadlineReader {
+    fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        let remaining = self.deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return Err(io::ErrorKind::TimedOut.into());
+        }
+        self.stream.set_read_timeout(Some(remaining))?;
+        self.stream.read(buffer)
+    }
+}
+
+fn read_error(error: io::Error) -> Response {
+    match error.kind() {
+        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut => Response::error(408, "Timed out reading the request"),
+        _ => Response::error(400, error),
+    }
+}
+
+fn read_request(reader: &mut impl BufRead, max_body: usize) -> Result<Request, Response> {
+    let mut head = reader.by_ref().take(MAX_HEAD as u64);
+    let mut read_line = || -> Result<String, Response> {
+        let mut line = String::new();
+        head.read_line(&mut line).map_err(read_error)?;
+        if !line.ends_with('\n') {
+            return Err(Response::error(431, "The request head is too large or incomplete"));
+        }
+        Ok(line.trim_end().to_owned())
+    };
+    let request_line = read_line()?;
+    let mut parts = request_line.split_whitespace();
+    let (Some(method), Some(path)) = (parts.next(), parts.next()) else {
+        return Err(Response::error(400, "Malformed request line"));
+    };
+    let mut length = None;
+    loop {
+        let header = read_line()?;
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                length = Some(
+                    value
+                        .trim()
+                        .parse::<usize>()
+                        .map_err(|_| Response::error(400, "Invalid Content-Length"))?,
+                );
+            }
+        }
+    }
+    let body = match length {
+        Some(length) if length > max_body => {
+            return Err(Response::error(
+                413,
+                format!("The request body is limited to {} bytes", max_body),
+            ))
+        }
+        Some(length) => {
+            let mut body = vec![0; length];
+            reader.read_exact(&mut body).map_err(read_error)?;
+            body
+        }
+        None if method == "POST" => return Err(Response::error(411, "Content-Length is required")),
+        None => vec![],
+    };
+    Ok(Request {
+        method: method.to_owned(),
+        path: path.to_owned(),
+        body,
+    })
+}
+
+fn write_response(stream: &mut impl Write, response: &Response) -> io::Result<()> {
+    write!(
+        stream,
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        response.status,
+        response.reason(),
+        response.body.len(),
+        response.body
+    )?;
+    stream.flush()
+}
+
+fn handle(stream: TcpStream, config: &Config, jobs: &Arc<Jobs>) -> io::Result<()> {
+    let start = Instant::now();
+    let deadline = start + config.timeout;
+    let mut reader = BufReader::new(DeadlineReader { stream, deadline });
+    let (response, summary) = match read_request(&mut reader, config.max_body) {
+        Ok(request) => {
+            let response = match route(&request) {
+                Ok(handler) => run_with_deadline(handler, jobs, deadline),
+                Err(response) => response,
+            };
+            (response, format!("{} {}", request.method, request.path))
+        }
+        Err(response) => (response, "Rejected request".to_owned()),
+    };
+    if config.verbose {
+        eprintln!(
+            "{} {} in {:.1}ms",
+            summary,
+            response.status,
+            start.elapsed().as_secs_f32() * 1000.0
+        );
+    }
+    // Answers late requests too, but still bounds how long a client that stopped reading can hold the connection
+    let stream = &mut reader.get_mut().stream;
+    stream.set_write_timeout(Some(
+        deadline
+            .saturating_duration_since(Instant::now())
+            .max(Duration::from_secs(1)),
+    ))?;
+    write_response(stream, &response)
+}
+
+/// Serves the JSON API until the process is stopped. Connections are handled by a fixed pool of threads and the
+/// sudokus are computed on the rayon pool, which stays warm between requests.
+pub fn run(address: &str, port: u16, config: Config) -> io::Result<()> {
+    let listener = TcpListener::bind((address, port))?;
+    eprintln!("Listening on http://{}", listener.local_addr()?);
+    let (sender, receiver) = mpsc::channel::<TcpStream>();
+    let receiver = Arc::new(Mutex::new(receiver));
+    let jobs = Jobs::new(config.max_jobs);
+    let config = Arc::new(config);
+    for _ in 0..config.threads {
+        let receiver = receiver.clone();
+        let config = config.clone();
+        let jobs = jobs.clone();
+        thread::spawn(move || loop {
+            let Ok(stream) = receiver.lock().unwrap().recv() else {
+                return;
+            };
+            if let Err(error) = handle(stream, &config, &jobs) {
+                if config.verbose {
+                    eprintln!("Connection failed: {}", error);
+                }
+            }
+        });
+    }
+    for stream in listener.incoming() {
+        match stream {
+            Ok(stream) => sender
+                .send(stream)
+                .map_err(|_| io::Error::other("Every worker has stopped"))?,
+            Err(error) => eprintln!("Connection failed: {}", error),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SUDOKU: &str = "060000300400700000000000080000008012500600000000000050082000700000500600000010000";
+
+    fn read(request: &str) -> Result<Request, Response> {
+        read_request(&mut request.as_bytes(), 64)
+    }
+
+    fn post(path: &str, body: &str) -> Request {
+        Request {
+            method: "POST".to_owned(),
+            path: path.to_owned(),
+            body: body.as_bytes().to_vec(),
+        }
+    }
+
+    fn respond(request: &Request) -> Response {
+        match route(request) {
+            Ok(handler) => handler(),
+            Err(response) => response,
+        }
+    }
+
+    #[test]
+    fn reading() {
+        let request = read("POST /solve HTTP/1.1\r\nHost: localhost\r\ncontent-length: 4\r\n\r\nbody").unwrap();
+        assert_eq!((request.method.as_str(), request.path.as_str()), ("POST", "/solve"));
+        assert_eq!(request.body, b"body");
+        assert!(read("GET /solve HTTP/1.1\r\n\r\n").unwrap().body.is_empty());
+
+        let status = |request: &str| read(request).err().map(|response| response.status);
+        assert_eq!(status("POST /solve HTTP/1.1\r\n\r\n"), Some(411));
+        assert_eq!(status("POST /solve HTTP/1.1\r\nContent-Length: 65\r\n\r\n"), Some(413));
+        assert_eq!(status("POST /solve HTTP/1.1\r\nContent-Length: x\r\n\r\n"), Some(400));
+        assert_eq!(status("POST /solve HTTP/1.1\r\nContent-Length: 4\r\n\r\nbo"), Some(400));
+        assert_eq!(status("POST\r\n\r\n"), Some(400));
+        assert_eq!(status("POST /solve HTTP/1.1\r\nHost: local"), Some(431));
+        let header = format!("X-Padding: {}\r\n", "x".repeat(MAX_HEAD));
+        assert_eq!(status(&format!("GET / HTTP/1.1\r\n{}\r\n", header)), Some(431));
+    }
+
+    #[test]
+    fn routing() {
+        let response = respond(&post("/solve?pretty", &format!(r#"{{"sudoku":"{}"}}"#, SUDOKU)));
+        assert_eq!(response.status, 200);
+        assert!(response.body.contains(r#""status":"unique""#));
+
+        let response = respond(&post("/validate", r#"{"sudoku":"1"}"#));
+        assert_eq!(response.status, 200);
+        assert!(response.body.contains(r#""valid":false"#));
+
+        assert_eq!(respond(&post("/solve", r#"{"sudoku":"1"}"#)).status, 400);
+        assert_eq!(respond(&post("/solve", r#"{"grid":"1"}"#)).status, 400);
+        assert_eq!(respond(&post("/generate", r#"{"count":0}"#)).status, 400);
+        let response = respond(&post("/generate", r#"{"givens":17,"count":100}"#));
+        assert_eq!(response.status, 400);
+        assert!(response.body.contains("Givens must be between 22 and 81"));
+        let response = respond(&post("/generate", r#"{"givens":40,"count":2}"#));
+        assert_eq!(response.status, 200);
+        let body: Value = serde_json::from_str(&response.body).unwrap();
+        assert_eq!(body["sudokus"].as_array().unwrap().len(), 2);
+        assert_eq!(respond(&post("/missing", "{}")).status, 404);
+        let mut request = post("/rate", "");
+        request.method = "GET".to_owned();
+        assert_eq!(respond(&request).status, 405);
+    }
+
+    #[test]
+    fn unsolvable_sudokus() {
+        let empty = "0".repeat(81);
+        // The last two cells of the first row can both only be a 9
+        let mut unsolvable = format!("1234567{}", "0".repeat(74));
+        unsolvable.replace_range(34..35, "8");
+        unsolvable.replace_range(62..63, "8");
+        for sudoku in [empty, unsolvable] {
+            for path in ["/solve", "/rate", "/hint", "/validate", "/canonicalize"] {
+                let response = respond(&post(path, &format!(r#"{{"sudoku":"{}"}}"#, sudoku)));
+                assert_eq!(response.status, 200, "{} {}", path, sudoku);
+            }
+        }
+    }
+
+    #[test]
+    fn job_limit() {
+        let jobs = Jobs::new(1);
+        let job = jobs.start().unwrap();
+        assert!(jobs.start().is_none());
+        let deadline = Instant::now() + Duration::from_secs(10);
+        let handler: Handler = Box::new(|| Response::error(500, "unreachable"));
+        assert_eq!(run_with_deadline(handler, &jobs, deadline).status, 503);
+        drop(job);
+        let handler: Handler = Box::new(|| Response {
+            status: 200,
+            body: String::new(),
+        });
+        assert_eq!(run_with_deadline(handler, &jobs, deadline).status, 200);
+        assert_eq!(jobs.running.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn generate_budget() {
+        // Nothing is removed for 81 givens, so every carving attempt generates a sudoku
+        assert_eq!(generate_batch(81, 3, 3).unwrap().len(), 3);
+        assert!(generate_batch(81, 3, 2).is_none());
+        assert!(generate_batch(22, 100, 1).is_none());
+    }
+}
@@ -0,0 +1,304 @@
+use std::collections::VecDeque;
+use std::io::{self, Write};
+use std::panic;
+
+/// Longest run of parameter bytes read in a control sequence before it's dropped, so that garbage input can't grow
+/// it without limit.
+const MAX_PARAMETERS: usize = 16;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Key {
+    Up,
+    Down,
+    Left,
+    Right,
+    Char(char),
+    Backspace,
+    Delete,
+    Tab,
+    Escape,
+    Interrupt,
+    Unknown,
+}
+
+/// Where the keys come from, read without buffering of its own so `pending` sees every byte not yet read.
+trait Source {
+    fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize>;
+
+    /// Whether more input arrives within a moment, which tells escape sequences from the escape key.
+    fn pending(&mut self) -> io::Result<bool>;
+}
+
+/// Turns the bytes of a source into keys.
+struct Keys<S> {
+    source: S,
+    /// Bytes read but not yet turned into keys
+    input: VecDeque<u8>,
+}
+
+impl<S: Source> Keys<S> {
+    fn new(source: S) -> Self {
+        Keys {
+            source,
+            input: VecDeque::new(),
+        }
+    }
+
+    fn read_key(&mut self) -> io::Result<Key> {
+        let key = match self.read_byte()? {
+            b'\t' => Key::Tab,
+            0x03 => Key::Interrupt,
+            0x7f | 0x08 => Key::Backspace,
+            // A lone escape is the escape key, otherwise it starts a sequence like `ESC [ A`
+            0x1b if self.input.is_empty() && !self.source.pending()? => Key::Escape,
+            0x1b => escape_sequence(|| self.read_byte())?,
+            byte if byte.is_ascii_graphic() || byte == b' ' => Key::Char(byte as char),
+            _ => Key::Unknown,
+        };
+        Ok(key)
+    }
+
+    fn read_byte(&mut self) -> io::Result<u8> {
+        if self.input.is_empty() {
+            let mut buffer = [0; 256];
+            match self.source.read(&mut buffer)? {
+                0 => return Err(io::ErrorKind::UnexpectedEof.into()),
+                read => self.input.extend(&buffer[..read]),
+            }
+        }
+        Ok(self.input.pop_front().unwrap())
+    }
+}
+
+/// Keeps the terminal in raw mode on the alternate screen until dropped.
+pub struct Terminal {
+    keys: Keys<RawMode>,
+}
+
+impl Terminal {
+    pub fn new() -> io::Result<Terminal> {
+        let mode = RawMode::enable()?;
+        mode.restore_on_panic();
+        let mut stdout = io::stdout();
+        write!(stdout, "\x1b[?1049h\x1b[?25l")?;
+        stdout.flush()?;
+        Ok(Terminal { keys: Keys::new(mode) })
+    }
+
+    /// Replaces the screen with the frame.
+    pub fn draw(&mut self, frame: &str) -> io::Result<()> {
+        let mut stdout = io::stdout().lock();
+        write!(stdout, "\x1b[H\x1b[2J{}", frame)?;
+        stdout.flush()
+    }
+
+    pub fn read_key(&mut self) -> io::Result<Key> {
+        self.keys.read_key()
+    }
+}
+
+/// Reads the rest of a sequence starting with escape. Control sequences like `ESC [ 1 ; 5 A` are read up to their
+/// final byte, so that keys the game doesn't know are skipped whole, unless they run past `MAX_PARAMETERS`.
+fn escape_sequence(mut read_byte: impl FnMut() -> io::Result<u8>) -> io::Result<Key> {
+    let key = match read_byte()? {
+        b'[' => {
+            let mut parameters = vec![];
+            loop {
+                match read_byte()? {
+                    b'~' if parameters == b"3" => break Key::Delete,
+                    byte @ 0x40..=0x7e => break arrow(byte).unwrap_or(Key::Unknown),
+                    _ if parameters.len() == MAX_PARAMETERS => break Key::Unknown,
+                    byte => parameters.push(byte),
+                }
+            }
+        }
+        b'O' => arrow(read_byte()?).unwrap_or(Key::Unknown),
+        _ => Key::Unknown,
+    };
+    Ok(key)
+}
+
+/// The arrow key of the final byte of a sequence. Modifiers like Ctrl are ignored.
+fn arrow(byte: u8) -> Option<Key> {
+    match byte {
+        b'A' => Some(Key::Up),
+        b'B' => Some(Key::Down),
+        b'C' => Some(Key::Right),
+        b'D' => Some(Key::Left),
+        _ => None,
+    }
+}
+
+impl Drop for Terminal {
+    fn drop(&mut self) {
+        let mut stdout = io::stdout();
+        let _ = write!(stdout, "\x1b[?25h\x1b[?1049l");
+        let _ = stdout.flush();
+        self.keys.source.disable();
+    }
+}
+
+#[cfg(unix)]
+struct RawMode {
+    original: libc::termios,
+}
+
+#[cfg(unix)]
+impl RawMode {
+    fn enable() -> io::Result<RawMode> {
+        // SAFETY: termios is plain data filled in by tcgetattr
+        let mut original: libc::termios = unsafe { std::mem::zeroed() };
+        // SAFETY: `original` is a valid termios for tcgetattr to write to
+        if unsafe { libc::tcgetattr(libc::STDIN_FILENO, &mut original) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let mut raw = original;
+        raw.c_iflag &= !(libc::BRKINT | libc::ICRNL | libc::INPCK | libc::ISTRIP | libc::IXON);
+        raw.c_lflag &= !(libc::ECHO | libc::ICANON | libc::IEXTEN | libc::ISIG);
+        raw.c_cc[libc::VMIN] = 1;
+        raw.c_cc[libc::VTIME] = 0;
+        // SAFETY: `raw` is a valid termios, only read by tcsetattr
+        if unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSAFLUSH, &raw) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(RawMode { original })
+    }
+
+    fn disable(&mut self) {
+        // SAFETY: `original` is the valid termios filled in by tcgetattr, only read by tcsetattr
+        unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSAFLUSH, &self.original) };
+    }
+
+    /// Restores the terminal before a panic is reported. Panics abort in release builds, so `Drop` never runs for
+    /// them. The screen is reset through standard error, as the panic may come while standard output is borrowed.
+    fn restore_on_panic(&self) {
+        let original = self.original;
+        let report = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            // SAFETY: `original` is the valid termios filled in by tcgetattr, only read by tcsetattr
+            unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSAFLUSH, &original) };
+            let _ = write!(io::stderr(), "\x1b[?25h\x1b[?1049l");
+            report(info);
+        }));
+    }
+}
+
+/// Reads the file descriptor directly, as bytes held in the buffer of `io::stdin()` would be invisible to the poll.
+#[cfg(unix)]
+impl Source for RawMode {
+    fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        loop {
+            // SAFETY: the buffer is valid for writes of its length
+            match unsafe { libc::read(libc::STDIN_FILENO, buffer.as_mut_ptr().cast(), buffer.len()) } {
+                -1 if io::Error::last_os_error().kind() == io::ErrorKind::Interrupted => continue,
+                -1 => return Err(io::Error::last_os_error()),
+                read => return Ok(read as usize),
+            }
+        }
+    }
+
+    fn pending(&mut self) -> io::Result<bool> {
+        let mut fd = libc::pollfd {
+            fd: libc::STDIN_FILENO,
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        // SAFETY: `fd` is a valid pollfd and the count of one matches it
+        match unsafe { libc::poll(&mut fd, 1, 30) } {
+            -1 => Err(io::Error::last_os_error()),
+            ready => Ok(ready > 0),
+        }
+    }
+}
+
+#[cfg(not(unix))]
+struct RawMode;
+
+#[cfg(not(unix))]
+impl RawMode {
+    fn enable() -> io::Result<RawMode> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "The terminal UI needs a Unix terminal",
+        ))
+    }
+
+    fn disable(&mut self) {}
+
+    fn restore_on_panic(&self) {}
+}
+
+#[cfg(not(unix))]
+impl Source for RawMode {
+    fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        io::Read::read(&mut io::stdin(), buffer)
+    }
+
+    fn pending(&mut self) -> io::Result<bool> {
+        Ok(false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(sequence: &[u8]) -> (Key, usize) {
+        let mut bytes = sequence.iter().copied();
+        let key = escape_sequence(|| bytes.next().ok_or(io::ErrorKind::UnexpectedEof.into())).unwrap();
+        (key, bytes.count())
+    }
+
+    /// Hands out the chunks one read at a time, with the next chunk pending until it's read.
+    struct Chunks(VecDeque<Vec<u8>>);
+
+    impl Source for Chunks {
+        fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+            let Some(chunk) = self.0.front_mut() else {
+                return Ok(0);
+            };
+            let read = chunk.len().min(buffer.len());
+            buffer[..read].copy_from_slice(&chunk[..read]);
+            chunk.drain(..read);
+            if chunk.is_empty() {
+                self.0.pop_front();
+            }
+            Ok(read)
+        }
+
+        fn pending(&mut self) -> io::Result<bool> {
+            Ok(!self.0.is_empty())
+        }
+    }
+
+    fn keys(chunks: &[&[u8]]) -> Vec<Key> {
+        let mut keys = Keys::new(Chunks(chunks.iter().map(|chunk| chunk.to_vec()).collect()));
+        std::iter::from_fn(|| keys.read_key().ok()).collect()
+    }
+
+    #[test]
+    fn lone_escape() {
+        assert_eq!(keys(&[b"\x1b"]), [Key::Escape]);
+        assert_eq!(keys(&[b"\x1b", b"[A"]), [Key::Up]);
+        assert_eq!(keys(&[b"\x1b", b"q"]), [Key::Unknown]);
+        assert_eq!(keys(&[b"1\x1b", b"[B"]), [Key::Char('1'), Key::Down]);
+
+        // A paste longer than a read leaves the sequence after it waiting in the source rather than lost
+        let paste = [b'5'; 300].iter().chain(b"\x1b[C").copied().collect::<Vec<u8>>();
+        let pasted = keys(&[&paste]);
+        assert_eq!(pasted.len(), 301);
+        assert_eq!(pasted.last(), Some(&Key::Right));
+    }
+
+    #[test]
+    fn escape_sequences() {
+        assert_eq!(parse(b"[A"), (Key::Up, 0));
+        assert_eq!(parse(b"OD"), (Key::Left, 0));
+        assert_eq!(parse(b"[3~"), (Key::Delete, 0));
+        assert_eq!(parse(b"[1;5A"), (Key::Up, 0));
+        assert_eq!(parse(b"[15~5"), (Key::Unknown, 1));
+        assert_eq!(parse(b"[200~9"), (Key::Unknown, 1));
+        assert_eq!(parse(b"x"), (Key::Unknown, 0));
+        assert_eq!(parse(&[b"[".as_slice(), &[b'1'; 20], b"A"].concat()), (Key::Unknown, 4));
+    }
+}
@@ -1,5 +1,12 @@
 use leptos::*;
-use rustdoku_sudoku::{candidates::Candidates, generator, grid::Grid, solver::alx_solve};
+use rustdoku_sudoku::{
+    candidates::Candidates,
+    coordinates::{CellIndex, Digit},
+    generator,
+    grid::{Grid, SetError},
+    session::{Move, Session},
+    solver::alx_solve,
+};
 use web_sys::KeyboardEvent;
 
 #[derive(Copy, Clone, Debug, PartialEq)]
@@ -10,9 +17,9 @@ enum InputType {
 
 #[derive(Copy, Clone, Debug)]
 struct AppState {
-    grid: RwSignal<Grid>,
+    session: RwSignal<Session>,
     placemarks: RwSignal<[Candidates; 81]>,
-    selected: RwSignal<Option<usize>>,
+    selected: RwSignal<Option<CellIndex>>,
     givens: RwSignal<usize>,
     assisted: RwSignal<bool>,
     input_type: RwSignal<InputType>,
@@ -22,7 +29,7 @@ impl Default for AppState {
     fn default() -> Self {
         let default_givens = 28;
         Self {
-            grid: create_rw_signal(generator::generate(default_givens)),
+            session: create_rw_signal(Session::new(generator::generate(default_givens))),
             placemarks: create_rw_signal([Candidates::new(false); 81]),
             selected: create_rw_signal(None),
             givens: create_rw_signal(default_givens),
@@ -32,12 +39,28 @@ impl Default for AppState {
     }
 }
 
+impl AppState {
+    fn grid(&self) -> Grid {
+        self.session.with(|session| *session.grid())
+    }
+
+    /// Plays a move, telling the player why it was refused unless the cell is a given.
+    fn play(&self, mv: Move) {
+        let mut result = Ok(());
+        self.session.update(|session| result = session.play(mv));
+        match result {
+            Ok(()) | Err(SetError::Frozen) => {}
+            Err(error) => gloo_dialogs::alert(&error.to_string()),
+        }
+    }
+}
+
 #[component]
-fn Cell(index: usize) -> impl IntoView {
+fn Cell(cell: CellIndex) -> impl IntoView {
     let state = expect_context::<AppState>();
 
     let on_select = move || {
-        state.selected.set(Some(index));
+        state.selected.set(Some(cell));
     };
 
     let on_keydown = move |event: KeyboardEvent| {
@@ -46,19 +69,21 @@ fn Cell(index: usize) -> impl IntoView {
             if let Some(selected) = state.selected.get() {
                 match state.input_type.get() {
                     InputType::Values => {
-                        let mut grid = state.grid.get();
-                        grid.set(selected, digit, state.assisted.get());
-                        state.grid.set(grid);
+                        state.play(Move::Set {
+                            cell: selected,
+                            value: Digit::new(digit),
+                            checked: state.assisted.get(),
+                        });
                     }
                     InputType::Candidates => {
-                        if state.assisted.get() {
-                            let mut grid = state.grid.get();
-                            grid.candidates_mut(selected).toggle(digit as usize - 1);
-                            state.grid.set(grid);
-                        } else {
-                            let mut placemarks = state.placemarks.get();
-                            placemarks[selected].toggle(digit as usize - 1);
-                            state.placemarks.set(placemarks);
+                        if let Some(digit) = Digit::new(digit) {
+                            if state.assisted.get() {
+                                state.play(Move::ToggleCandidate { cell: selected, digit });
+                            } else {
+                                let mut placemarks = state.placemarks.get();
+                                placemarks[selected.index()].toggle_digit(digit);
+                                state.placemarks.set(placemarks);
+                            }
                         }
                     }
                 };
@@ -69,7 +94,7 @@ fn Cell(index: usize) -> impl IntoView {
     };
 
     let class = create_memo(move |_| {
-        if state.selected.get() == Some(index) {
+        if state.selected.get() == Some(cell) {
             "cell selected"
         } else {
             "cell"
@@ -77,15 +102,15 @@ fn Cell(index: usize) -> impl IntoView {
     });
 
     view! {
-        {move || if (state.grid.get()).get(index) > 0 {
-            let value = char::from_digit((state.grid.get()).get(index).into(), 10)
+        {move || if state.grid().get(cell) > 0 {
+            let value = char::from_digit(state.grid().get(cell).into(), 10)
                 .unwrap()
                 .to_string();
             view! {
                 <div
                     tabindex=0
                     role="button"
-                    aria-label=(index.to_string())
+                    aria-label=(cell.to_string())
                     class=(class)
                     on:click=move |_| on_select()
                     on:focus=move |_| on_select()
@@ -94,15 +119,15 @@ fn Cell(index: usize) -> impl IntoView {
             }
         } else {
             let candidates = if state.assisted.get() {
-                *state.grid.get().candidates(index)
+                *state.grid().candidates(cell)
             } else {
-                state.placemarks.get()[index]
+                state.placemarks.get()[cell.index()]
             };
             let candidates =
-                (0..9)
-                    .map(|candidate| {
-                        if candidates.get(candidate) {
-                            view! { <div class="candidate">{candidate + 1}</div> }
+                Digit::all()
+                    .map(|digit| {
+                        if candidates.get_digit(digit) {
+                            view! { <div class="candidate">{digit.get()}</div> }
                         } else {
                             view! { <div class="candidate"></div> }
                         }
@@ -112,7 +137,7 @@ fn Cell(index: usize) -> impl IntoView {
                 <div
                     tabindex=0
                     role="button"
-                    aria-label=(index.to_string())
+                    aria-label=(cell.to_string())
                     class=(class)
                     on:click=move |_| on_select()
                     on:focus=move |_| on_select()
@@ -125,30 +150,83 @@ fn Cell(index: usize) -> impl IntoView {
     }
 }
 
+/// Alerts the player about cells with the same value in a unit, returning whether there were any.
+fn alert_conflicts(grid: &Grid) -> bool {
+    let conflicts = grid.conflicts();
+    if conflicts.is_empty() {
+        return false;
+    }
+    let conflicts = conflicts
+        .iter()
+        .map(|conflict| {
+            format!(
+                "Cells {} and {} in {}",
+                conflict.cells.0, conflict.cells.1, conflict.unit
+            )
+        })
+        .collect::<Vec<String>>()
+        .join("\n");
+    gloo_dialogs::alert(&format!("Current sudoku has conflicts:\n{}", conflicts));
+    true
+}
+
 #[component]
 fn App() -> impl IntoView {
     provide_context(AppState::default());
     let state = expect_context::<AppState>();
 
     let on_clear = move |_| {
-        state.grid.set(Grid::new());
+        state.session.set(Session::new(Grid::new()));
         state.placemarks.set([Candidates::new(false); 81]);
     };
 
     let on_check = move |_| {
-        if state.grid.get().is_valid() {
+        let grid = state.grid();
+        if alert_conflicts(&grid) {
+            return;
+        }
+        let mut givens = Grid::new();
+        for cell in CellIndex::all().filter(|cell| grid.frozen(*cell)) {
+            givens.set_unchecked(cell, Digit::new(grid.get(cell))).unwrap();
+        }
+        let solutions = alx_solve(&givens, 2);
+        let mistakes = match solutions.as_slice() {
+            [solution] => grid.mistakes(solution),
+            [] => {
+                gloo_dialogs::alert("The givens have no solution, so mistakes can't be told");
+                return;
+            }
+            _ => {
+                gloo_dialogs::alert("The givens have several solutions, so mistakes can't be told");
+                return;
+            }
+        };
+        if mistakes.is_empty() {
             gloo_dialogs::alert("Current sudoku is valid!");
         } else {
-            gloo_dialogs::alert("Current sudoku is invalid.");
+            let cells = mistakes
+                .iter()
+                .map(|cell| cell.to_string())
+                .collect::<Vec<String>>()
+                .join(", ");
+            gloo_dialogs::alert(&format!("Current sudoku has mistakes in cells {}", cells));
         }
     };
 
     let on_solve = move |_| {
-        let solutions = alx_solve(&state.grid.get(), 2);
+        let grid = state.grid();
+        if alert_conflicts(&grid) {
+            return;
+        }
+        let solutions = alx_solve(&grid, 2);
         if solutions.is_empty() {
             gloo_dialogs::alert("No solution found");
         } else if solutions.len() == 1 {
-            state.grid.set(solutions[0]);
+            state.session.update(|session| {
+                for cell in CellIndex::all().filter(|cell| grid.get(*cell) == 0) {
+                    session.set(cell, Digit::new(solutions[0].get(cell)), false).unwrap();
+                }
+            });
         } else {
             gloo_dialogs::alert("Multiple solutions found");
         }
@@ -156,10 +234,34 @@ fn App() -> impl IntoView {
 
     let on_generate = move |_| {
         state.selected.set(None);
-        state.grid.set(generator::generate(state.givens.get()));
+        state.session.set(Session::new(generator::generate(state.givens.get())));
         state.placemarks.set([Candidates::new(false); 81]);
     };
 
+    let on_undo = move |_| {
+        state.session.update(|session| {
+            session.undo();
+        });
+    };
+
+    let on_redo = move |_| {
+        state.session.update(|session| {
+            session.redo();
+        });
+    };
+
+    let on_pencilmarks = move |_| {
+        if state.assisted.get() {
+            state.session.update(|session| {
+                session.fill_candidates();
+            });
+        } else {
+            let mut placemarks = state.placemarks.get();
+            state.grid().fill_pencilmarks(&mut placemarks);
+            state.placemarks.set(placemarks);
+        }
+    };
+
     let on_givens = move |event| {
         state.givens.set(event_target_value(&event).parse().unwrap());
     };
@@ -172,7 +274,7 @@ fn App() -> impl IntoView {
         if let Some(sudoku) = gloo_dialogs::prompt("Insert sudoku in 00001002... format", None) {
             match sudoku.parse() {
                 Ok(sudoku) => {
-                    state.grid.set(sudoku);
+                    state.session.set(Session::new(sudoku));
                 }
                 Err(error) => gloo_dialogs::alert(&format!("{}", error)),
             }
@@ -181,27 +283,27 @@ fn App() -> impl IntoView {
 
     let on_export = move |_| {
         let clipboard = window().navigator().clipboard().unwrap();
-        let _ = clipboard.write_text(&format!("{}", state.grid.get()));
+        let _ = clipboard.write_text(&format!("{}", state.grid()));
         gloo_dialogs::alert("Sudoku exported to clipboard");
     };
 
     let on_value_changed = move |value| {
         if let Some(selected) = state.selected.get() {
-            let mut temp = state.grid.get();
-            temp.set(selected, value as u8, state.assisted.get());
-            state.grid.set(temp);
+            state.play(Move::Set {
+                cell: selected,
+                value: Digit::new(value as u8),
+                checked: state.assisted.get(),
+            });
         }
     };
 
-    let on_candidate_changed = move |candidate: usize| {
+    let on_candidate_changed = move |digit: Digit| {
         if let Some(selected) = state.selected.get() {
             if state.assisted.get() {
-                let mut temp = state.grid.get();
-                temp.candidates_mut(selected).toggle(candidate - 1);
-                state.grid.set(temp);
+                state.play(Move::ToggleCandidate { cell: selected, digit });
             } else {
                 let mut temp = state.placemarks.get();
-                temp[selected].toggle(candidate - 1);
+                temp[selected.index()].toggle_digit(digit);
                 state.placemarks.set(temp);
             }
         }
@@ -216,10 +318,13 @@ fn App() -> impl IntoView {
                     <button on:click=on_check>Check</button>
                     <button on:click=on_solve>Solve</button>
                     <button on:click=on_generate>Generate</button>
+                    <button on:click=on_undo disabled=move || !state.session.with(Session::can_undo)>Undo</button>
+                    <button on:click=on_redo disabled=move || !state.session.with(Session::can_redo)>Redo</button>
                     <input type="number" id="givens" prop:value=move || state.givens.get() size=3 min=17 max=81 on:input=on_givens />
                     <label for="givens">Givens</label>
                 </div>
                 <div class="controls">
+                    <button on:click=on_pencilmarks>Pencilmarks</button>
                     <input type="checkbox" id="assisted" prop:checked=move || state.assisted.get() on:change=on_assisted />
                     <label for="assisted">Assisted</label>
                 </div>
@@ -229,10 +334,10 @@ fn App() -> impl IntoView {
                 </div>
             </header>
             <section id="grid">
-                {(0..81)
-                    .map(|index| {
+                {CellIndex::all()
+                    .map(|cell| {
                         view! {
-                            <Cell index />
+                            <Cell cell />
                         }
                     })
                     .collect_view()}
@@ -261,7 +366,7 @@ fn App() -> impl IntoView {
                 <div id="value_inputs" class:active=move || state.input_type.get() == InputType::Values>
                     {move || (1..=9_usize).map(|i| {
                         let checked = if let Some(selected) = state.selected.get() {
-                            (state.grid.get()).get(selected) == i as u8
+                            state.grid().get(selected) == i as u8
                         } else {
                             false
                         };
@@ -284,7 +389,7 @@ fn App() -> impl IntoView {
                         value="0"
                         on:change=move |_| on_value_changed(0)
                         checked=move || if let Some(selected) = state.selected.get() {
-                            (state.grid.get()).get(selected) == 0
+                            state.grid().get(selected) == 0
                         } else {
                             false
                         }
@@ -295,12 +400,12 @@ fn App() -> impl IntoView {
                     id="candidate_inputs"
                     class:active=move || state.input_type.get() == InputType::Candidates
                 >
-                    {move || (1..=9_usize).map(|i| {
+                    {move || Digit::all().map(|digit| {
                         let checked = if let Some(selected) = state.selected.get() {
                             if state.assisted.get() {
-                                (state.grid.get()).candidates(selected).get(i - 1)
+                                state.grid().candidates(selected).get_digit(digit)
                             } else {
-                                (state.placemarks.get())[selected].get(i - 1)
+                                (state.placemarks.get())[selected.index()].get_digit(digit)
                             }
                         } else {
                             false
@@ -309,12 +414,12 @@ fn App() -> impl IntoView {
                             <input
                                 type="checkbox"
                                 name="candidate"
-                                id=format!("candidate_{}", i)
-                                value=i
-                                on:change=move |_| on_candidate_changed(i)
+                                id=format!("candidate_{}", digit)
+                                value=digit.get()
+                                on:change=move |_| on_candidate_changed(digit)
                                 prop:checked=checked
                             />
-                            <label for=format!("candidate_{}", i)>{i}</label>
+                            <label for=format!("candidate_{}", digit)>{digit.get()}</label>
                         }
                     }).collect_view()}
                 </div>
@@ -1,8 +1,29 @@
-#[derive(Copy, Clone, Debug)]
+use core::fmt;
+use core::ops::{BitAnd, BitOr, Not};
+
+use super::coordinates::Digit;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// A set of candidates for a cell. Single candidates are addressed either by their index from 0 to 8 or by their `Digit`,
+/// while the iterator and the single candidate accessors give the digits as numbers from 1 to 9.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(try_from = "u16", into = "u16")
+)]
 pub struct Candidates {
     value: u16,
 }
 
+#[derive(PartialEq, Eq, Error, Debug)]
+pub enum CandidatesError {
+    #[error("Invalid candidates {0}, expected a set of nine bits")]
+    OutOfRange(u16),
+}
+
 impl Candidates {
     pub fn new(all: bool) -> Candidates {
         Candidates {
@@ -10,6 +31,11 @@ impl Candidates {
         }
     }
 
+    /// Candidates for the given digits.
+    pub fn from_digits(digits: &[Digit]) -> Candidates {
+        digits.iter().copied().collect()
+    }
+
     pub fn value(self) -> u16 {
         self.value
     }
@@ -38,6 +64,23 @@ impl Candidates {
         self.value ^= 1 << candidate;
     }
 
+    /// Whether the digit is a candidate. Unlike `get`, the digit can't be out of range.
+    pub fn get_digit(self, digit: Digit) -> bool {
+        self.get(digit.candidate())
+    }
+
+    pub fn set_digit(&mut self, digit: Digit) {
+        self.set(digit.candidate());
+    }
+
+    pub fn unset_digit(&mut self, digit: Digit) {
+        self.unset(digit.candidate());
+    }
+
+    pub fn toggle_digit(&mut self, digit: Digit) {
+        self.toggle(digit.candidate());
+    }
+
     pub fn some(self) -> bool {
         self.value > 0
     }
@@ -47,19 +90,112 @@ impl Candidates {
     }
 
     pub fn count(self) -> usize {
-        let mut count = 0;
-        let mut flipper = self.value;
-        while flipper > 0 {
-            flipper &= flipper - 1;
-            count += 1;
+        self.value.count_ones() as usize
+    }
+
+    /// The candidate digits in ascending order.
+    pub fn iter(self) -> impl Iterator<Item = u8> {
+        Digit::all().filter(move |digit| self.get_digit(*digit)).map(Digit::get)
+    }
+
+    /// The lowest candidate digit.
+    pub fn first(self) -> Option<u8> {
+        self.some().then(|| self.value.trailing_zeros() as u8 + 1)
+    }
+
+    /// The only candidate digit, if exactly one is left.
+    pub fn single(self) -> Option<u8> {
+        (self.count() == 1).then(|| self.value.trailing_zeros() as u8 + 1)
+    }
+
+    pub fn is_subset(self, other: Candidates) -> bool {
+        self.value & !other.value == 0
+    }
+
+    pub fn is_superset(self, other: Candidates) -> bool {
+        other.is_subset(self)
+    }
+}
+
+impl From<Candidates> for u16 {
+    fn from(candidates: Candidates) -> u16 {
+        candidates.value
+    }
+}
+
+impl TryFrom<u16> for Candidates {
+    type Error = CandidatesError;
+
+    fn try_from(value: u16) -> Result<Self, Self::Error> {
+        match value {
+            0..=511 => Ok(Candidates { value }),
+            _ => Err(CandidatesError::OutOfRange(value)),
+        }
+    }
+}
+
+impl FromIterator<Digit> for Candidates {
+    fn from_iter<I: IntoIterator<Item = Digit>>(digits: I) -> Self {
+        let mut candidates = Candidates::new(false);
+        for digit in digits {
+            candidates.set_digit(digit);
         }
-        count
+        candidates
+    }
+}
+
+impl BitAnd for Candidates {
+    type Output = Candidates;
+
+    fn bitand(self, other: Candidates) -> Candidates {
+        Candidates {
+            value: self.value & other.value,
+        }
+    }
+}
+
+impl BitOr for Candidates {
+    type Output = Candidates;
+
+    fn bitor(self, other: Candidates) -> Candidates {
+        Candidates {
+            value: self.value | other.value,
+        }
+    }
+}
+
+impl Not for Candidates {
+    type Output = Candidates;
+
+    fn not(self) -> Candidates {
+        Candidates {
+            value: !self.value & 511,
+        }
+    }
+}
+
+/// Formats the candidate digits in ascending order, such as `1379`.
+impl fmt::Display for Candidates {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for digit in self.iter() {
+            write!(f, "{}", digit)?;
+        }
+        Ok(())
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use alloc::{string::ToString, vec, vec::Vec};
+
+    fn digit(value: u8) -> Digit {
+        Digit::new(value).unwrap()
+    }
+
+    fn digit_list(values: &[u8]) -> Vec<Digit> {
+        values.iter().map(|value| digit(*value)).collect()
+    }
 
     #[test]
     fn get_value() {
@@ -87,6 +223,12 @@ mod tests {
         assert_eq!(c.value(), 511);
     }
 
+    #[test]
+    fn from_value() {
+        assert_eq!(Candidates::try_from(511), Ok(Candidates::new(true)));
+        assert_eq!(Candidates::try_from(512), Err(CandidatesError::OutOfRange(512)));
+    }
+
     #[test]
     fn mega_test() {
         let mut c = Candidates::new(true);
@@ -108,4 +250,40 @@ mod tests {
         }
         assert!(c.some());
     }
+
+    #[test]
+    fn digits() {
+        let c = Candidates::from_digits(&digit_list(&[3, 1, 9]));
+        assert_eq!(c.iter().collect::<Vec<u8>>(), vec![1, 3, 9]);
+        assert_eq!(c.to_string(), "139");
+        assert_eq!(c.first(), Some(1));
+        assert_eq!(c.single(), None);
+        assert_eq!(Candidates::from_digits(&digit_list(&[5])).single(), Some(5));
+        assert_eq!(Candidates::new(false).first(), None);
+        assert_eq!(Candidates::new(false).to_string(), "");
+        let mut c = Candidates::new(false);
+        c.set_digit(digit(9));
+        assert!(c.get_digit(digit(9)) && c.get(8));
+        c.toggle_digit(digit(1));
+        c.unset_digit(digit(9));
+        assert_eq!(c.iter().collect::<Vec<u8>>(), vec![1]);
+        assert_eq!(
+            digit_list(&[2, 3]).into_iter().collect::<Candidates>(),
+            Candidates::from_digits(&digit_list(&[3, 2]))
+        );
+    }
+
+    #[test]
+    fn set_operations() {
+        let a = Candidates::from_digits(&digit_list(&[1, 2, 3]));
+        let b = Candidates::from_digits(&digit_list(&[3, 4]));
+        assert_eq!(a & b, Candidates::from_digits(&digit_list(&[3])));
+        assert_eq!(a | b, Candidates::from_digits(&digit_list(&[1, 2, 3, 4])));
+        assert_eq!(!a, Candidates::from_digits(&digit_list(&[4, 5, 6, 7, 8, 9])));
+        assert_eq!(!Candidates::new(true), Candidates::new(false));
+        assert!((a & b).is_subset(a));
+        assert!(a.is_superset(a & b));
+        assert!(!a.is_subset(b));
+        assert!(Candidates::new(false).is_subset(b));
+    }
 }
@@ -0,0 +1,203 @@
+use alloc::{boxed::Box, vec::Vec};
+use core::cmp::Ordering;
+use itertools::iproduct;
+use once_cell::race::OnceBox;
+
+use super::coordinates::{CellIndex, Digit};
+use super::grid::Grid;
+
+const PERMUTATIONS: [[usize; 3]; 6] = [[0, 1, 2], [0, 2, 1], [1, 0, 2], [1, 2, 0], [2, 0, 1], [2, 1, 0]];
+
+static ORDERS: OnceBox<Vec<[usize; 9]>> = OnceBox::new();
+
+// Every order of the nine columns that keeps stacks together
+fn orders() -> &'static [[usize; 9]] {
+    ORDERS.get_or_init(|| {
+        Box::new(
+            iproduct!(PERMUTATIONS, PERMUTATIONS, PERMUTATIONS, PERMUTATIONS)
+                .map(|(bands, first, second, third)| {
+                    let inner = [first, second, third];
+                    let mut order = [0; 9];
+                    for (band, source) in bands.iter().enumerate() {
+                        for (row, inner_row) in inner[band].iter().enumerate() {
+                            order[band * 3 + row] = source * 3 + inner_row;
+                        }
+                    }
+                    order
+                })
+                .collect(),
+        )
+    })
+}
+
+struct Candidate {
+    transposed: bool,
+    rows: [usize; 9],
+    columns: [usize; 9],
+    values: [u8; 81],
+}
+
+struct Search<'a> {
+    source: &'a [u8; 81],
+    transposed: bool,
+    columns: &'a [usize; 9],
+    rows: [usize; 9],
+    best: &'a mut Candidate,
+}
+
+impl Search<'_> {
+    /// Picks the source rows one result row at a time, keeping bands together, and prunes as soon as a relabeled row
+    /// is greater than the same row of the best grid so far. Digits are relabeled in order of first appearance, so
+    /// the first digit read becomes 1.
+    fn search(&mut self, row: usize, labels: [u8; 10], next: u8, improved: bool) {
+        if row == 9 {
+            if improved {
+                self.best.transposed = self.transposed;
+                self.best.rows = self.rows;
+                self.best.columns = *self.columns;
+            }
+            return;
+        }
+        let choices = if row.is_multiple_of(3) {
+            (0..9)
+                .step_by(3)
+                .filter(|band| !self.rows[..row].contains(band))
+                .flat_map(|band| band..band + 3)
+                .collect::<Vec<usize>>()
+        } else {
+            let band = self.rows[row - 1] / 3 * 3;
+            (band..band + 3)
+                .filter(|source_row| !self.rows[row - row % 3..row].contains(source_row))
+                .collect()
+        };
+        for source_row in choices {
+            let mut labels = labels;
+            let mut next = next;
+            let mut values = [0; 9];
+            for (column, source_column) in self.columns.iter().enumerate() {
+                let value = self.source[source_row * 9 + source_column] as usize;
+                if value > 0 && labels[value] == 0 {
+                    labels[value] = next;
+                    next += 1;
+                }
+                values[column] = labels[value];
+            }
+            let best = &mut self.best.values[row * 9..];
+            let improved = match values.as_slice().cmp(&best[..9]) {
+                Ordering::Greater => continue,
+                Ordering::Equal => improved,
+                Ordering::Less => {
+                    best[..9].copy_from_slice(&values);
+                    best[9..].fill(10);
+                    true
+                }
+            };
+            self.rows[row] = source_row;
+            self.search(row + 1, labels, next, improved);
+        }
+    }
+}
+
+fn minimal(grid: &Grid) -> Candidate {
+    let values: [u8; 81] = core::array::from_fn(|index| grid.get(CellIndex::at(index)));
+    let transposed: [u8; 81] = core::array::from_fn(|index| grid.get(CellIndex::at(index % 9 * 9 + index / 9)));
+    let mut best = Candidate {
+        transposed: false,
+        rows: orders()[0],
+        columns: orders()[0],
+        values: [10; 81],
+    };
+    for (transpose, source) in [(false, &values), (true, &transposed)] {
+        for columns in orders().iter() {
+            Search {
+                source,
+                transposed: transpose,
+                columns,
+                rows: [0; 9],
+                best: &mut best,
+            }
+            .search(0, [0; 10], 1, false);
+        }
+    }
+    best
+}
+
+/// Returns the lexicographically smallest grid, with blanks as 0, reachable by relabeling digits, transposing, and
+/// permuting bands, stacks, and the rows and columns within them. Equivalent grids share the same canonical form.
+/// Frozen flags follow their cells and candidates are recalculated.
+pub fn canonicalize(grid: &Grid) -> Grid {
+    let best = minimal(grid);
+    let mut canonical = Grid::new();
+    for (row, source_row) in best.rows.iter().enumerate() {
+        for (column, source_column) in best.columns.iter().enumerate() {
+            let cell = CellIndex::at(row * 9 + column);
+            let source = CellIndex::at(if best.transposed {
+                source_column * 9 + source_row
+            } else {
+                source_row * 9 + source_column
+            });
+            canonical
+                .set_unchecked(cell, Digit::new(best.values[cell.index()]))
+                .unwrap();
+            if grid.frozen(source) {
+                canonical.freeze(cell);
+            }
+        }
+    }
+    canonical.recompute_all_candidates();
+    canonical
+}
+
+/// Whether the grids are the same puzzle up to relabeling digits and the validity preserving permutations.
+pub fn are_equivalent(a: &Grid, b: &Grid) -> bool {
+    let filled = |grid: &Grid| grid.cells().filter(|value| *value > 0).count();
+    filled(a) == filled(b) && minimal(a).values == minimal(b).values
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::{String, ToString};
+
+    #[test]
+    fn canonical_test() {
+        let grid: Grid = "000000010400000000020000000000050407008000300001090000300400200050100000000806000"
+            .parse()
+            .unwrap();
+        let canonical = canonicalize(&grid);
+        assert_eq!(
+            canonical.to_string(),
+            "000000001000000020000003000000040500006000300007810000010020004030000070950000000"
+        );
+        assert_eq!(canonical.givens(), 17);
+        assert!(canonical.is_valid());
+        assert_eq!(canonicalize(&canonical), canonical);
+    }
+
+    #[test]
+    fn equivalent_test() {
+        let grid: Grid = "000000010400000000020000000000050407008000300001090000300400200050100000000806000"
+            .parse()
+            .unwrap();
+        // Transposed, with the first two bands swapped and digits 1 and 2 exchanged
+        let transposed: String = (0..81)
+            .map(|index| {
+                let (row, column) = (index / 9, index % 9);
+                let row = [3, 4, 5, 0, 1, 2, 6, 7, 8][row];
+                match grid.get(CellIndex::from_coords(column, row).unwrap()) {
+                    1 => '2',
+                    2 => '1',
+                    value => char::from_digit(value as u32, 10).unwrap(),
+                }
+            })
+            .collect();
+        let other: Grid = transposed.parse().unwrap();
+        assert!(are_equivalent(&grid, &other));
+        assert_eq!(canonicalize(&grid), canonicalize(&other));
+
+        let different: Grid = "000000010400000000020000000000050604008000300001090000300400200050100000000807000"
+            .parse()
+            .unwrap();
+        assert!(!are_equivalent(&grid, &different));
+    }
+}
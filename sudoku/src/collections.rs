@@ -0,0 +1,31 @@
+//! The maps and sets used by the solvers: ahash tables with std, B-trees in no_std builds.
+
+#[cfg(feature = "std")]
+pub(crate) use ahash::{AHashMap as Map, AHashSet as Set};
+#[cfg(not(feature = "std"))]
+pub(crate) use alloc::collections::{BTreeMap as Map, BTreeSet as Set};
+
+/// A map key, which has to be both hashable and ordered to work with either map.
+pub(crate) trait Key: Eq + Ord + core::hash::Hash + Copy {}
+
+impl<T: Eq + Ord + core::hash::Hash + Copy> Key for T {}
+
+#[cfg(feature = "std")]
+pub(crate) fn map<K, V>(capacity: usize) -> Map<K, V> {
+    Map::with_capacity(capacity)
+}
+
+#[cfg(not(feature = "std"))]
+pub(crate) fn map<K, V>(_capacity: usize) -> Map<K, V> {
+    Map::new()
+}
+
+#[cfg(feature = "std")]
+pub(crate) fn set<T>(capacity: usize) -> Set<T> {
+    Set::with_capacity(capacity)
+}
+
+#[cfg(not(feature = "std"))]
+pub(crate) fn set<T>(_capacity: usize) -> Set<T> {
+    Set::new()
+}
@@ -0,0 +1,325 @@
+use alloc::{
+    borrow::ToOwned,
+    string::{String, ToString},
+};
+use core::{fmt, str::FromStr};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use super::grid::PEERS;
+
+/// A cell of a grid, formatted and parsed in R1C1 notation as `r3c5`. It's always in range, so the grid accessors
+/// taking one can't be handed a cell outside the grid.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(try_from = "usize", into = "usize")
+)]
+pub struct CellIndex(u8);
+
+impl CellIndex {
+    pub fn new(index: usize) -> Option<CellIndex> {
+        (index < 81).then_some(CellIndex(index as u8))
+    }
+
+    /// The cell at an index the crate computed itself, like those of the peer tables.
+    pub(crate) const fn at(index: usize) -> CellIndex {
+        debug_assert!(index < 81);
+        CellIndex(index as u8)
+    }
+
+    /// The position of the cell in row order, from 0 to 80.
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+
+    /// The cell at the row and column, both from 0 to 8.
+    pub fn from_coords(row: usize, column: usize) -> Option<CellIndex> {
+        (row < 9 && column < 9).then_some(CellIndex((row * 9 + column) as u8))
+    }
+
+    /// Every cell in row order.
+    pub fn all() -> impl Iterator<Item = CellIndex> {
+        (0..81).map(CellIndex)
+    }
+
+    pub fn row(self) -> usize {
+        self.0 as usize / 9
+    }
+
+    pub fn column(self) -> usize {
+        self.0 as usize % 9
+    }
+
+    pub fn box_index(self) -> usize {
+        self.row() / 3 * 3 + self.column() / 3
+    }
+
+    /// The row, column and box containing the cell.
+    pub fn units(self) -> [Unit; 3] {
+        [
+            Unit::Row(UnitIndex(self.row() as u8)),
+            Unit::Column(UnitIndex(self.column() as u8)),
+            Unit::Box(UnitIndex(self.box_index() as u8)),
+        ]
+    }
+
+    pub fn peers(self) -> impl Iterator<Item = CellIndex> {
+        PEERS[self.0 as usize].iter().map(|peer| CellIndex(*peer as u8))
+    }
+}
+
+impl From<CellIndex> for usize {
+    fn from(cell: CellIndex) -> usize {
+        cell.0 as usize
+    }
+}
+
+impl TryFrom<usize> for CellIndex {
+    type Error = CoordinateError;
+
+    fn try_from(index: usize) -> Result<Self, Self::Error> {
+        CellIndex::new(index).ok_or_else(|| CoordinateError::InvalidCell(index.to_string()))
+    }
+}
+
+impl fmt::Display for CellIndex {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "r{}c{}", self.row() + 1, self.column() + 1)
+    }
+}
+
+/// Parses R1C1 notation such as `r3c5` or `R3C5`.
+impl FromStr for CellIndex {
+    type Err = CoordinateError;
+
+    fn from_str(string: &str) -> Result<Self, Self::Err> {
+        let invalid = || CoordinateError::InvalidCell(string.to_owned());
+        let lowercase = string.to_ascii_lowercase();
+        let (row, column) = lowercase
+            .strip_prefix('r')
+            .and_then(|rest| rest.split_once('c'))
+            .ok_or_else(invalid)?;
+        let coordinate = |part: &str| match part.parse::<usize>() {
+            Ok(number @ 1..=9) => Ok(number - 1),
+            _ => Err(invalid()),
+        };
+        CellIndex::from_coords(coordinate(row)?, coordinate(column)?).ok_or_else(invalid)
+    }
+}
+
+/// A digit from 1 to 9.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(try_from = "u8", into = "u8")
+)]
+pub struct Digit(u8);
+
+impl Digit {
+    pub fn new(digit: u8) -> Option<Digit> {
+        (1..10).contains(&digit).then_some(Digit(digit))
+    }
+
+    pub fn all() -> impl Iterator<Item = Digit> {
+        (1..10).map(Digit)
+    }
+
+    pub fn get(self) -> u8 {
+        self.0
+    }
+
+    /// The index of the digit in `Candidates`, from 0 to 8.
+    pub fn candidate(self) -> usize {
+        self.0 as usize - 1
+    }
+}
+
+impl From<Digit> for u8 {
+    fn from(digit: Digit) -> u8 {
+        digit.0
+    }
+}
+
+impl TryFrom<u8> for Digit {
+    type Error = CoordinateError;
+
+    fn try_from(digit: u8) -> Result<Self, Self::Error> {
+        Digit::new(digit).ok_or_else(|| CoordinateError::InvalidDigit(digit.to_string()))
+    }
+}
+
+impl fmt::Display for Digit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl FromStr for Digit {
+    type Err = CoordinateError;
+
+    fn from_str(string: &str) -> Result<Self, Self::Err> {
+        string
+            .parse()
+            .ok()
+            .and_then(Digit::new)
+            .ok_or_else(|| CoordinateError::InvalidDigit(string.to_owned()))
+    }
+}
+
+/// The number of a row, column or box, from 0 to 8. Like `CellIndex`, it's always in range.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct UnitIndex(u8);
+
+impl UnitIndex {
+    pub fn new(index: usize) -> Option<UnitIndex> {
+        (index < 9).then_some(UnitIndex(index as u8))
+    }
+
+    /// The unit at an index the crate computed itself.
+    pub(crate) const fn at(index: usize) -> UnitIndex {
+        debug_assert!(index < 9);
+        UnitIndex(index as u8)
+    }
+
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+
+    pub fn all() -> impl Iterator<Item = UnitIndex> {
+        (0..9).map(UnitIndex)
+    }
+}
+
+impl From<UnitIndex> for usize {
+    fn from(index: UnitIndex) -> usize {
+        index.0 as usize
+    }
+}
+
+impl TryFrom<usize> for UnitIndex {
+    type Error = CoordinateError;
+
+    fn try_from(index: usize) -> Result<Self, Self::Error> {
+        UnitIndex::new(index).ok_or_else(|| CoordinateError::InvalidUnit(index.to_string()))
+    }
+}
+
+/// A row, column or box. Boxes are numbered left to right, top to bottom.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Unit {
+    Row(UnitIndex),
+    Column(UnitIndex),
+    Box(UnitIndex),
+}
+
+impl Unit {
+    /// Every row, then every column, then every box.
+    pub fn all() -> impl Iterator<Item = Unit> {
+        UnitIndex::all()
+            .map(Unit::Row)
+            .chain(UnitIndex::all().map(Unit::Column))
+            .chain(UnitIndex::all().map(Unit::Box))
+    }
+
+    /// The cells of the unit, left to right and top to bottom.
+    pub fn cells(self) -> impl Iterator<Item = CellIndex> {
+        (0..9).map(move |position| {
+            let (row, column) = match self {
+                Unit::Row(row) => (row.index(), position),
+                Unit::Column(column) => (position, column.index()),
+                Unit::Box(r#box) => (
+                    r#box.index() / 3 * 3 + position / 3,
+                    r#box.index() % 3 * 3 + position % 3,
+                ),
+            };
+            CellIndex((row * 9 + column) as u8)
+        })
+    }
+}
+
+impl fmt::Display for Unit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Unit::Row(row) => write!(f, "row {}", row.index() + 1),
+            Unit::Column(column) => write!(f, "column {}", column.index() + 1),
+            Unit::Box(r#box) => write!(f, "box {}", r#box.index() + 1),
+        }
+    }
+}
+
+#[derive(PartialEq, Eq, Error, Debug)]
+pub enum CoordinateError {
+    #[error("Invalid cell '{0}', expected R1C1 notation such as r3c5")]
+    InvalidCell(String),
+    #[error("Invalid digit '{0}', expected 1-9")]
+    InvalidDigit(String),
+    #[error("Invalid unit '{0}', expected 0-8")]
+    InvalidUnit(String),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::{vec, vec::Vec};
+
+    fn unit(index: usize) -> UnitIndex {
+        UnitIndex::new(index).unwrap()
+    }
+
+    #[test]
+    fn cell_index() {
+        let cell: CellIndex = "r3c5".parse().unwrap();
+        assert_eq!(usize::from(cell), 22);
+        assert_eq!((cell.row(), cell.column(), cell.box_index()), (2, 4, 1));
+        assert_eq!(cell.to_string(), "r3c5");
+        assert_eq!("R3C5".parse::<CellIndex>(), Ok(cell));
+        assert_eq!(
+            cell.units(),
+            [Unit::Row(unit(2)), Unit::Column(unit(4)), Unit::Box(unit(1))]
+        );
+        assert_eq!(cell.peers().count(), 20);
+        assert_eq!(CellIndex::all().count(), 81);
+        assert_eq!(CellIndex::new(81), None);
+        assert_eq!(CellIndex::try_from(80), Ok(CellIndex::from_coords(8, 8).unwrap()));
+        for invalid in ["r0c1", "r1c10", "r1", "c1r1", "x"] {
+            assert_eq!(
+                invalid.parse::<CellIndex>(),
+                Err(CoordinateError::InvalidCell(invalid.to_owned()))
+            );
+        }
+    }
+
+    #[test]
+    fn digit() {
+        assert_eq!(Digit::new(0), None);
+        assert_eq!(Digit::new(10), None);
+        assert_eq!("5".parse::<Digit>().unwrap().candidate(), 4);
+        assert_eq!(Digit::try_from(0), Err(CoordinateError::InvalidDigit("0".to_owned())));
+        assert_eq!(
+            Digit::all().map(u8::from).collect::<Vec<u8>>(),
+            (1..10).collect::<Vec<u8>>()
+        );
+    }
+
+    #[test]
+    fn units() {
+        assert_eq!(Unit::all().count(), 27);
+        assert_eq!(
+            Unit::Box(unit(4)).cells().map(usize::from).collect::<Vec<usize>>(),
+            vec![30, 31, 32, 39, 40, 41, 48, 49, 50]
+        );
+        assert_eq!(Unit::Column(unit(8)).cells().last(), CellIndex::new(80));
+        assert_eq!(UnitIndex::new(9), None);
+        assert_eq!(
+            UnitIndex::try_from(42),
+            Err(CoordinateError::InvalidUnit("42".to_owned()))
+        );
+        for unit in Unit::all() {
+            assert!(unit.cells().all(|cell| cell.units().contains(&unit)));
+        }
+    }
+}
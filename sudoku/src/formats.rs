@@ -0,0 +1,196 @@
+use alloc::{borrow::ToOwned, boxed::Box, format, string::String, vec, vec::Vec};
+use core::{error::Error, fmt, str::FromStr};
+use thiserror::Error;
+
+use super::coordinates::{CellIndex, Digit};
+use super::grid::Grid;
+
+pub mod hodoku;
+pub mod opensudoku;
+pub mod sdk;
+pub mod ss;
+
+/// A puzzle along with the metadata stored by the puzzle file formats. Givens are frozen in the grid, while values
+/// entered by the player are not.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Puzzle {
+    pub grid: Grid,
+    pub title: Option<String>,
+    pub author: Option<String>,
+    pub description: Option<String>,
+    pub difficulty: Option<String>,
+    /// The Hodoku technique code, followed by its candidates when there are some, such as `0100` or `0901:5`
+    pub technique: Option<String>,
+    /// The candidates the Hodoku technique eliminates, or the values it places for singles
+    pub eliminations: Vec<(CellIndex, Digit)>,
+}
+
+impl Puzzle {
+    pub fn new(grid: Grid) -> Self {
+        Puzzle {
+            grid,
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum FormatError {
+    #[error("Invalid puzzle on line {0}: {1}")]
+    InvalidPuzzle(usize, Box<dyn Error + Sync + Send>),
+    #[error("Invalid entry in {0} conflicts with the puzzle")]
+    InvalidEntry(CellIndex),
+    #[error("Invalid XML: {0}")]
+    InvalidXml(String),
+    #[error("Expected a single puzzle but found {0}")]
+    PuzzleCount(usize),
+    #[error("Unknown format '{0}'")]
+    UnknownFormat(String),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    /// One 81 character puzzle per line
+    Plain,
+    /// SadMan Software `.sdk`
+    Sdk,
+    /// SimpleSudoku `.ss`
+    Ss,
+    /// OpenSudoku XML collection
+    OpenSudoku,
+    /// Hodoku library lines
+    Hodoku,
+}
+
+impl Format {
+    pub fn from_extension(extension: &str) -> Result<Format, FormatError> {
+        match extension.to_ascii_lowercase().as_str() {
+            "txt" => Ok(Format::Plain),
+            "sdk" => Ok(Format::Sdk),
+            "ss" => Ok(Format::Ss),
+            "opensudoku" | "xml" => Ok(Format::OpenSudoku),
+            _ => Err(FormatError::UnknownFormat(extension.to_owned())),
+        }
+    }
+
+    pub fn read(self, string: &str) -> Result<Vec<Puzzle>, FormatError> {
+        match self {
+            Format::Plain => string
+                .lines()
+                .enumerate()
+                .filter(|(_, line)| !line.trim().is_empty())
+                .map(|(line, sudoku)| {
+                    Ok(Puzzle::new(
+                        sudoku
+                            .parse()
+                            .map_err(|error| FormatError::InvalidPuzzle(line + 1, error))?,
+                    ))
+                })
+                .collect(),
+            Format::Sdk => Ok(vec![sdk::read(string)?]),
+            Format::Ss => Ok(vec![ss::read(string)?]),
+            Format::OpenSudoku => opensudoku::read(string),
+            Format::Hodoku => hodoku::read(string),
+        }
+    }
+
+    /// Writes the puzzles, failing if the format holds only a single puzzle and more were given.
+    pub fn write(self, puzzles: &[Puzzle]) -> Result<String, FormatError> {
+        match self {
+            Format::Plain => Ok(puzzles.iter().map(|puzzle| format!("{}\n", puzzle.grid)).collect()),
+            Format::Sdk => Ok(sdk::write(single(puzzles)?)),
+            Format::Ss => Ok(ss::write(single(puzzles)?)),
+            Format::OpenSudoku => Ok(opensudoku::write(puzzles)),
+            Format::Hodoku => Ok(hodoku::write(puzzles)),
+        }
+    }
+}
+
+impl FromStr for Format {
+    type Err = FormatError;
+
+    fn from_str(string: &str) -> Result<Self, Self::Err> {
+        match string.to_ascii_lowercase().as_str() {
+            "plain" => Ok(Format::Plain),
+            "hodoku" => Ok(Format::Hodoku),
+            extension => Format::from_extension(extension),
+        }
+    }
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Format::Plain => "plain",
+            Format::Sdk => "sdk",
+            Format::Ss => "ss",
+            Format::OpenSudoku => "opensudoku",
+            Format::Hodoku => "hodoku",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+fn single(puzzles: &[Puzzle]) -> Result<&Puzzle, FormatError> {
+    match puzzles {
+        [puzzle] => Ok(puzzle),
+        _ => Err(FormatError::PuzzleCount(puzzles.len())),
+    }
+}
+
+/// Enters the non-given values on top of the givens and recomputes the candidates. The values aren't checked against
+/// their peers, as a saved game can hold the player's mistakes, but they can't differ from the givens.
+fn enter(grid: &mut Grid, values: &[u8]) -> Result<(), FormatError> {
+    for (cell, value) in CellIndex::all().zip(values) {
+        match Digit::new(*value) {
+            Some(_) if grid.frozen(cell) && grid.get(cell) != *value => return Err(FormatError::InvalidEntry(cell)),
+            Some(_) if grid.frozen(cell) => {}
+            Some(digit) => grid.set_unchecked(cell, Some(digit)).unwrap(),
+            None => {}
+        }
+    }
+    grid.recompute_all_candidates();
+    Ok(())
+}
+
+/// Whether the candidate of an empty cell was removed by the player rather than by a peer's value.
+fn eliminated(grid: &Grid, cell: CellIndex, digit: Digit) -> bool {
+    grid.get(cell) == 0
+        && !grid.candidates(cell).get_digit(digit)
+        && !cell.peers().any(|peer| grid.get(peer) == digit.get())
+}
+
+fn digit(value: u8, blank: char) -> char {
+    match value {
+        0 => blank,
+        value => char::from_digit(value as u32, 10).unwrap(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn format_names() {
+        assert_eq!("SDK".parse::<Format>().unwrap(), Format::Sdk);
+        assert_eq!("Hodoku".parse::<Format>().unwrap(), Format::Hodoku);
+        assert!(Format::from_extension("hsol").is_err());
+        assert_eq!(Format::from_extension("xml").unwrap(), Format::OpenSudoku);
+        assert!(matches!(
+            "doc".parse::<Format>(),
+            Err(FormatError::UnknownFormat(extension)) if extension == "doc"
+        ));
+    }
+
+    #[test]
+    fn single_puzzle_formats() {
+        let grid: Grid = "060000300400700000000000080000008012500600000000000050082000700000500600000010000"
+            .parse()
+            .unwrap();
+        let puzzles = vec![Puzzle::new(grid); 2];
+        assert!(matches!(Format::Sdk.write(&puzzles), Err(FormatError::PuzzleCount(2))));
+        let plain = Format::Plain.write(&puzzles).unwrap();
+        assert_eq!(Format::Plain.read(&plain).unwrap(), puzzles);
+    }
+}
@@ -0,0 +1,214 @@
+//! Hodoku library lines in the form `:technique:candidates:puzzle:deleted candidates:eliminations:extra:`.
+//!
+//! In the puzzle, digits are givens, digits prefixed with `+` are the player's entries and `.` or `0` are blanks.
+//! Deleted candidates are listed as `digit row column` triples separated by spaces, such as `512 613`, and so are the
+//! eliminations the technique should find, or the values it places for singles.
+//!
+//! The technique code, along with its candidates when they aren't `x`, is kept as the puzzle's technique, such as
+//! `0100` or `0901:5`, and the eliminations as its eliminations.
+//!
+//! These are the lines of Hodoku's puzzle libraries, which are plain text. Its saved `.hsol` games are XML and aren't
+//! read.
+
+use alloc::{
+    borrow::ToOwned,
+    boxed::Box,
+    format,
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+use itertools::Itertools;
+
+use super::{eliminated, enter, FormatError, Puzzle};
+use crate::coordinates::{CellIndex, Digit};
+use crate::grid::{Grid, ParseError};
+
+fn parse_line(line: &str) -> Result<Puzzle, Box<dyn core::error::Error + Sync + Send>> {
+    let fields = line.split(':').collect::<Vec<&str>>();
+    if fields.len() < 5 || !fields[0].is_empty() {
+        return Err(format!("Expected ':' separated fields in '{}'", line).into());
+    }
+
+    let mut givens = String::with_capacity(81);
+    let mut values = Vec::with_capacity(81);
+    let mut entry = false;
+    for (index, c) in fields[3].char_indices() {
+        match c {
+            '+' => entry = true,
+            _ => {
+                let value = match c {
+                    '.' => 0,
+                    c => c.to_digit(10).ok_or(ParseError::InvalidDigit(c, index))? as u8,
+                };
+                givens.push(if entry { '0' } else { c });
+                values.push(value);
+                entry = false;
+            }
+        }
+    }
+    let mut grid: Grid = givens.parse()?;
+    enter(&mut grid, &values)?;
+
+    for (cell, digit) in candidates(fields[4], "deleted candidate")? {
+        grid.candidates_mut(cell).unset_digit(digit);
+    }
+    let eliminations = match fields.get(5) {
+        Some(field) => candidates(field, "elimination")?,
+        None => vec![],
+    };
+    let technique = match (fields[1], fields[2]) {
+        ("", _) => None,
+        (technique, "x" | "") => Some(technique.to_owned()),
+        (technique, candidates) => Some(format!("{}:{}", technique, candidates)),
+    };
+    Ok(Puzzle {
+        technique,
+        eliminations,
+        ..Puzzle::new(grid)
+    })
+}
+
+/// Parses the `digit row column` triples of a field, where `kind` names them in errors.
+fn candidates(field: &str, kind: &str) -> Result<Vec<(CellIndex, Digit)>, Box<dyn core::error::Error + Sync + Send>> {
+    field
+        .split_whitespace()
+        .map(|triple| {
+            let digits = triple
+                .chars()
+                .map(|c| c.to_digit(10).filter(|digit| (1..10).contains(digit)))
+                .collect::<Option<Vec<u32>>>();
+            match digits.as_deref() {
+                Some([digit, row, column]) => Ok((
+                    CellIndex::from_coords(*row as usize - 1, *column as usize - 1).unwrap(),
+                    Digit::new(*digit as u8).unwrap(),
+                )),
+                _ => Err(format!("Invalid {} '{}'", kind, triple).into()),
+            }
+        })
+        .collect()
+}
+
+fn triple((cell, digit): (CellIndex, Digit)) -> String {
+    format!("{}{}{}", digit, cell.row() + 1, cell.column() + 1)
+}
+
+/// The technique code and candidates of a puzzle read from Hodoku. Puzzles from elsewhere get an empty code, as any
+/// code would claim a technique, even `0000` which is a full house.
+fn technique(puzzle: &Puzzle) -> (&str, &str) {
+    match puzzle.technique.as_deref() {
+        Some(technique) => technique.split_once(':').unwrap_or((technique, "x")),
+        None => ("", "x"),
+    }
+}
+
+pub fn read(string: &str) -> Result<Vec<Puzzle>, FormatError> {
+    string
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty() && !line.starts_with('#'))
+        .map(|(line, text)| parse_line(text.trim()).map_err(|error| FormatError::InvalidPuzzle(line + 1, error)))
+        .collect()
+}
+
+pub fn write(puzzles: &[Puzzle]) -> String {
+    puzzles
+        .iter()
+        .map(|puzzle| {
+            let grid = &puzzle.grid;
+            let cells = CellIndex::all()
+                .map(|cell| match (grid.get(cell), grid.frozen(cell)) {
+                    (0, _) => ".".to_owned(),
+                    (value, true) => value.to_string(),
+                    (value, false) => format!("+{}", value),
+                })
+                .join("");
+            let deleted = CellIndex::all()
+                .flat_map(|cell| Digit::all().map(move |digit| (cell, digit)))
+                .filter(|(cell, digit)| eliminated(grid, *cell, *digit))
+                .map(triple)
+                .join(" ");
+            let eliminations = puzzle.eliminations.iter().copied().map(triple).join(" ");
+            let (technique, candidates) = technique(puzzle);
+            format!(
+                ":{}:{}:{}:{}:{}:\n",
+                technique, candidates, cells, deleted, eliminations
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HODOKU: &str = "\
+:0000:x:+96....3..4..7............8......8.125..6............5..82...7.....5..6......1....:513 222::
+:0901:5:.......1.4.........2...........5.4.7..8...3....1.9....3..4..2...5.1........8.6...:::
+";
+
+    #[test]
+    fn read_write() {
+        let puzzles = read(&format!("# Library\n{}", HODOKU)).unwrap();
+        assert_eq!(puzzles.len(), 2);
+        let grid = &puzzles[0].grid;
+        assert_eq!(grid.givens(), 17);
+        assert_eq!(grid.get(CellIndex::at(0)), 9);
+        assert!(!grid.frozen(CellIndex::at(0)));
+        assert!(!grid.candidates(CellIndex::at(2)).get(4));
+        assert!(!grid.candidates(CellIndex::at(10)).get(1));
+        assert!(grid.candidates(CellIndex::at(10)).get(0));
+        assert_eq!(puzzles[0].technique.as_deref(), Some("0000"));
+        assert_eq!(puzzles[1].technique.as_deref(), Some("0901:5"));
+        assert_eq!(puzzles[1].difficulty, None);
+        assert_eq!(write(&puzzles), HODOKU);
+
+        let puzzle = Puzzle {
+            difficulty: Some("Hard".to_owned()),
+            technique: None,
+            ..puzzles[1].clone()
+        };
+        let written = write(&[puzzle]);
+        assert!(written.starts_with("::x:...."));
+        assert_eq!(read(&written).unwrap()[0].technique, None);
+    }
+
+    #[test]
+    fn eliminations() {
+        let hodoku = HODOKU.replace("6...:::", "6...::512 613:");
+        let puzzles = read(&hodoku).unwrap();
+        assert_eq!(
+            puzzles[1].eliminations,
+            [
+                (CellIndex::from_coords(0, 1).unwrap(), Digit::new(5).unwrap()),
+                (CellIndex::from_coords(0, 2).unwrap(), Digit::new(6).unwrap())
+            ]
+        );
+        assert!(puzzles[0].eliminations.is_empty());
+        // The eliminations are only what the technique should find, so the candidates stay as they are
+        assert!(puzzles[1].grid.eq_full(&read(HODOKU).unwrap()[1].grid));
+        assert_eq!(write(&puzzles), hodoku);
+        assert!(matches!(
+            read(&hodoku.replace("512 613:", "512 60:")),
+            Err(FormatError::InvalidPuzzle(2, _))
+        ));
+    }
+
+    #[test]
+    fn read_invalid() {
+        assert!(matches!(read("0000:x:..."), Err(FormatError::InvalidPuzzle(1, _))));
+        assert!(matches!(
+            read(&HODOKU.replace(":513 222:", ":513 22:")),
+            Err(FormatError::InvalidPuzzle(1, _))
+        ));
+    }
+
+    #[test]
+    fn conflicting_entry() {
+        let hodoku = HODOKU.replace("+96", "+36");
+        let puzzles = read(&hodoku).unwrap();
+        assert_eq!(puzzles[0].grid.get(CellIndex::at(0)), 3);
+        assert_eq!(puzzles[0].grid.conflicts().len(), 1);
+        assert_eq!(write(&puzzles), hodoku);
+    }
+}
@@ -0,0 +1,321 @@
+//! OpenSudoku XML collections. The collection's name, author, description and level apply to every `<game>` in it.
+//!
+//! A game's `data` is either the 81 givens, or a `version: 1` header followed by `value|note|editable|` for every
+//! cell, where the note lists candidates as `1,2,` and editable cells are the player's entries.
+
+use alloc::{borrow::ToOwned, boxed::Box, format, string::String, vec, vec::Vec};
+use itertools::Itertools;
+
+use super::{digit, eliminated, enter, FormatError, Puzzle};
+use crate::candidates::Candidates;
+use crate::coordinates::{CellIndex, Digit};
+use crate::grid::Grid;
+
+struct Element {
+    line: usize,
+    name: String,
+    attributes: Vec<(String, String)>,
+    text: String,
+}
+
+impl Element {
+    fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+fn unescape(string: &str) -> Result<String, FormatError> {
+    let mut result = String::with_capacity(string.len());
+    let mut rest = string;
+    while let Some(start) = rest.find('&') {
+        result.push_str(&rest[..start]);
+        let end = rest[start..]
+            .find(';')
+            .ok_or_else(|| FormatError::InvalidXml(format!("unterminated entity in '{}'", string)))?;
+        let entity = &rest[start + 1..start + end];
+        let c = match entity {
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "amp" => Some('&'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            _ => match entity.strip_prefix("#x").or_else(|| entity.strip_prefix("#X")) {
+                Some(hex) => u32::from_str_radix(hex, 16).ok().and_then(char::from_u32),
+                None => entity
+                    .strip_prefix('#')
+                    .and_then(|decimal| decimal.parse().ok())
+                    .and_then(char::from_u32),
+            },
+        };
+        result.push(c.ok_or_else(|| FormatError::InvalidXml(format!("unknown entity '&{};'", entity)))?);
+        rest = &rest[start + end + 1..];
+    }
+    result.push_str(rest);
+    Ok(result)
+}
+
+fn escape(string: &str) -> String {
+    string
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+/// Reads the start tags of a document along with the text directly following each of them, which is enough for the
+/// flat structure of OpenSudoku files.
+fn elements(xml: &str) -> Result<Vec<Element>, FormatError> {
+    let unterminated = |what: &str| FormatError::InvalidXml(format!("unterminated {}", what));
+    let mut elements: Vec<Element> = vec![];
+    let mut rest = xml;
+    let mut open = false;
+    let (mut line, mut counted) = (1, 0);
+    while let Some(start) = rest.find('<') {
+        if open {
+            elements.last_mut().unwrap().text.push_str(&unescape(&rest[..start])?);
+        }
+        rest = &rest[start..];
+        let (skip, end) = if rest.starts_with("<?") {
+            (rest.find("?>").ok_or_else(|| unterminated("declaration"))?, "?>")
+        } else if rest.starts_with("<!--") {
+            (rest.find("-->").ok_or_else(|| unterminated("comment"))?, "-->")
+        } else {
+            (rest.find('>').ok_or_else(|| unterminated("tag"))?, ">")
+        };
+        let tag = &rest[1..skip];
+        rest = &rest[skip + end.len()..];
+        open = false;
+        if end != ">" || tag.starts_with('!') || tag.starts_with('/') {
+            continue;
+        }
+        let (tag, empty) = match tag.strip_suffix('/') {
+            Some(tag) => (tag, true),
+            None => (tag, false),
+        };
+        let name_end = tag.find(char::is_whitespace).unwrap_or(tag.len());
+        let offset = xml.len() - rest.len();
+        line += xml[counted..offset].matches('\n').count();
+        counted = offset;
+        let mut element = Element {
+            line,
+            name: tag[..name_end].to_owned(),
+            attributes: vec![],
+            text: String::new(),
+        };
+        let mut attributes = tag[name_end..].trim_start();
+        while !attributes.is_empty() {
+            let invalid = || FormatError::InvalidXml(format!("invalid attributes in <{}>", element.name));
+            let (key, value) = attributes.split_once('=').ok_or_else(invalid)?;
+            let value = value.trim_start();
+            let quote = value
+                .chars()
+                .next()
+                .filter(|c| *c == '"' || *c == '\'')
+                .ok_or_else(invalid)?;
+            let (value, remaining) = value[1..].split_once(quote).ok_or_else(invalid)?;
+            element.attributes.push((key.trim().to_owned(), unescape(value)?));
+            attributes = remaining.trim_start();
+        }
+        elements.push(element);
+        open = !empty;
+    }
+    Ok(elements)
+}
+
+fn parse_data(data: &str) -> Result<Grid, Box<dyn core::error::Error + Sync + Send>> {
+    let data = data.trim();
+    let Some(version) = data.strip_prefix("version:") else {
+        return data.parse();
+    };
+    let (version, cells) = version
+        .trim_start()
+        .split_once(char::is_whitespace)
+        .unwrap_or((version, ""));
+    if version != "1" {
+        return Err(format!("Unsupported game data version {}", version).into());
+    }
+    let cells = cells
+        .trim()
+        .split_terminator('|')
+        .tuples()
+        .map(|(value, note, editable)| (value.trim(), note.trim(), editable.trim()))
+        .collect::<Vec<(&str, &str, &str)>>();
+    let givens = cells
+        .iter()
+        .map(|(value, _, editable)| if *editable == "1" { "0" } else { value })
+        .join("");
+    let mut grid: Grid = givens.parse()?;
+    let values = cells
+        .iter()
+        .zip(CellIndex::all())
+        .map(|((value, _, _), cell)| match value.parse::<u8>() {
+            Ok(value @ 0..=9) => Ok(value),
+            _ => Err(format!("Invalid value '{}' in {}", value, cell)),
+        })
+        .collect::<Result<Vec<u8>, _>>()?;
+    enter(&mut grid, &values)?;
+    for (cell, (_, note, _)) in CellIndex::all().zip(&cells) {
+        if grid.get(cell) == 0 && *note != "0" && !note.is_empty() {
+            let mut candidates = Candidates::new(false);
+            for candidate in note.split_terminator(',') {
+                match candidate.trim().parse::<Digit>() {
+                    Ok(digit) => candidates.set_digit(digit),
+                    _ => return Err(format!("Invalid note '{}' in {}", note, cell).into()),
+                }
+            }
+            *grid.candidates_mut(cell) = candidates;
+        }
+    }
+    Ok(grid)
+}
+
+pub fn read(string: &str) -> Result<Vec<Puzzle>, FormatError> {
+    let elements = elements(string)?;
+    if elements.first().map(|element| element.name.as_str()) != Some("opensudoku") {
+        return Err(FormatError::InvalidXml("missing <opensudoku> root element".to_owned()));
+    }
+    let mut template = Puzzle::default();
+    let mut puzzles = vec![];
+    for element in &elements {
+        let text = Some(element.text.trim().to_owned()).filter(|text| !text.is_empty());
+        match element.name.as_str() {
+            "name" => template.title = text,
+            "author" => template.author = text,
+            "description" => template.description = text,
+            "level" => template.difficulty = text,
+            "game" => {
+                let data = element
+                    .attribute("data")
+                    .ok_or_else(|| FormatError::InvalidXml("<game> without data".to_owned()))?;
+                let grid = parse_data(data).map_err(|error| FormatError::InvalidPuzzle(element.line, error))?;
+                puzzles.push(grid);
+            }
+            _ => {}
+        }
+    }
+    Ok(puzzles
+        .into_iter()
+        .map(|grid| Puzzle {
+            grid,
+            ..template.clone()
+        })
+        .collect())
+}
+
+/// Writes a collection using the metadata of the first puzzle. Games with player entries or eliminated candidates are
+/// written with the versioned data so they are kept, with the remaining candidates as the note of such cells.
+pub fn write(puzzles: &[Puzzle]) -> String {
+    let mut lines = vec![
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>".to_owned(),
+        "<opensudoku>".to_owned(),
+    ];
+    if let Some(first) = puzzles.first() {
+        for (name, value) in [
+            ("name", &first.title),
+            ("author", &first.author),
+            ("description", &first.description),
+            ("level", &first.difficulty),
+        ] {
+            if let Some(value) = value {
+                lines.push(format!("  <{}>{}</{}>", name, escape(value), name));
+            }
+        }
+    }
+    for puzzle in puzzles {
+        let grid = &puzzle.grid;
+        let noted = |cell: CellIndex| Digit::all().any(|digit| eliminated(grid, cell, digit));
+        let data = if CellIndex::all().any(|cell| (grid.get(cell) > 0 && !grid.frozen(cell)) || noted(cell)) {
+            let cells = CellIndex::all()
+                .map(|cell| {
+                    let note = if noted(cell) {
+                        grid.candidates(cell).iter().map(|digit| format!("{},", digit)).join("")
+                    } else {
+                        "0".to_owned()
+                    };
+                    format!("{}|{}|{}|", grid.get(cell), note, if grid.frozen(cell) { 0 } else { 1 })
+                })
+                .join("");
+            format!("version: 1&#10;{}", cells)
+        } else {
+            CellIndex::all()
+                .map(|cell| digit(if grid.frozen(cell) { grid.get(cell) } else { 0 }, '0'))
+                .collect()
+        };
+        lines.push(format!("  <game data=\"{}\"/>", data));
+    }
+    lines.push("</opensudoku>".to_owned());
+    lines.push(String::new());
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coordinates::Digit;
+
+    const OPENSUDOKU: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<opensudoku>
+  <name>Seventeen &amp; more</name>
+  <author>Ville Orkas</author>
+  <level>Hard</level>
+  <game data="060000300400700000000000080000008012500600000000000050082000700000500600000010000"/>
+  <game data="000000010400000000020000000000050407008000300001090000300400200050100000000806000"/>
+</opensudoku>
+"#;
+
+    #[test]
+    fn read_write() {
+        let puzzles = read(OPENSUDOKU).unwrap();
+        assert_eq!(puzzles.len(), 2);
+        assert_eq!(puzzles[1].title.as_deref(), Some("Seventeen & more"));
+        assert_eq!(puzzles[1].author.as_deref(), Some("Ville Orkas"));
+        assert_eq!(puzzles[1].difficulty.as_deref(), Some("Hard"));
+        assert_eq!(puzzles[1].grid.givens(), 17);
+        assert_eq!(write(&puzzles), OPENSUDOKU);
+    }
+
+    #[test]
+    fn versioned_data() {
+        let mut puzzles = read(OPENSUDOKU).unwrap();
+        puzzles.truncate(1);
+        puzzles[0].grid.set_checked(CellIndex::at(0), Digit::new(9)).unwrap();
+        let xml = write(&puzzles);
+        assert!(xml.contains(r#"<game data="version: 1&#10;9|0|1|6|0|0|0|0|1|"#));
+        let grid = read(&xml).unwrap().remove(0).grid;
+        assert_eq!(grid.get(CellIndex::at(0)), 9);
+        assert!(!grid.frozen(CellIndex::at(0)));
+        assert_eq!(grid.givens(), 17);
+
+        puzzles[0].grid.candidates_mut(CellIndex::at(2)).unset(4);
+        let xml = write(&puzzles);
+        assert!(xml.contains(r#"<game data="version: 1&#10;9|0|1|6|0|0|0|1,7,8,|1|"#));
+        let grid = read(&xml).unwrap().remove(0).grid;
+        assert_eq!(grid.candidates(CellIndex::at(2)).value(), 0b1100_0001);
+
+        let data = format!("version: 1\n{}", "0|1,3,|1|".repeat(81));
+        let grid = parse_data(&data).unwrap();
+        assert_eq!(grid.candidates(CellIndex::at(80)).value(), 0b101);
+    }
+
+    #[test]
+    fn invalid_xml() {
+        assert!(matches!(read("<sudoku></sudoku>"), Err(FormatError::InvalidXml(_))));
+        assert!(matches!(
+            read("<opensudoku><game data=\"1"),
+            Err(FormatError::InvalidXml(_))
+        ));
+        assert!(matches!(
+            read("<opensudoku>\n<game data=\"123\"/>\n</opensudoku>"),
+            Err(FormatError::InvalidPuzzle(2, _))
+        ));
+        let data = format!("version: 1&#10;12|0|1|{}", "0|0|1|".repeat(80));
+        assert!(matches!(
+            read(&format!("<opensudoku>\n<game data=\"{}\"/>\n</opensudoku>", data)),
+            Err(FormatError::InvalidPuzzle(2, _))
+        ));
+    }
+}
@@ -0,0 +1,162 @@
+//! SadMan Software `.sdk` files: `#A` author, `#D` description and `#L` level lines followed by the givens, with the
+//! player's progress in an optional `[State]` section.
+
+use alloc::{borrow::ToOwned, boxed::Box, format, string::String, vec, vec::Vec};
+use itertools::Itertools;
+
+use super::{digit, enter, FormatError, Puzzle};
+use crate::coordinates::CellIndex;
+use crate::grid::ParseError;
+
+pub fn read(string: &str) -> Result<Puzzle, FormatError> {
+    let mut puzzle = Puzzle::default();
+    let mut givens = (0, String::new());
+    let mut state = (0, String::new());
+    let mut in_state = false;
+    for (line, text) in string.lines().enumerate() {
+        let text = text.trim();
+        if let Some(metadata) = text.strip_prefix('#') {
+            let mut chars = metadata.chars();
+            let tag = chars.next();
+            let value = Some(chars.as_str().trim().to_owned());
+            match tag {
+                Some('A') => puzzle.author = value,
+                Some('D') => puzzle.description = value,
+                Some('L') => puzzle.difficulty = value,
+                _ => {}
+            }
+        } else if text.eq_ignore_ascii_case("[Puzzle]") {
+            in_state = false;
+        } else if text.eq_ignore_ascii_case("[State]") {
+            in_state = true;
+        } else if !text.is_empty() {
+            let (first, rows) = if in_state { &mut state } else { &mut givens };
+            if rows.is_empty() {
+                *first = line + 1;
+            }
+            rows.push_str(text);
+        }
+    }
+    puzzle.grid = givens
+        .1
+        .parse()
+        .map_err(|error| FormatError::InvalidPuzzle(givens.0, error))?;
+    if !state.1.is_empty() {
+        // Parsed digit by digit rather than as a grid, which would reject the player's mistakes
+        let values = state
+            .1
+            .char_indices()
+            .map(|(index, c)| match c {
+                '.' => Ok(0),
+                c => c
+                    .to_digit(10)
+                    .map(|value| value as u8)
+                    .ok_or(ParseError::InvalidDigit(c, index)),
+            })
+            .collect::<Result<Vec<u8>, ParseError>>()
+            .and_then(|values| match values.len() {
+                81 => Ok(values),
+                length => Err(ParseError::InvalidLength(length)),
+            })
+            .map_err(|error| FormatError::InvalidPuzzle(state.0, Box::new(error)))?;
+        enter(&mut puzzle.grid, &values)?;
+    }
+    Ok(puzzle)
+}
+
+pub fn write(puzzle: &Puzzle) -> String {
+    let mut lines = vec![];
+    for (tag, value) in [
+        ('A', &puzzle.author),
+        ('D', &puzzle.description),
+        ('L', &puzzle.difficulty),
+    ] {
+        if let Some(value) = value {
+            lines.push(format!("#{}{}", tag, value));
+        }
+    }
+    let rows = |value: &dyn Fn(CellIndex) -> u8| {
+        CellIndex::all()
+            .map(|cell| digit(value(cell), '.'))
+            .chunks(9)
+            .into_iter()
+            .map(|mut row| row.join(""))
+            .collect::<Vec<String>>()
+    };
+    let grid = &puzzle.grid;
+    lines.push("[Puzzle]".to_owned());
+    lines.extend(rows(&|cell| if grid.frozen(cell) { grid.get(cell) } else { 0 }));
+    if CellIndex::all().any(|cell| grid.get(cell) > 0 && !grid.frozen(cell)) {
+        lines.push("[State]".to_owned());
+        lines.extend(rows(&|cell| grid.get(cell)));
+    }
+    lines.push(String::new());
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SDK: &str = "#AVille Orkas
+#DSeventeen givens
+#LHard
+[Puzzle]
+.6....3..
+4..7.....
+.......8.
+.....8.12
+5..6.....
+.......5.
+.82...7..
+...5..6..
+....1....
+[State]
+96....3..
+4..7.....
+.......8.
+.....8.12
+5..6.....
+.......5.
+.82...7..
+...5..6..
+....1....
+";
+
+    #[test]
+    fn read_write() {
+        let puzzle = read(SDK).unwrap();
+        assert_eq!(puzzle.author.as_deref(), Some("Ville Orkas"));
+        assert_eq!(puzzle.description.as_deref(), Some("Seventeen givens"));
+        assert_eq!(puzzle.difficulty.as_deref(), Some("Hard"));
+        assert_eq!(puzzle.title, None);
+        assert_eq!(puzzle.grid.givens(), 17);
+        assert_eq!(puzzle.grid.get(CellIndex::at(0)), 9);
+        assert!(!puzzle.grid.frozen(CellIndex::at(0)));
+        assert_eq!(write(&puzzle), SDK);
+    }
+
+    #[test]
+    fn read_plain() {
+        let puzzle = read(&SDK.lines().skip(4).take(9).join("\n")).unwrap();
+        assert_eq!(puzzle.grid.givens(), 17);
+        assert_eq!(puzzle.author, None);
+    }
+
+    #[test]
+    fn conflicting_state() {
+        let sdk = SDK.replace("96....3..", "36....3..");
+        let puzzle = read(&sdk).unwrap();
+        assert_eq!(puzzle.grid.get(CellIndex::at(0)), 3);
+        assert!(!puzzle.grid.is_valid());
+        assert_eq!(write(&puzzle), sdk);
+    }
+
+    #[test]
+    fn invalid_state() {
+        let sdk = SDK.replace("96....3..", "97....3..");
+        assert!(matches!(read(&sdk), Err(FormatError::InvalidEntry(cell)) if cell == CellIndex::at(1)));
+        let sdk = SDK.replace("96....3..", "96....3.");
+        assert!(matches!(read(&sdk), Err(FormatError::InvalidPuzzle(15, _))));
+    }
+}
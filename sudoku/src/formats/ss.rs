@@ -0,0 +1,92 @@
+//! SimpleSudoku `.ss` files: the givens as nine rows of `.` and digits, boxed with `|` and `-` separators inside a
+//! frame with `*` corners, as SimpleSudoku saves them:
+//!
+//! ```text
+//! *-----------*
+//! |.6.|...|3..|
+//! |4..|7..|...|
+//! |...|...|.8.|
+//! |---+---+---|
+//! ```
+//!
+//! Boards without the frame are read as well.
+
+use alloc::{borrow::ToOwned, format, string::String, vec};
+use itertools::Itertools;
+
+use super::{digit, FormatError, Puzzle};
+use crate::coordinates::CellIndex;
+
+pub fn read(string: &str) -> Result<Puzzle, FormatError> {
+    let first = string
+        .lines()
+        .position(|line| !line.trim().is_empty())
+        .unwrap_or_default();
+    // The frame corners are blanked rather than removed so errors keep pointing at the right index
+    let grid = string
+        .replace('*', " ")
+        .parse()
+        .map_err(|error| FormatError::InvalidPuzzle(first + 1, error))?;
+    Ok(Puzzle::new(grid))
+}
+
+pub fn write(puzzle: &Puzzle) -> String {
+    let grid = &puzzle.grid;
+    let mut lines = vec!["*-----------*".to_owned()];
+    for row in 0..9 {
+        if row == 3 || row == 6 {
+            lines.push("|---+---+---|".to_owned());
+        }
+        let row = (0..9)
+            .map(|column| CellIndex::from_coords(row, column).unwrap())
+            .map(|cell| digit(if grid.frozen(cell) { grid.get(cell) } else { 0 }, '.'))
+            .chunks(3)
+            .into_iter()
+            .map(|mut chunk| chunk.join(""))
+            .join("|");
+        lines.push(format!("|{}|", row));
+    }
+    lines.push("*-----------*".to_owned());
+    lines.push(String::new());
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::format;
+
+    const SS: &str = "*-----------*
+|.6.|...|3..|
+|4..|7..|...|
+|...|...|.8.|
+|---+---+---|
+|...|..8|.12|
+|5..|6..|...|
+|...|...|.5.|
+|---+---+---|
+|.82|...|7..|
+|...|5..|6..|
+|...|.1.|...|
+*-----------*
+";
+
+    #[test]
+    fn read_write() {
+        let puzzle = read(SS).unwrap();
+        assert_eq!(puzzle.grid.givens(), 17);
+        assert_eq!(write(&puzzle), SS);
+
+        let unframed = ".6.|...|3..\n4..|7..|...\n...|...|.8.\n-----------\n...|..8|.12\n5..|6..|...\n...|...|.5.\n\
+                        -----------\n.82|...|7..\n...|5..|6..\n...|.1.|...\n";
+        assert_eq!(read(unframed).unwrap(), puzzle);
+    }
+
+    #[test]
+    fn read_invalid() {
+        assert!(matches!(
+            read(&format!("\n{}", SS.replacen(".6.", ".6", 1))),
+            Err(FormatError::InvalidPuzzle(2, _))
+        ));
+    }
+}
@@ -1,14 +1,16 @@
 use crate::grid::{BOXES, COLUMNS, ROWS};
 
+use super::coordinates::{CellIndex, Digit};
 use super::grid::Grid;
 use super::solver::alx_solve;
+use super::transform::Symmetry;
 
 use ahash::AHashSet;
 use rand::prelude::IteratorRandom;
 use rand::seq::SliceRandom;
 use rand::thread_rng;
 
-fn seed_grid() -> Grid {
+pub(crate) fn seed_grid() -> Grid {
     let mut rng = thread_rng();
     let mut grid = Grid::new();
     let mut indices = *[&ROWS, &COLUMNS, &BOXES]
@@ -19,50 +21,89 @@ fn seed_grid() -> Grid {
         .unwrap();
     indices.shuffle(&mut rng);
     for (n, index) in indices.iter().enumerate() {
-        grid.set(*index, n as u8 + 1, false);
+        grid.set_unchecked(CellIndex::at(*index), Digit::new(n as u8 + 1))
+            .unwrap();
     }
     grid
 }
 
 pub fn generate(givens: usize) -> Grid {
     debug_assert!((17..=81).contains(&givens), "Givens must be between 17 and 81");
+    try_generate(givens, usize::MAX).unwrap()
+}
+
+/// Generates a sudoku like `generate`, but gives up after carving `attempts` solved grids without getting down to
+/// `givens`. Below about 22 givens most grids can't be carved that far, so unbounded generation can run for minutes.
+pub fn try_generate(givens: usize, attempts: usize) -> Option<Grid> {
+    debug_assert!((17..=81).contains(&givens), "Givens must be between 17 and 81");
+    carve(
+        givens,
+        &CellIndex::all().map(|cell| vec![cell]).collect::<Vec<Vec<CellIndex>>>(),
+        attempts,
+    )
+}
+
+/// Generates a sudoku whose pattern of givens has the symmetry, which has to allow that many givens.
+pub fn generate_symmetric(givens: usize, symmetry: Symmetry) -> Grid {
+    debug_assert!((17..=81).contains(&givens), "Givens must be between 17 and 81");
+    debug_assert!(
+        symmetry.allows(givens),
+        "{} symmetry can't have {} givens",
+        symmetry,
+        givens
+    );
+    carve(givens, &symmetry.orbits(), usize::MAX).unwrap()
+}
+
+/// Empties random orbits of cells from a random solved grid as long as the solution stays unique, until `givens` cells
+/// are left. Starts over with a new grid when no orbit can be removed before that, up to `attempts` grids.
+fn carve(givens: usize, orbits: &[Vec<CellIndex>], attempts: usize) -> Option<Grid> {
     let mut rng = thread_rng();
-    loop {
+    for _ in 0..attempts {
         let mut grid = seed_grid();
         grid = alx_solve(&grid, 1)[0];
-        let mut not_removed = (0..81).collect::<AHashSet<usize>>();
+        let mut not_removed = (0..orbits.len()).collect::<AHashSet<usize>>();
+        let mut remaining = 81;
         let mut stuck = false;
-        'outer: while not_removed.len() > givens {
+        'outer: while remaining > givens {
             let mut tried = AHashSet::new();
             loop {
-                let index = *not_removed.iter().choose(&mut rng).unwrap();
-                let old_value = grid.get(index);
-                grid.set(index, 0, false);
+                let Some(orbit) = not_removed
+                    .iter()
+                    .filter(|orbit| !tried.contains(*orbit) && orbits[**orbit].len() <= remaining - givens)
+                    .choose(&mut rng)
+                    .copied()
+                else {
+                    stuck = true;
+                    break 'outer;
+                };
+                let old_grid = grid;
+                for cell in &orbits[orbit] {
+                    grid.set_unchecked(*cell, None).unwrap();
+                }
                 if alx_solve(&grid, 2).len() == 1 {
-                    not_removed.remove(&index);
+                    not_removed.remove(&orbit);
+                    remaining -= orbits[orbit].len();
                     break;
                 } else {
-                    grid.set(index, old_value, false);
-                    tried.insert(index);
-                    if tried == not_removed {
-                        stuck = true;
-                        break 'outer;
-                    }
+                    grid = old_grid;
+                    tried.insert(orbit);
                 }
             }
         }
         if stuck {
             continue;
         }
-        for index in 0..81 {
-            if not_removed.contains(&index) {
-                grid.freeze(index);
+        for cell in CellIndex::all() {
+            if grid.get(cell) > 0 {
+                grid.freeze(cell);
             } else {
-                grid.set(index, 0, true);
+                grid.set_checked(cell, None).unwrap();
             }
         }
-        break grid;
+        return Some(grid);
     }
+    None
 }
 
 #[cfg(test)]
@@ -75,4 +116,20 @@ mod tests {
         let grid = generate(givens);
         assert_eq!(grid.givens(), givens);
     }
+
+    #[test]
+    fn try_generate_test() {
+        assert_eq!(try_generate(40, 1).unwrap().givens(), 40);
+        assert!(try_generate(17, 1).is_none());
+    }
+
+    #[test]
+    fn generate_symmetric_test() {
+        for symmetry in [Symmetry::Rotational, Symmetry::Diagonal] {
+            let grid = generate_symmetric(31, symmetry);
+            assert_eq!(grid.givens(), 31);
+            assert!(symmetry.holds(&grid), "{}", symmetry);
+            assert_eq!(alx_solve(&grid, 2).len(), 1);
+        }
+    }
 }
@@ -1,10 +1,23 @@
+use alloc::{
+    borrow::ToOwned,
+    boxed::Box,
+    format,
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+use core::cmp::Ordering;
+use core::hash::{Hash, Hasher};
+use core::{error::Error, fmt, str::FromStr};
 use itertools::Itertools;
-use std::{error::Error, fmt, str::FromStr};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
 use super::candidates::Candidates;
+use super::coordinates::{CellIndex, Digit, Unit};
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 struct Cell {
     pub value: u8,
     pub candidates: Candidates,
@@ -21,11 +34,36 @@ impl Cell {
     }
 }
 
+/// What a cell of a pencilmark board or candidate string holds besides its candidates.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Content {
+    Empty,
+    Given,
+    Placed,
+}
+
 #[derive(Clone, Copy, Debug)]
 pub struct Grid {
     cells: [Cell; 81],
 }
 
+/// Two cells holding the same value within a unit, with `cells.0 < cells.1`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Conflict {
+    pub cells: (CellIndex, CellIndex),
+    pub unit: Unit,
+}
+
+/// An empty cell whose stored candidates differ from the ones its peers allow.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct CandidateAudit {
+    pub cell: CellIndex,
+    /// Allowed candidates that aren't stored, usually eliminated by the player
+    pub missing: Candidates,
+    /// Stored candidates already placed in a peer
+    pub extra: Candidates,
+}
+
 const fn insert<const N: usize>(mut array: [usize; N], value: usize) -> [usize; N] {
     let mut index = 0;
     while index < array.len() {
@@ -112,86 +150,141 @@ impl Grid {
         self.cells.iter().map(|cell| cell.value)
     }
 
-    pub fn get(&self, index: usize) -> u8 {
-        debug_assert!(index < 81);
-        self.cells[index].value
+    /// The value of the cell, or 0 for a blank.
+    pub fn get(&self, cell: CellIndex) -> u8 {
+        self.cells[cell.index()].value
     }
 
-    pub fn set(&mut self, index: usize, value: u8, checked: bool) -> bool {
-        debug_assert!(index < 81);
-        debug_assert!(value < 10);
-        let cell = &mut self.cells[index];
-        if !cell.frozen {
-            cell.value = value;
-            if checked {
-                return self.update_candidates(index);
-            }
-            if value > 0 {
-                cell.candidates.unset_all();
-            } else {
-                cell.candidates.set_all();
-            }
-            return true;
+    /// Sets the value, or clears the cell for `None`, without looking at the peers. The cell's candidates are cleared
+    /// for a value and reset to all digits for a blank. Fails only for frozen cells.
+    pub fn set_unchecked(&mut self, cell: CellIndex, value: Option<Digit>) -> Result<(), SetError> {
+        let value = value.map_or(0, Digit::get);
+        let cell = &mut self.cells[cell.index()];
+        if cell.frozen {
+            return Err(SetError::Frozen);
+        }
+        cell.value = value;
+        if value > 0 {
+            cell.candidates.unset_all();
+        } else {
+            cell.candidates.set_all();
+        }
+        Ok(())
+    }
+
+    /// Sets the value and updates the candidates of the cell and its peers. The grid is left untouched if the value
+    /// is already placed in a peer or the update would leave a cell without candidates.
+    pub fn set_checked(&mut self, cell: CellIndex, value: Option<Digit>) -> Result<(), SetError> {
+        let index = cell.index();
+        if self.cells[index].frozen {
+            return Err(SetError::Frozen);
         }
-        false
+        let old_value = self.cells[index].value;
+        self.cells[index].value = value.map_or(0, Digit::get);
+        self.update_candidates(cell)
+            .inspect_err(|_| self.cells[index].value = old_value)
     }
 
-    pub fn update_candidates(&mut self, index: usize) -> bool {
-        debug_assert!(index < 81);
+    /// Updates the candidates of the cell and its peers after its value has changed.
+    pub fn update_candidates(&mut self, cell: CellIndex) -> Result<(), SetError> {
+        let index = cell.index();
         let mut cells = self.cells;
         let value = cells[index].value;
-        if value > 0 {
-            let cell = Cell::new(value);
+        if let Some(digit) = Digit::new(value) {
+            if let Some(with) = PEERS[index].iter().find(|peer| cells[**peer].value == value) {
+                return Err(SetError::Conflict {
+                    with: CellIndex::at(*with),
+                });
+            }
             for peer in PEERS[index] {
-                let peer = &mut cells[peer];
-                if peer.value == 0 {
-                    peer.candidates.unset((cell.value - 1) as usize);
-                    if peer.candidates.none() {
-                        return false;
+                let cell = &mut cells[peer];
+                if cell.value == 0 {
+                    cell.candidates.unset_digit(digit);
+                    if cell.candidates.none() {
+                        return Err(SetError::NoCandidatesLeft {
+                            cell: CellIndex::at(peer),
+                        });
                     }
-                } else if cell.value == peer.value {
-                    return false;
                 }
             }
-            cells[index] = cell;
+            cells[index] = Cell::new(value);
         } else {
-            let old_value = cells[index].value;
             let mut cell = Cell::new(0);
             for peer in PEERS[index] {
-                let peer = &mut cells[peer];
-                if peer.value > 0 {
-                    cell.candidates.unset(peer.value as usize - 1);
-                    if cell.candidates.none() {
-                        return false;
-                    }
-                } else if old_value > 0 {
-                    peer.candidates.set(old_value as usize - 1);
+                let peer = &cells[peer];
+                if let Some(digit) = Digit::new(peer.value) {
+                    cell.candidates.unset_digit(digit);
                 }
             }
+            if cell.candidates.none() {
+                return Err(SetError::NoCandidatesLeft {
+                    cell: CellIndex::at(index),
+                });
+            }
             cells[index] = cell;
         }
         self.cells = cells;
-        true
+        Ok(())
     }
 
-    pub fn candidates(&self, index: usize) -> &Candidates {
-        debug_assert!(index < 81);
-        &self.cells[index].candidates
+    /// The candidates allowed by the values of the cell's peers, or none for a filled cell.
+    pub fn real_candidates(&self, cell: CellIndex) -> Candidates {
+        let index = cell.index();
+        if self.cells[index].value > 0 {
+            return Candidates::new(false);
+        }
+        let placed = PEERS[index]
+            .iter()
+            .filter_map(|peer| Digit::new(self.cells[*peer].value))
+            .collect::<Candidates>();
+        !placed
     }
 
-    pub fn candidates_mut(&mut self, index: usize) -> &mut Candidates {
-        debug_assert!(index < 81);
-        &mut self.cells[index].candidates
+    /// Replaces the candidates of every cell with the ones allowed by the values, regardless of how the grid was
+    /// built. Player eliminations are discarded.
+    pub fn recompute_all_candidates(&mut self) {
+        for cell in CellIndex::all() {
+            self.cells[cell.index()].candidates = self.real_candidates(cell);
+        }
+    }
+
+    /// Fills separately kept pencilmarks, such as a frontend's own notes, with the candidates allowed by the values.
+    pub fn fill_pencilmarks(&self, pencilmarks: &mut [Candidates; 81]) {
+        for (cell, pencilmarks) in CellIndex::all().zip(pencilmarks.iter_mut()) {
+            *pencilmarks = self.real_candidates(cell);
+        }
     }
 
-    pub fn freeze(&mut self, index: usize) {
-        debug_assert!(index < 81);
-        self.cells[index].frozen = true;
+    /// Lists the empty cells whose stored candidates disagree with the ones allowed by the values.
+    pub fn audit_candidates(&self) -> Vec<CandidateAudit> {
+        CellIndex::all()
+            .filter(|cell| self.get(*cell) == 0)
+            .filter_map(|cell| {
+                let stored = *self.candidates(cell);
+                let real = self.real_candidates(cell);
+                (stored != real).then_some(CandidateAudit {
+                    cell,
+                    missing: real & !stored,
+                    extra: stored & !real,
+                })
+            })
+            .collect()
     }
 
-    pub fn frozen(&self, index: usize) -> bool {
-        debug_assert!(index < 81);
-        self.cells[index].frozen
+    pub fn candidates(&self, cell: CellIndex) -> &Candidates {
+        &self.cells[cell.index()].candidates
+    }
+
+    pub fn candidates_mut(&mut self, cell: CellIndex) -> &mut Candidates {
+        &mut self.cells[cell.index()].candidates
+    }
+
+    pub fn freeze(&mut self, cell: CellIndex) {
+        self.cells[cell.index()].frozen = true;
+    }
+
+    pub fn frozen(&self, cell: CellIndex) -> bool {
+        self.cells[cell.index()].frozen
     }
 
     pub fn givens(&self) -> usize {
@@ -202,6 +295,19 @@ impl Grid {
         !self.cells.iter().any(|cell| cell.value == 0)
     }
 
+    /// Compares the whole state of the grids, including candidates and frozen flags, unlike `==` which only compares
+    /// values.
+    pub fn eq_full(&self, other: &Grid) -> bool {
+        self.cells == other.cells
+    }
+
+    /// A 64-bit FNV-1a hash of the values, stable across runs and platforms, for deduplication tables.
+    pub fn fingerprint(&self) -> u64 {
+        self.cells().fold(0xcbf29ce484222325, |hash, value| {
+            (hash ^ value as u64).wrapping_mul(0x100000001b3)
+        })
+    }
+
     pub fn is_valid(&self) -> bool {
         !self.cells.iter().enumerate().any(|(index, cell)| {
             cell.value > 0
@@ -211,6 +317,279 @@ impl Grid {
                     .any(|peer| peer.value > 0 && peer.value == cell.value)
         })
     }
+
+    /// Lists every pair of cells with the same value and the unit they clash in. Cells sharing both a line and a box
+    /// are reported once for each unit.
+    pub fn conflicts(&self) -> Vec<Conflict> {
+        let mut conflicts = vec![];
+        for cell in CellIndex::all().filter(|cell| self.get(*cell) > 0) {
+            for peer in cell
+                .peers()
+                .filter(|peer| *peer > cell && self.get(*peer) == self.get(cell))
+                .sorted()
+            {
+                let units = peer.units();
+                conflicts.extend(
+                    cell.units()
+                        .into_iter()
+                        .filter(|unit| units.contains(unit))
+                        .map(|unit| Conflict {
+                            cells: (cell, peer),
+                            unit,
+                        }),
+                );
+            }
+        }
+        conflicts
+    }
+
+    /// Lists the filled cells whose value differs from the given solution.
+    pub fn mistakes(&self, solution: &Grid) -> Vec<CellIndex> {
+        CellIndex::all()
+            .filter(|cell| self.get(*cell) > 0 && self.get(*cell) != solution.get(*cell))
+            .collect()
+    }
+}
+
+impl Grid {
+    /// Formats the grid as a boxed 9x9 board with `.` for blanks, which parses back into the same grid.
+    pub fn to_pretty_string(&self) -> String {
+        let separator = "+-------+-------+-------+";
+        let mut lines = vec![separator.to_owned()];
+        for row_index in 0..9 {
+            let row = (0..9)
+                .map(|column| match self.get(CellIndex::at(row_index * 9 + column)) {
+                    0 => '.',
+                    value => char::from_digit(value as u32, 10).unwrap(),
+                })
+                .chunks(3)
+                .into_iter()
+                .map(|mut chunk| chunk.join(" "))
+                .join(" | ");
+            lines.push(format!("| {} |", row));
+            if row_index % 3 == 2 {
+                lines.push(separator.to_owned());
+            }
+        }
+        lines.join("\n")
+    }
+
+    /// Formats the grid as a pencilmark board in the standard layout used by Hodoku and forum posts, where a filled
+    /// cell shows its value and an empty cell lists its candidates. An empty cell without candidates has no notation
+    /// in that layout and is written as a `0`.
+    pub fn to_pencilmark_string(&self) -> String {
+        self.pencilmark_board(|cell| match self.get(cell) {
+            0 if self.candidates(cell).none() => "0".to_owned(),
+            0 => self.candidates(cell).to_string(),
+            value => value.to_string(),
+        })
+    }
+
+    /// Formats the grid as a pencilmark board like `to_pencilmark_string`, but marks the cells the standard layout
+    /// can't tell apart from a given: values placed after the givens as `*5` and the lone candidate of an empty cell
+    /// as `?5`. Hodoku and other tools don't read these markers.
+    pub fn to_marked_pencilmark_string(&self) -> String {
+        self.pencilmark_board(|cell| match self.get(cell) {
+            0 if self.candidates(cell).single().is_some() => format!("?{}", self.candidates(cell)),
+            0 if self.candidates(cell).none() => "0".to_owned(),
+            0 => self.candidates(cell).to_string(),
+            value if self.frozen(cell) => value.to_string(),
+            value => format!("*{}", value),
+        })
+    }
+
+    /// Lays out the text of every cell as a boxed board with aligned columns.
+    fn pencilmark_board(&self, text: impl Fn(CellIndex) -> String) -> String {
+        let cells = CellIndex::all().map(text).collect::<Vec<String>>();
+        let widths = (0..9)
+            .map(|column| (0..9).map(|row| cells[row * 9 + column].len()).max().unwrap())
+            .collect::<Vec<usize>>();
+        let border = |corner: char, middle: char, end: char| {
+            let segments = widths
+                .chunks(3)
+                .map(|chunk| "-".repeat(chunk.iter().sum::<usize>() + 2 * chunk.len()))
+                .join(&middle.to_string());
+            format!("{}{}{}", corner, segments, end)
+        };
+        let mut lines = vec![border('.', '.', '.')];
+        for row in 0..9 {
+            let row_string = (0..9)
+                .map(|column| format!("{:width$}", cells[row * 9 + column], width = widths[column]))
+                .chunks(3)
+                .into_iter()
+                .map(|mut chunk| format!(" {} ", chunk.join("  ")))
+                .join("|");
+            lines.push(format!("|{}|", row_string));
+            match row {
+                2 | 5 => lines.push(border(':', '+', ':')),
+                8 => lines.push(border('\'', '\'', '\'')),
+                _ => {}
+            }
+        }
+        lines.join("\n")
+    }
+
+    /// Parses a pencilmark board, where a cell listing a single digit is a given, a `0` is an empty cell without
+    /// candidates and any other cell lists its candidates. The `*5` and `?5` markers of `to_marked_pencilmark_string`
+    /// are read as a placed value and a lone candidate. Border characters, including `*` frame corners, and
+    /// whitespace only separate cells.
+    pub fn from_pencilmarks(string: &str) -> Result<Grid, Box<dyn Error + Sync + Send>> {
+        let mut cells = vec![];
+        let mut contents = vec![];
+        let mut cell: Option<Candidates> = None;
+        // The `*`, `?` or `0` marking the cell being read, along with its index
+        let mut marker: Option<(char, usize)> = None;
+        let mut finish = |cell: Option<Candidates>, marker: Option<(char, usize)>| match (cell, marker) {
+            (None, Some(('*', _))) => Ok(()),
+            (None, Some(('0', _))) => {
+                contents.push(Content::Empty);
+                cells.push(Candidates::new(false));
+                Ok(())
+            }
+            (Some(_), Some(('0', index))) => Err(ParseError::InvalidDigit('0', index)),
+            (Some(candidates), Some((c, index))) if candidates.single().is_none() => {
+                Err(ParseError::InvalidDigit(c, index))
+            }
+            (Some(candidates), marker) => {
+                contents.push(match marker {
+                    Some(('*', _)) => Content::Placed,
+                    Some(_) => Content::Empty,
+                    None if candidates.single().is_some() => Content::Given,
+                    None => Content::Empty,
+                });
+                cells.push(candidates);
+                Ok(())
+            }
+            (None, Some((c, index))) => Err(ParseError::InvalidDigit(c, index)),
+            (None, None) => Ok(()),
+        };
+        for (index, c) in string.char_indices() {
+            match c {
+                '1'..='9' => cell
+                    .get_or_insert(Candidates::new(false))
+                    .set_digit(Digit::new(c.to_digit(10).unwrap() as u8).unwrap()),
+                '*' | '?' | '0' if cell.is_none() && marker.is_none() => marker = Some((c, index)),
+                '.' | ':' | '\'' | '|' | '-' | '+' => finish(cell.take(), marker.take())?,
+                c if c.is_whitespace() => finish(cell.take(), marker.take())?,
+                c => return Err(Box::new(ParseError::InvalidDigit(c, index))),
+            }
+        }
+        finish(cell, marker)?;
+        Ok(Grid::from_candidates(&cells, &contents)?)
+    }
+
+    /// Formats the candidates as 729 bits, nine per cell, where a filled cell only has the bit of its value set and an
+    /// empty cell without candidates has none.
+    pub fn to_candidate_string(&self) -> String {
+        CellIndex::all()
+            .flat_map(|cell| {
+                let value = self.get(cell);
+                Digit::all().map(move |digit| {
+                    let set = if value > 0 {
+                        digit.get() == value
+                    } else {
+                        self.candidates(cell).get_digit(digit)
+                    };
+                    if set {
+                        '1'
+                    } else {
+                        '0'
+                    }
+                })
+            })
+            .collect()
+    }
+
+    /// Parses 729 candidate bits, where every cell with a single bit set is a given.
+    pub fn from_candidate_string(string: &str) -> Result<Grid, Box<dyn Error + Sync + Send>> {
+        let cells = Grid::candidate_bits(string)?;
+        let contents = cells
+            .iter()
+            .map(|candidates| match candidates.single() {
+                Some(_) => Content::Given,
+                None => Content::Empty,
+            })
+            .collect::<Vec<Content>>();
+        Ok(Grid::from_candidates(&cells, &contents)?)
+    }
+
+    /// Formats the grid as its candidate string, a `:` and a content flag for each of the 81 cells, `0` for an empty
+    /// cell, `1` for a given and `2` for a placed value, so the givens survive a round trip.
+    pub fn to_candidate_content_string(&self) -> String {
+        let flags = CellIndex::all()
+            .map(|cell| match (self.get(cell), self.frozen(cell)) {
+                (0, _) => '0',
+                (_, true) => '1',
+                (_, false) => '2',
+            })
+            .collect::<String>();
+        format!("{}:{}", self.to_candidate_string(), flags)
+    }
+
+    /// Parses a candidate string followed by a `:` and the content flags of the cells.
+    pub fn from_candidate_content_string(string: &str) -> Result<Grid, Box<dyn Error + Sync + Send>> {
+        let (bits, flags) = string
+            .split_once(':')
+            .ok_or(ParseError::InvalidBitLength(string.chars().count()))?;
+        let cells = Grid::candidate_bits(bits)?;
+        let contents = flags
+            .char_indices()
+            .map(|(index, c)| match c {
+                '0' => Ok(Content::Empty),
+                '1' => Ok(Content::Given),
+                '2' => Ok(Content::Placed),
+                c => Err(ParseError::InvalidDigit(c, bits.len() + 1 + index)),
+            })
+            .collect::<Result<Vec<Content>, ParseError>>()?;
+        if contents.len() != 81 {
+            return Err(Box::new(ParseError::InvalidLength(contents.len())));
+        }
+        Ok(Grid::from_candidates(&cells, &contents)?)
+    }
+
+    /// Reads 729 candidate bits into the candidates of the cells.
+    fn candidate_bits(bits: &str) -> Result<Vec<Candidates>, ParseError> {
+        let mut cells = vec![Candidates::new(false); bits.chars().count().div_ceil(9)];
+        for (position, (index, c)) in bits.char_indices().enumerate() {
+            match c {
+                '1' => cells[position / 9].set_digit(Digit::new((position % 9) as u8 + 1).unwrap()),
+                '0' => {}
+                c => return Err(ParseError::InvalidDigit(c, index)),
+            }
+        }
+        if bits.chars().count() != 729 {
+            return Err(ParseError::InvalidBitLength(bits.chars().count()));
+        }
+        Ok(cells)
+    }
+
+    /// Builds a grid from the candidates of every cell, where the single candidate of a given or placed value is its
+    /// value and the candidates of an empty cell are kept as they are.
+    fn from_candidates(cells: &[Candidates], contents: &[Content]) -> Result<Grid, ParseError> {
+        if cells.len() != 81 {
+            return Err(ParseError::InvalidLength(cells.len()));
+        }
+        let mut grid = Grid::new();
+        for ((cell, candidates), content) in CellIndex::all().zip(cells).zip(contents) {
+            match (content, candidates.single()) {
+                (Content::Empty, _) => {}
+                (_, None) => return Err(ParseError::InvalidSudoku(cell.index())),
+                (content, Some(value)) => {
+                    grid.set_checked(cell, Digit::new(value))
+                        .map_err(|_| ParseError::InvalidSudoku(cell.index()))?;
+                    if *content == Content::Given {
+                        grid.freeze(cell);
+                    }
+                }
+            }
+        }
+        for ((cell, candidates), content) in CellIndex::all().zip(cells).zip(contents) {
+            if *content == Content::Empty {
+                grid.cells[cell.index()].candidates = *candidates;
+            }
+        }
+        Ok(grid)
+    }
 }
 
 impl Default for Grid {
@@ -234,25 +613,77 @@ impl PartialEq for Grid {
     }
 }
 
+impl Eq for Grid {}
+
+/// Hashes the values only, consistent with `==`.
+impl Hash for Grid {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        for value in self.cells() {
+            value.hash(state);
+        }
+    }
+}
+
+/// Orders grids by their values, cell by cell, like their string forms.
+impl Ord for Grid {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.cells().cmp(other.cells())
+    }
+}
+
+impl PartialOrd for Grid {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Error, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum SetError {
+    #[error("Cell is frozen")]
+    Frozen,
+    #[error("Value is already placed in {with}")]
+    Conflict { with: CellIndex },
+    #[error("No candidates left in {cell}")]
+    NoCandidatesLeft { cell: CellIndex },
+}
+
 #[derive(PartialEq, Eq, Error, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum ParseError {
     #[error("Invalid digit '{0}' at index {1} while parsing sudoku")]
     InvalidDigit(char, usize),
     #[error("Invalid sudoku at index '{0}'")]
     InvalidSudoku(usize),
+    #[error("Invalid sudoku length {0}, expected 81 cells")]
+    InvalidLength(usize),
+    #[error("Invalid candidate string length {0}, expected 729 bits")]
+    InvalidBitLength(usize),
 }
 
+/// Parses a grid from 81 cells, where `.` and `0` are blanks. Whitespace and the `|`, `-` and `+` characters are
+/// skipped, so both single line and boxed multi-line boards are accepted.
 impl FromStr for Grid {
     type Err = Box<dyn Error + Sync + Send>;
 
     fn from_str(string: &str) -> Result<Self, Self::Err> {
-        let mut grid = Grid::new();
+        let mut values = Vec::with_capacity(81);
         for (index, c) in string.char_indices() {
-            let value = c.to_digit(10).ok_or(ParseError::InvalidDigit(c, index))? as u8;
-            if !grid.set(index, value, true) {
-                return Err(Box::new(ParseError::InvalidSudoku(index)));
+            match c {
+                '.' => values.push(0),
+                '|' | '-' | '+' => {}
+                c if c.is_whitespace() => {}
+                c => values.push(c.to_digit(10).ok_or(ParseError::InvalidDigit(c, index))? as u8),
             }
-            let cell = &mut grid.cells[index];
+        }
+        if values.len() != 81 {
+            return Err(Box::new(ParseError::InvalidLength(values.len())));
+        }
+        let mut grid = Grid::new();
+        for (cell, value) in CellIndex::all().zip(values) {
+            grid.set_checked(cell, Digit::new(value))
+                .map_err(|_| ParseError::InvalidSudoku(cell.index()))?;
+            let cell = &mut grid.cells[cell.index()];
             if cell.value != 0 {
                 cell.frozen = true;
             }
@@ -264,6 +695,15 @@ impl FromStr for Grid {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::coordinates::UnitIndex;
+
+    fn cell(notation: &str) -> CellIndex {
+        notation.parse().unwrap()
+    }
+
+    fn digit(value: u8) -> Option<Digit> {
+        Digit::new(value)
+    }
 
     #[test]
     fn peers() {
@@ -274,6 +714,127 @@ mod tests {
         );
     }
 
+    #[test]
+    fn equality() {
+        let grid: Grid = "060000300400700000000000080000008012500600000000000050082000700000500600000010000"
+            .parse()
+            .unwrap();
+        let mut other = grid;
+        assert!(grid.eq_full(&other));
+        other.candidates_mut(cell("r1c1")).unset(0);
+        assert_eq!(grid, other);
+        assert!(!grid.eq_full(&other));
+        assert_eq!(grid.fingerprint(), other.fingerprint());
+
+        let mut entered = grid;
+        entered.set_checked(cell("r1c1"), digit(9)).unwrap();
+        assert_ne!(grid, entered);
+        assert_ne!(grid.fingerprint(), entered.fingerprint());
+        assert!(grid < entered);
+        assert_eq!(Grid::new().fingerprint(), Grid::new().fingerprint());
+
+        let set: crate::collections::Set<Grid> = [grid, other, entered].into_iter().collect();
+        assert_eq!(set.len(), 2);
+    }
+
+    #[test]
+    fn set_errors() {
+        let mut grid = Grid::new();
+        for (cell, value) in CellIndex::all().zip(1..9) {
+            grid.set_checked(cell, digit(value)).unwrap();
+        }
+        assert_eq!(
+            grid.set_checked(cell("r2c1"), digit(1)),
+            Err(SetError::Conflict { with: cell("r1c1") })
+        );
+        assert_eq!(grid.get(cell("r2c1")), 0);
+        assert_eq!(
+            grid.set_checked(cell("r2c9"), digit(9)),
+            Err(SetError::NoCandidatesLeft { cell: cell("r1c9") })
+        );
+        assert_eq!(
+            SetError::NoCandidatesLeft { cell: cell("r1c9") }.to_string(),
+            "No candidates left in r1c9"
+        );
+        assert_eq!(grid.get(cell("r2c9")), 0);
+        assert!(grid.candidates(cell("r2c9")).get(8));
+        assert_eq!(grid.set_unchecked(cell("r2c9"), digit(9)), Ok(()));
+        grid.freeze(cell("r1c1"));
+        assert_eq!(grid.set_unchecked(cell("r1c1"), None), Err(SetError::Frozen));
+        assert_eq!(grid.set_checked(cell("r1c1"), None), Err(SetError::Frozen));
+    }
+
+    #[test]
+    fn conflicts() {
+        let mut grid: Grid = "060000300400700000000000080000008012500600000000000050082000700000500600000010000"
+            .parse()
+            .unwrap();
+        assert!(grid.conflicts().is_empty());
+        grid.set_unchecked(cell("r1c3"), digit(6)).unwrap();
+        grid.set_unchecked(cell("r8c1"), digit(4)).unwrap();
+        assert_eq!(
+            grid.conflicts(),
+            vec![
+                Conflict {
+                    cells: (cell("r1c2"), cell("r1c3")),
+                    unit: Unit::Row(UnitIndex::new(0).unwrap())
+                },
+                Conflict {
+                    cells: (cell("r1c2"), cell("r1c3")),
+                    unit: Unit::Box(UnitIndex::new(0).unwrap())
+                },
+                Conflict {
+                    cells: (cell("r2c1"), cell("r8c1")),
+                    unit: Unit::Column(UnitIndex::new(0).unwrap())
+                },
+            ]
+        );
+        assert_eq!(Unit::Box(UnitIndex::new(4).unwrap()).to_string(), "box 5");
+    }
+
+    #[test]
+    fn recompute_candidates() {
+        let checked: Grid = "060000300400700000000000080000008012500600000000000050082000700000500600000010000"
+            .parse()
+            .unwrap();
+        let mut grid = Grid::new();
+        for cell in CellIndex::all() {
+            grid.set_unchecked(cell, digit(checked.get(cell))).unwrap();
+        }
+        assert_eq!(grid.audit_candidates().len(), 64);
+        let mut pencilmarks = [Candidates::new(false); 81];
+        grid.fill_pencilmarks(&mut pencilmarks);
+        assert!(CellIndex::all().all(|cell| pencilmarks[cell.index()] == *checked.candidates(cell)));
+        grid.recompute_all_candidates();
+        assert!(CellIndex::all().all(|cell| grid.candidates(cell) == checked.candidates(cell)));
+        assert!(grid.audit_candidates().is_empty());
+
+        grid.candidates_mut(cell("r1c1")).unset(1);
+        grid.candidates_mut(cell("r1c1")).set(5);
+        assert_eq!(
+            grid.audit_candidates(),
+            vec![CandidateAudit {
+                cell: cell("r1c1"),
+                missing: Candidates::from_digits(&[digit(2).unwrap()]),
+                extra: Candidates::from_digits(&[digit(6).unwrap()]),
+            }]
+        );
+    }
+
+    #[test]
+    fn mistakes() {
+        let mut grid: Grid = "060000300400700000000000080000008012500600000000000050082000700000500600000010000"
+            .parse()
+            .unwrap();
+        let solution = crate::solver::alx_solve(&grid, 1)[0];
+        assert!(grid.mistakes(&solution).is_empty());
+        grid.set_checked(cell("r1c1"), digit(solution.get(cell("r1c1"))))
+            .unwrap();
+        let wrong = (1..10).find(|value| *value != solution.get(cell("r1c3"))).unwrap();
+        grid.set_unchecked(cell("r1c3"), digit(wrong)).unwrap();
+        assert_eq!(grid.mistakes(&solution), vec![cell("r1c3")]);
+    }
+
     #[test]
     fn error_digit() {
         let result =
@@ -295,4 +856,258 @@ mod tests {
             &ParseError::InvalidSudoku(1)
         )
     }
+
+    #[test]
+    fn error_length() {
+        let result = "0600003004007".parse::<Grid>();
+        assert_eq!(
+            result.err().unwrap().downcast::<ParseError>().unwrap().as_ref(),
+            &ParseError::InvalidLength(13)
+        );
+        let result =
+            "0600003004007000000000000800000080125006000000000000500820007000005006000000100000".parse::<Grid>();
+        assert_eq!(
+            result.err().unwrap().downcast::<ParseError>().unwrap().as_ref(),
+            &ParseError::InvalidLength(82)
+        )
+    }
+
+    #[test]
+    fn parse_formats() {
+        let grid: Grid = "060000300400700000000000080000008012500600000000000050082000700000500600000010000"
+            .parse()
+            .unwrap();
+        let dots: Grid = ".6....3..4..7............8......8.125..6............5..82...7.....5..6......1...."
+            .parse()
+            .unwrap();
+        assert_eq!(grid, dots);
+        assert_eq!(dots.givens(), 17);
+        let pretty = grid.to_pretty_string();
+        assert_eq!(pretty.lines().count(), 13);
+        assert_eq!(pretty.lines().nth(1).unwrap(), "| . 6 . | . . . | 3 . . |");
+        assert_eq!(pretty.parse::<Grid>().unwrap(), grid);
+        let board = "
+            .6.|...|3..
+            4..|7..|...
+            ...|...|.8.
+            -----------
+            ...|..8|.12
+            5..|6..|...
+            ...|...|.5.
+            -----------
+            .82|...|7..
+            ...|5..|6..
+            ...|.1.|...
+        ";
+        assert_eq!(board.parse::<Grid>().unwrap(), grid);
+    }
+
+    #[test]
+    fn pencilmarks() {
+        let mut grid: Grid = "060000300400700000000000080000008012500600000000000050082000700000500600000010000"
+            .parse()
+            .unwrap();
+        grid.candidates_mut(cell("r1c1")).unset(1);
+        let pencilmarks = grid.to_pencilmark_string();
+        assert_eq!(pencilmarks.lines().count(), 13);
+        assert!(pencilmarks.starts_with(".----"));
+        assert!(pencilmarks.lines().nth(4).unwrap().starts_with(":----"));
+        assert!(pencilmarks.lines().nth(12).unwrap().starts_with("'----"));
+        assert!(pencilmarks.lines().nth(1).unwrap().starts_with("| 1789     6 "));
+        let parsed = Grid::from_pencilmarks(&pencilmarks).unwrap();
+        assert_eq!(parsed, grid);
+        assert_eq!(parsed.givens(), 17);
+        assert_eq!(parsed.to_pencilmark_string(), pencilmarks);
+
+        assert_eq!(grid.to_marked_pencilmark_string(), pencilmarks);
+
+        let candidates = grid.to_candidate_string();
+        assert_eq!(candidates.len(), 729);
+        assert!(candidates.starts_with("100000111000001000"));
+        let parsed = Grid::from_candidate_string(&candidates).unwrap();
+        assert_eq!(parsed.to_candidate_string(), candidates);
+        assert_eq!(parsed.to_pencilmark_string(), pencilmarks);
+        assert_eq!(parsed.givens(), 17);
+
+        let contents = grid.to_candidate_content_string();
+        assert_eq!(contents.len(), 729 + 1 + 81);
+        assert!(contents.starts_with(&candidates));
+        assert!(
+            contents.ends_with(":010000100100100000000000010000001011100100000000000010011000100000100100000010000")
+        );
+        let parsed = Grid::from_candidate_content_string(&contents).unwrap();
+        assert_eq!(parsed.to_candidate_content_string(), contents);
+    }
+
+    #[test]
+    fn standard_pencilmarks() {
+        let board = "
+            *--------------------------------------------------------------------*
+            | 1789   6      1789   | 1234   2589   12459 | 3      12579  4579   |
+            | 4      12389  135789 | 7      12589  12569 | 1259   1269   569    |
+            | 1279   12379  1579   | 1234   2569   12459 | 1249   8      4579   |
+            |----------------------+---------------------+-----------------------|
+            | 3679   3479   34679  | 349    3479   8      | 49     1      2      |
+            | 5      12389  134789 | 6      23479  12479  | 489    3479   3789   |
+            | 12679  123479 134679 | 12349  234789 124789 | 1489   5      3789   |
+            |----------------------+---------------------+-----------------------|
+            | 1369   8      2      | 349    34569  3469   | 7      349    1359   |
+            | 1379   13479  13479  | 5      23489  2479   | 6      2349   13489  |
+            | 3679   34579  345679 | 23489  1      24679  | 2489   23479  34589  |
+            *--------------------------------------------------------------------*
+        ";
+        let parsed = Grid::from_pencilmarks(board).unwrap();
+        assert_eq!(parsed.givens(), 17);
+        assert_eq!(parsed.get(cell("r1c2")), 6);
+        assert_eq!(parsed.candidates(cell("r1c1")).to_string(), "1789");
+    }
+
+    #[test]
+    fn pencilmarks_with_placed_values() {
+        let mut grid: Grid = "060000300400700000000000080000008012500600000000000050082000700000500600000010000"
+            .parse()
+            .unwrap();
+        grid.set_checked(cell("r1c1"), digit(9)).unwrap();
+        grid.set_checked(cell("r9c9"), digit(8)).unwrap();
+        grid.candidates_mut(cell("r1c3")).unset(0);
+
+        let pencilmarks = grid.to_marked_pencilmark_string();
+        assert!(pencilmarks
+            .lines()
+            .nth(1)
+            .unwrap()
+            .starts_with("| *9      6       578 "));
+        let parsed = Grid::from_pencilmarks(&pencilmarks).unwrap();
+        assert!(parsed.eq_full(&grid));
+        assert_eq!(parsed.givens(), 17);
+        assert!(!parsed.frozen(cell("r1c1")));
+        assert_eq!(parsed.to_marked_pencilmark_string(), pencilmarks);
+
+        let pencilmarks = grid.to_pencilmark_string();
+        let row = pencilmarks
+            .lines()
+            .nth(1)
+            .unwrap()
+            .split_whitespace()
+            .take(4)
+            .collect::<Vec<&str>>();
+        assert_eq!(row, ["|", "9", "6", "578"]);
+        let parsed = Grid::from_pencilmarks(&pencilmarks).unwrap();
+        assert_eq!(parsed.givens(), 19);
+
+        let contents = grid.to_candidate_content_string();
+        let parsed = Grid::from_candidate_content_string(&contents).unwrap();
+        assert!(parsed.eq_full(&grid));
+        assert!(!parsed.frozen(cell("r9c9")));
+        assert_eq!(parsed.to_candidate_content_string(), contents);
+    }
+
+    #[test]
+    fn pencilmarks_with_lone_candidates() {
+        let mut grid: Grid = "060000300400700000000000080000008012500600000000000050082000700000500600000010000"
+            .parse()
+            .unwrap();
+        grid.set_checked(cell("r1c1"), digit(9)).unwrap();
+        grid.candidates_mut(cell("r1c3")).unset_all();
+        grid.candidates_mut(cell("r1c3")).set(6);
+
+        let pencilmarks = grid.to_marked_pencilmark_string();
+        let row = pencilmarks
+            .lines()
+            .nth(1)
+            .unwrap()
+            .split_whitespace()
+            .take(4)
+            .collect::<Vec<&str>>();
+        assert_eq!(row, ["|", "*9", "6", "?7"]);
+        let parsed = Grid::from_pencilmarks(&pencilmarks).unwrap();
+        assert!(parsed.eq_full(&grid));
+        assert_eq!(parsed.givens(), 17);
+        assert_eq!(parsed.get(cell("r1c3")), 0);
+
+        let contents = grid.to_candidate_content_string();
+        assert!(
+            contents.ends_with(":210000100100100000000000010000001011100100000000000010011000100000100100000010000")
+        );
+        let parsed = Grid::from_candidate_content_string(&contents).unwrap();
+        assert!(parsed.eq_full(&grid));
+        assert_eq!(parsed.get(cell("r1c3")), 0);
+    }
+
+    #[test]
+    fn pencilmarks_without_candidates() {
+        let mut grid: Grid = "060000300400700000000000080000008012500600000000000050082000700000500600000010000"
+            .parse()
+            .unwrap();
+        grid.candidates_mut(cell("r1c1")).unset_all();
+        grid.candidates_mut(cell("r9c9")).unset_all();
+
+        let pencilmarks = grid.to_pencilmark_string();
+        assert!(pencilmarks.lines().nth(1).unwrap().starts_with("| 0  "));
+        let parsed = Grid::from_pencilmarks(&pencilmarks).unwrap();
+        assert!(parsed.eq_full(&grid));
+        assert!(parsed.candidates(cell("r1c1")).none());
+        assert_eq!(parsed.to_pencilmark_string(), pencilmarks);
+
+        let candidates = grid.to_candidate_string();
+        assert!(candidates.starts_with("000000000"));
+        let parsed = Grid::from_candidate_string(&candidates).unwrap();
+        assert!(parsed.eq_full(&grid));
+        assert!(parsed.candidates(cell("r9c9")).none());
+        assert_eq!(parsed.to_candidate_string(), candidates);
+        assert_eq!(parsed.to_marked_pencilmark_string(), pencilmarks);
+        let parsed = Grid::from_candidate_string(&"0".repeat(729)).unwrap();
+        assert_eq!(parsed.givens(), 0);
+        assert!(parsed.candidates(cell("r1c1")).none());
+    }
+
+    #[test]
+    fn pencilmark_errors() {
+        let result = Grid::from_pencilmarks("| 12 30 |");
+        assert_eq!(
+            result.err().unwrap().downcast::<ParseError>().unwrap().as_ref(),
+            &ParseError::InvalidDigit('0', 6)
+        );
+        let result = Grid::from_pencilmarks("| 12 03 |");
+        assert_eq!(
+            result.err().unwrap().downcast::<ParseError>().unwrap().as_ref(),
+            &ParseError::InvalidDigit('0', 5)
+        );
+        let result = Grid::from_pencilmarks("| 12 3 |");
+        assert_eq!(
+            result.err().unwrap().downcast::<ParseError>().unwrap().as_ref(),
+            &ParseError::InvalidLength(2)
+        );
+        let result = Grid::from_pencilmarks("| *12 3 |");
+        assert_eq!(
+            result.err().unwrap().downcast::<ParseError>().unwrap().as_ref(),
+            &ParseError::InvalidDigit('*', 2)
+        );
+        let contents = format!("{}:{}", "1".repeat(729), "1".repeat(81));
+        let result = Grid::from_candidate_content_string(&contents);
+        assert_eq!(
+            result.err().unwrap().downcast::<ParseError>().unwrap().as_ref(),
+            &ParseError::InvalidSudoku(0)
+        );
+        let result = Grid::from_candidate_string(&"1".repeat(700));
+        assert_eq!(
+            result.err().unwrap().downcast::<ParseError>().unwrap().as_ref(),
+            &ParseError::InvalidBitLength(700)
+        );
+        let result = Grid::from_pencilmarks("| ?12 3 |");
+        assert_eq!(
+            result.err().unwrap().downcast::<ParseError>().unwrap().as_ref(),
+            &ParseError::InvalidDigit('?', 2)
+        );
+        let result = Grid::from_candidate_content_string(&format!("{}:1", "1".repeat(729)));
+        assert_eq!(
+            result.err().unwrap().downcast::<ParseError>().unwrap().as_ref(),
+            &ParseError::InvalidLength(1)
+        );
+        let result = Grid::from_candidate_string(&format!("{}:{}", "1".repeat(729), "1".repeat(81)));
+        assert_eq!(
+            result.err().unwrap().downcast::<ParseError>().unwrap().as_ref(),
+            &ParseError::InvalidDigit(':', 729)
+        );
+    }
 }
@@ -1,4 +1,20 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
 pub mod candidates;
+pub mod canonical;
+mod collections;
+pub mod coordinates;
+pub mod formats;
+#[cfg(feature = "generator")]
 pub mod generator;
 pub mod grid;
+pub mod logic;
+pub mod multigrid;
+pub mod render;
+#[cfg(feature = "serde")]
+pub mod serialization;
+pub mod session;
 pub mod solver;
+pub mod transform;
@@ -0,0 +1,595 @@
+use alloc::{collections::BTreeMap, format, vec, vec::Vec};
+use core::fmt;
+use itertools::Itertools;
+
+use super::candidates::Candidates;
+use super::coordinates::{CellIndex, Digit, Unit, UnitIndex};
+use super::grid::{Grid, SetError};
+
+/// The score of puzzles the techniques can't finish.
+pub const GUESSING: f32 = 10.0;
+
+/// A human solving technique. Techniques are ordered from the easiest to the hardest, which is also the order they're
+/// tried in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Technique {
+    /// The last empty cell of a unit
+    FullHouse,
+    /// The only cell of a unit left for a digit
+    HiddenSingle,
+    /// The only candidate left in a cell
+    NakedSingle,
+    /// A digit confined to a single line within a box, eliminated from the rest of the line
+    Pointing,
+    /// A digit confined to a single box within a line, eliminated from the rest of the box
+    Claiming,
+    NakedPair,
+    XWing,
+    HiddenPair,
+    NakedTriple,
+    Swordfish,
+    HiddenTriple,
+    XyWing,
+    NakedQuad,
+    Jellyfish,
+    HiddenQuad,
+}
+
+impl Technique {
+    pub const ALL: [Technique; 15] = [
+        Technique::FullHouse,
+        Technique::HiddenSingle,
+        Technique::NakedSingle,
+        Technique::Pointing,
+        Technique::Claiming,
+        Technique::NakedPair,
+        Technique::XWing,
+        Technique::HiddenPair,
+        Technique::NakedTriple,
+        Technique::Swordfish,
+        Technique::HiddenTriple,
+        Technique::XyWing,
+        Technique::NakedQuad,
+        Technique::Jellyfish,
+        Technique::HiddenQuad,
+    ];
+
+    /// The rating of the technique on the Sudoku Explainer scale.
+    pub fn difficulty(self) -> f32 {
+        match self {
+            Technique::FullHouse => 1.0,
+            Technique::HiddenSingle => 1.5,
+            Technique::NakedSingle => 2.3,
+            Technique::Pointing => 2.6,
+            Technique::Claiming => 2.8,
+            Technique::NakedPair => 3.0,
+            Technique::XWing => 3.2,
+            Technique::HiddenPair => 3.4,
+            Technique::NakedTriple => 3.6,
+            Technique::Swordfish => 3.8,
+            Technique::HiddenTriple => 4.0,
+            Technique::XyWing => 4.2,
+            Technique::NakedQuad => 5.0,
+            Technique::Jellyfish => 5.2,
+            Technique::HiddenQuad => 5.4,
+        }
+    }
+
+    /// Finds a step using the technique, based on the candidates stored in the grid.
+    pub fn find(self, grid: &Grid) -> Option<Step> {
+        match self {
+            Technique::FullHouse => full_house(grid),
+            Technique::HiddenSingle => hidden_single(grid),
+            Technique::NakedSingle => naked_single(grid),
+            Technique::Pointing => pointing(grid),
+            Technique::Claiming => claiming(grid),
+            Technique::NakedPair => naked_subset(grid, 2),
+            Technique::XWing => fish(grid, 2),
+            Technique::HiddenPair => hidden_subset(grid, 2),
+            Technique::NakedTriple => naked_subset(grid, 3),
+            Technique::Swordfish => fish(grid, 3),
+            Technique::HiddenTriple => hidden_subset(grid, 3),
+            Technique::XyWing => xy_wing(grid),
+            Technique::NakedQuad => naked_subset(grid, 4),
+            Technique::Jellyfish => fish(grid, 4),
+            Technique::HiddenQuad => hidden_subset(grid, 4),
+        }
+    }
+}
+
+impl fmt::Display for Technique {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Technique::FullHouse => "Full house",
+            Technique::HiddenSingle => "Hidden single",
+            Technique::NakedSingle => "Naked single",
+            Technique::Pointing => "Pointing",
+            Technique::Claiming => "Claiming",
+            Technique::NakedPair => "Naked pair",
+            Technique::XWing => "X-Wing",
+            Technique::HiddenPair => "Hidden pair",
+            Technique::NakedTriple => "Naked triple",
+            Technique::Swordfish => "Swordfish",
+            Technique::HiddenTriple => "Hidden triple",
+            Technique::XyWing => "XY-Wing",
+            Technique::NakedQuad => "Naked quad",
+            Technique::Jellyfish => "Jellyfish",
+            Technique::HiddenQuad => "Hidden quad",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// A single deduction: the values it places or the candidates it eliminates, and what it's based on.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Step {
+    pub technique: Technique,
+    /// The digits the deduction is about
+    pub digits: Candidates,
+    /// The units the deduction is about
+    pub units: Vec<Unit>,
+    /// The cells the deduction is based on
+    pub cells: Vec<CellIndex>,
+    pub placements: Vec<(CellIndex, Digit)>,
+    pub eliminations: Vec<(CellIndex, Digit)>,
+}
+
+impl Step {
+    fn placement(technique: Technique, units: Vec<Unit>, cell: CellIndex, digit: Digit) -> Step {
+        Step {
+            technique,
+            digits: Candidates::from_digits(&[digit]),
+            units,
+            cells: vec![],
+            placements: vec![(cell, digit)],
+            eliminations: vec![],
+        }
+    }
+
+    /// A step eliminating `digits` from the candidates of `targets`, or none if no target holds any of them.
+    fn elimination(
+        grid: &Grid,
+        technique: Technique,
+        digits: Candidates,
+        units: Vec<Unit>,
+        cells: Vec<CellIndex>,
+        targets: impl Iterator<Item = CellIndex>,
+    ) -> Option<Step> {
+        let eliminations = targets
+            .filter(|target| !cells.contains(target))
+            .flat_map(|target| to_digits(*grid.candidates(target) & digits).map(move |digit| (target, digit)))
+            .collect::<Vec<(CellIndex, Digit)>>();
+        (!eliminations.is_empty()).then_some(Step {
+            technique,
+            digits,
+            units,
+            cells,
+            placements: vec![],
+            eliminations,
+        })
+    }
+
+    /// Places the values and removes the candidates of the step. The grid is left untouched if a placement fails.
+    pub fn apply(&self, grid: &mut Grid) -> Result<(), SetError> {
+        let mut next = *grid;
+        for (cell, digit) in &self.placements {
+            next.set_checked(*cell, Some(*digit))?;
+        }
+        for (cell, digit) in &self.eliminations {
+            next.candidates_mut(*cell).unset_digit(*digit);
+        }
+        *grid = next;
+        Ok(())
+    }
+}
+
+/// Describes the step in Hodoku style, such as `Naked pair 37 in row 1 (r1c1, r1c5): r1c2<>3, r1c9<>7`.
+impl fmt::Display for Step {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.technique)?;
+        if self.placements.is_empty() {
+            write!(f, " {}", self.digits)?;
+        }
+        if !self.units.is_empty() {
+            write!(f, " in {}", self.units.iter().join(", "))?;
+        }
+        if !self.cells.is_empty() {
+            write!(f, " ({})", self.cells.iter().join(", "))?;
+        }
+        let placements = self
+            .placements
+            .iter()
+            .map(|(cell, digit)| format!("{}={}", cell, digit));
+        let eliminations = self
+            .eliminations
+            .iter()
+            .map(|(cell, digit)| format!("{}<>{}", cell, digit));
+        write!(f, ": {}", placements.chain(eliminations).join(", "))
+    }
+}
+
+/// Finds the easiest step, based on the candidates stored in the grid.
+pub fn next_step(grid: &Grid) -> Option<Step> {
+    Technique::ALL.iter().find_map(|technique| technique.find(grid))
+}
+
+/// The steps taken by repeatedly applying the easiest technique that makes progress, and the grid they lead to.
+#[derive(Clone, Debug)]
+pub struct LogicalSolution {
+    pub steps: Vec<Step>,
+    pub grid: Grid,
+}
+
+impl LogicalSolution {
+    pub fn is_complete(&self) -> bool {
+        self.grid.is_complete()
+    }
+}
+
+/// Solves the grid with the techniques as far as they go, starting from the candidates allowed by the values.
+pub fn solve(grid: &Grid) -> LogicalSolution {
+    let mut grid = *grid;
+    grid.recompute_all_candidates();
+    let mut steps = vec![];
+    while !grid.is_complete() {
+        match next_step(&grid) {
+            Some(step) if step.apply(&mut grid).is_ok() => steps.push(step),
+            _ => break,
+        }
+    }
+    LogicalSolution { steps, grid }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Rating {
+    /// The difficulty of the hardest technique used, or `GUESSING` if the techniques can't finish the puzzle
+    pub score: f32,
+    pub hardest: Option<Technique>,
+    /// How many steps each technique took
+    pub counts: BTreeMap<Technique, usize>,
+    pub needs_guessing: bool,
+}
+
+impl Rating {
+    pub fn of(solution: &LogicalSolution) -> Rating {
+        let mut counts = BTreeMap::new();
+        for step in &solution.steps {
+            *counts.entry(step.technique).or_insert(0) += 1;
+        }
+        let hardest = counts.keys().last().copied();
+        let needs_guessing = !solution.is_complete();
+        Rating {
+            score: match hardest {
+                _ if needs_guessing => GUESSING,
+                Some(technique) => technique.difficulty(),
+                None => 0.0,
+            },
+            hardest,
+            counts,
+            needs_guessing,
+        }
+    }
+}
+
+pub fn rate(grid: &Grid) -> Rating {
+    Rating::of(&solve(grid))
+}
+
+/// Boxes first since their singles are the easiest to spot, then rows and columns.
+fn units() -> impl Iterator<Item = Unit> {
+    UnitIndex::all()
+        .map(Unit::Box)
+        .chain(UnitIndex::all().map(Unit::Row))
+        .chain(UnitIndex::all().map(Unit::Column))
+}
+
+fn lines() -> impl Iterator<Item = Unit> {
+    UnitIndex::all()
+        .map(Unit::Row)
+        .chain(UnitIndex::all().map(Unit::Column))
+}
+
+fn to_digits(candidates: Candidates) -> impl Iterator<Item = Digit> {
+    candidates.iter().map(|digit| Digit::new(digit).unwrap())
+}
+
+/// The cells of the unit with the digit as a candidate.
+fn cells_with(grid: &Grid, unit: Unit, digit: Digit) -> Vec<CellIndex> {
+    unit.cells()
+        .filter(|cell| grid.candidates(*cell).get_digit(digit))
+        .collect()
+}
+
+fn full_house(grid: &Grid) -> Option<Step> {
+    units().find_map(|unit| {
+        let (cell,) = unit.cells().filter(|cell| grid.get(*cell) == 0).collect_tuple()?;
+        let placed = unit.cells().filter_map(|cell| Digit::new(grid.get(cell)));
+        let digit = (!placed.collect::<Candidates>()).single()?;
+        Some(Step::placement(
+            Technique::FullHouse,
+            vec![unit],
+            cell,
+            Digit::new(digit).unwrap(),
+        ))
+    })
+}
+
+fn hidden_single(grid: &Grid) -> Option<Step> {
+    units()
+        .flat_map(|unit| Digit::all().map(move |digit| (unit, digit)))
+        .find_map(|(unit, digit)| {
+            let (cell,) = cells_with(grid, unit, digit).into_iter().collect_tuple()?;
+            Some(Step::placement(Technique::HiddenSingle, vec![unit], cell, digit))
+        })
+}
+
+fn naked_single(grid: &Grid) -> Option<Step> {
+    CellIndex::all().find_map(|cell| {
+        let digit = grid.candidates(cell).single()?;
+        Some(Step::placement(
+            Technique::NakedSingle,
+            vec![],
+            cell,
+            Digit::new(digit).unwrap(),
+        ))
+    })
+}
+
+fn pointing(grid: &Grid) -> Option<Step> {
+    UnitIndex::all()
+        .map(Unit::Box)
+        .flat_map(|r#box| Digit::all().map(move |digit| (r#box, digit)))
+        .find_map(|(r#box, digit)| {
+            let cells = cells_with(grid, r#box, digit);
+            if cells.len() < 2 {
+                return None;
+            }
+            let [row, column, _] = cells[0].units();
+            [row, column]
+                .into_iter()
+                .filter(|line| cells.iter().all(|cell| cell.units().contains(line)))
+                .find_map(|line| {
+                    Step::elimination(
+                        grid,
+                        Technique::Pointing,
+                        Candidates::from_digits(&[digit]),
+                        vec![r#box, line],
+                        cells.clone(),
+                        line.cells(),
+                    )
+                })
+        })
+}
+
+fn claiming(grid: &Grid) -> Option<Step> {
+    lines()
+        .flat_map(|line| Digit::all().map(move |digit| (line, digit)))
+        .find_map(|(line, digit)| {
+            let cells = cells_with(grid, line, digit);
+            let [_, _, r#box] = cells.first()?.units();
+            if cells.len() < 2 || !cells.iter().all(|cell| cell.units().contains(&r#box)) {
+                return None;
+            }
+            Step::elimination(
+                grid,
+                Technique::Claiming,
+                Candidates::from_digits(&[digit]),
+                vec![line, r#box],
+                cells,
+                r#box.cells(),
+            )
+        })
+}
+
+fn technique(naked: bool, size: usize) -> Technique {
+    match (naked, size) {
+        (true, 2) => Technique::NakedPair,
+        (true, 3) => Technique::NakedTriple,
+        (true, _) => Technique::NakedQuad,
+        (false, 2) => Technique::HiddenPair,
+        (false, 3) => Technique::HiddenTriple,
+        (false, _) => Technique::HiddenQuad,
+    }
+}
+
+/// `size` cells of a unit holding only `size` candidates between them, which can't go anywhere else in the unit.
+fn naked_subset(grid: &Grid, size: usize) -> Option<Step> {
+    units().find_map(|unit| {
+        let cells = unit
+            .cells()
+            .filter(|cell| (2..=size).contains(&grid.candidates(*cell).count()))
+            .collect::<Vec<CellIndex>>();
+        cells.into_iter().combinations(size).find_map(|subset| {
+            let digits = subset
+                .iter()
+                .fold(Candidates::new(false), |digits, cell| digits | *grid.candidates(*cell));
+            if digits.count() != size {
+                return None;
+            }
+            Step::elimination(grid, technique(true, size), digits, vec![unit], subset, unit.cells())
+        })
+    })
+}
+
+/// `size` digits confined to the same `size` cells of a unit, which can't hold any other digit.
+fn hidden_subset(grid: &Grid, size: usize) -> Option<Step> {
+    units().find_map(|unit| {
+        let digits = Digit::all()
+            .map(|digit| (digit, cells_with(grid, unit, digit)))
+            .filter(|(_, cells)| (2..=size).contains(&cells.len()))
+            .collect::<Vec<(Digit, Vec<CellIndex>)>>();
+        digits.iter().combinations(size).find_map(|subset| {
+            let cells = subset
+                .iter()
+                .flat_map(|(_, cells)| cells)
+                .copied()
+                .sorted()
+                .dedup()
+                .collect::<Vec<CellIndex>>();
+            if cells.len() != size {
+                return None;
+            }
+            let digits = subset.iter().map(|(digit, _)| *digit).collect::<Candidates>();
+            Step::elimination(
+                grid,
+                technique(false, size),
+                !digits,
+                vec![unit],
+                vec![],
+                cells.iter().copied(),
+            )
+            .map(|step| Step { digits, cells, ..step })
+        })
+    })
+}
+
+/// Builds a row or a column from its index.
+type Line = fn(UnitIndex) -> Unit;
+
+/// A digit confined to the same `size` columns in `size` rows, or the other way around, which can't go anywhere else
+/// in those columns.
+fn fish(grid: &Grid, size: usize) -> Option<Step> {
+    let technique = match size {
+        2 => Technique::XWing,
+        3 => Technique::Swordfish,
+        _ => Technique::Jellyfish,
+    };
+    Digit::all()
+        .cartesian_product([true, false])
+        .find_map(|(digit, by_rows)| {
+            let (base, cover): (Line, Line) = if by_rows {
+                (Unit::Row, Unit::Column)
+            } else {
+                (Unit::Column, Unit::Row)
+            };
+            let cross = |cell: &CellIndex| UnitIndex::at(if by_rows { cell.column() } else { cell.row() });
+            let lines = UnitIndex::all()
+                .map(|line| (line, cells_with(grid, base(line), digit)))
+                .filter(|(_, cells)| (2..=size).contains(&cells.len()))
+                .collect::<Vec<(UnitIndex, Vec<CellIndex>)>>();
+            lines.iter().combinations(size).find_map(|subset| {
+                let cells = subset
+                    .iter()
+                    .flat_map(|(_, cells)| cells)
+                    .copied()
+                    .sorted()
+                    .collect::<Vec<CellIndex>>();
+                let covers = cells.iter().map(cross).sorted().dedup().collect::<Vec<UnitIndex>>();
+                if covers.len() != size {
+                    return None;
+                }
+                let bases = subset.iter().map(|(line, _)| base(*line)).collect::<Vec<Unit>>();
+                let targets = covers.iter().flat_map(|cover_line| cover(*cover_line).cells());
+                Step::elimination(
+                    grid,
+                    technique,
+                    Candidates::from_digits(&[digit]),
+                    bases.clone(),
+                    cells.clone(),
+                    targets.filter(|cell| !bases.iter().any(|line| cell.units().contains(line))),
+                )
+            })
+        })
+}
+
+/// A pivot with candidates `xy` seeing pincers with `xz` and `yz`. One of the pincers holds `z`, so `z` can be
+/// eliminated from every cell seeing both of them.
+fn xy_wing(grid: &Grid) -> Option<Step> {
+    let bivalue = |cell: &CellIndex| grid.candidates(*cell).count() == 2;
+    CellIndex::all().filter(bivalue).find_map(|pivot| {
+        let xy = *grid.candidates(pivot);
+        let pincers = pivot
+            .peers()
+            .filter(bivalue)
+            .filter(|pincer| (*grid.candidates(*pincer) & xy).count() == 1)
+            .collect::<Vec<CellIndex>>();
+        pincers.iter().array_combinations().find_map(|[first, second]| {
+            let (xz, yz) = (*grid.candidates(*first), *grid.candidates(*second));
+            let z = xz & yz;
+            if xz & xy == yz & xy || z.count() != 1 || (z & xy).some() {
+                return None;
+            }
+            let targets = first.peers().filter(|cell| second.peers().any(|peer| peer == *cell));
+            Step::elimination(
+                grid,
+                Technique::XyWing,
+                z,
+                vec![],
+                vec![pivot, *first, *second],
+                targets,
+            )
+            .map(|step| Step { digits: xy | z, ..step })
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::solver::alx_solve;
+    use alloc::string::ToString;
+
+    const PUZZLES: [&str; 4] = [
+        "000000010400000000020000000000050407008000300001090000300400200050100000000806000",
+        "000000012000035000000600070700000300000400800100000000000120000080000040050000600",
+        "013500000000020060000000000600008070200000000000300000720040000000000301000800500",
+        "100000002090400050006000700050903000000070000000850040700000600030009080002000001",
+    ];
+
+    #[test]
+    fn steps_agree_with_solution() {
+        for puzzle in PUZZLES {
+            let grid: Grid = puzzle.parse().unwrap();
+            let solution = alx_solve(&grid, 1).remove(0);
+            let logical = solve(&grid);
+            for step in &logical.steps {
+                for (cell, digit) in &step.placements {
+                    assert_eq!(solution.get(*cell), digit.get(), "{}", step);
+                }
+                for (cell, digit) in &step.eliminations {
+                    assert_ne!(solution.get(*cell), digit.get(), "{}", step);
+                }
+            }
+            assert!(logical
+                .grid
+                .cells()
+                .zip(solution.cells())
+                .all(|(value, solved)| value == 0 || value == solved));
+        }
+    }
+
+    #[test]
+    fn rating() {
+        let rating = rate(&PUZZLES[0].parse().unwrap());
+        assert!(!rating.needs_guessing);
+        assert_eq!(rating.score, rating.hardest.unwrap().difficulty());
+        assert_eq!(rating.counts.values().sum::<usize>(), 81 - 17);
+
+        let rating = rate(&PUZZLES[3].parse().unwrap());
+        assert!(rating.needs_guessing);
+        assert_eq!(rating.score, GUESSING);
+    }
+
+    #[test]
+    fn techniques() {
+        let mut grid: Grid = PUZZLES[0].parse().unwrap();
+        let step = next_step(&grid).unwrap();
+        assert_eq!(step.technique, Technique::HiddenSingle);
+        step.apply(&mut grid).unwrap();
+        assert_eq!(grid.get(step.placements[0].0), step.placements[0].1.get());
+
+        // Row 1 has 5 and 6 only in its first two cells, which makes a hidden pair
+        let mut grid = Grid::new();
+        for cell in 2..9 {
+            grid.candidates_mut(CellIndex::at(cell)).unset(4);
+            grid.candidates_mut(CellIndex::at(cell)).unset(5);
+        }
+        let step = Technique::HiddenPair.find(&grid).unwrap();
+        assert_eq!(
+            step.to_string(),
+            "Hidden pair 56 in row 1 (r1c1, r1c2): r1c1<>1, r1c1<>2, r1c1<>3, r1c1<>4, r1c1<>7, r1c1<>8, r1c1<>9, \
+             r1c2<>1, r1c2<>2, r1c2<>3, r1c2<>4, r1c2<>7, r1c2<>8, r1c2<>9"
+        );
+        assert_eq!(Technique::NakedPair.find(&grid), None);
+    }
+}
@@ -0,0 +1,452 @@
+use alloc::{boxed::Box, vec, vec::Vec};
+use core::{error::Error, fmt};
+use itertools::Itertools;
+#[cfg(feature = "generator")]
+use rand::{prelude::IteratorRandom, thread_rng};
+use thiserror::Error;
+
+use super::collections::{self, Map, Set};
+use super::coordinates::{CellIndex, Digit};
+#[cfg(feature = "generator")]
+use super::generator::seed_grid;
+use super::grid::{Grid, SetError};
+use super::solver::{select, solve};
+
+#[derive(PartialEq, Eq, Error, Debug)]
+pub enum LayoutError {
+    #[error("Layout must contain at least one grid")]
+    Empty,
+    #[error("Grid offset ({0}, {1}) is not aligned to a box")]
+    Unaligned(usize, usize),
+    #[error("Grid offset ({0}, {1}) is used more than once")]
+    Duplicate(usize, usize),
+}
+
+/// Describes a gattai puzzle as the row and column offsets of its 9x9 sub-grids on a shared board.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Layout {
+    offsets: Vec<(usize, usize)>,
+    // Board position of every distinct cell, indexed by cell id
+    positions: Vec<(usize, usize)>,
+    // Every (grid, cell) pair sharing a cell, indexed by cell id
+    locations: Vec<Vec<(usize, CellIndex)>>,
+    // Cell id of every sub-grid cell
+    ids: Vec<[usize; 81]>,
+}
+
+impl Layout {
+    pub fn new(offsets: Vec<(usize, usize)>) -> Result<Layout, LayoutError> {
+        if offsets.is_empty() {
+            return Err(LayoutError::Empty);
+        }
+        if let Some((row, column)) = offsets.iter().find(|(row, column)| row % 3 != 0 || column % 3 != 0) {
+            return Err(LayoutError::Unaligned(*row, *column));
+        }
+        if let Some((_, (row, column))) = offsets
+            .iter()
+            .enumerate()
+            .find(|(position, offset)| offsets[..*position].contains(offset))
+        {
+            return Err(LayoutError::Duplicate(*row, *column));
+        }
+
+        let mut cells: Map<(usize, usize), Vec<(usize, CellIndex)>> = Map::new();
+        for (grid, (row, column)) in offsets.iter().enumerate() {
+            for cell in CellIndex::all() {
+                cells
+                    .entry((row + cell.row(), column + cell.column()))
+                    .or_default()
+                    .push((grid, cell));
+            }
+        }
+        let (positions, locations): (Vec<_>, Vec<_>) = cells.into_iter().sorted().unzip();
+        let mut ids = vec![[0; 81]; offsets.len()];
+        for (id, location) in locations.iter().enumerate() {
+            for (grid, cell) in location {
+                ids[*grid][cell.index()] = id;
+            }
+        }
+
+        Ok(Layout {
+            offsets,
+            positions,
+            locations,
+            ids,
+        })
+    }
+
+    /// Five grids where the center grid shares a corner box with each of the other four.
+    pub fn samurai() -> Layout {
+        Layout::new(vec![(0, 0), (0, 12), (6, 6), (12, 0), (12, 12)]).unwrap()
+    }
+
+    pub fn offsets(&self) -> &[(usize, usize)] {
+        &self.offsets
+    }
+
+    pub fn grids(&self) -> usize {
+        self.offsets.len()
+    }
+
+    pub fn cells(&self) -> usize {
+        self.positions.len()
+    }
+
+    pub fn position(&self, id: usize) -> (usize, usize) {
+        self.positions[id]
+    }
+
+    pub fn locations(&self, id: usize) -> &[(usize, CellIndex)] {
+        &self.locations[id]
+    }
+
+    pub fn id(&self, grid: usize, cell: CellIndex) -> usize {
+        self.ids[grid][cell.index()]
+    }
+}
+
+/// A set of overlapping grids. Cells shared by several grids always hold the same value.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MultiGrid {
+    layout: Layout,
+    grids: Vec<Grid>,
+}
+
+impl MultiGrid {
+    pub fn new(layout: Layout) -> Self {
+        let grids = vec![Grid::new(); layout.grids()];
+        MultiGrid { layout, grids }
+    }
+
+    pub fn layout(&self) -> &Layout {
+        &self.layout
+    }
+
+    pub fn grids(&self) -> &[Grid] {
+        &self.grids
+    }
+
+    pub fn grid(&self, grid: usize) -> &Grid {
+        &self.grids[grid]
+    }
+
+    pub fn get(&self, grid: usize, cell: CellIndex) -> u8 {
+        self.grids[grid].get(cell)
+    }
+
+    /// Sets the value in every grid sharing the cell without looking at the peers.
+    pub fn set_unchecked(&mut self, grid: usize, cell: CellIndex, value: Option<Digit>) -> Result<(), SetError> {
+        self.set_with(grid, cell, |grid, cell| grid.set_unchecked(cell, value))
+    }
+
+    /// Sets the value in every grid sharing the cell and updates the candidates. Nothing is changed if any of them
+    /// refuses it, and the error refers to cells within the refusing grid.
+    pub fn set_checked(&mut self, grid: usize, cell: CellIndex, value: Option<Digit>) -> Result<(), SetError> {
+        self.set_with(grid, cell, |grid, cell| grid.set_checked(cell, value))
+    }
+
+    fn set_with(
+        &mut self,
+        grid: usize,
+        cell: CellIndex,
+        set: impl Fn(&mut Grid, CellIndex) -> Result<(), SetError>,
+    ) -> Result<(), SetError> {
+        let mut grids = self.grids.clone();
+        for (grid, cell) in self.layout.locations(self.layout.id(grid, cell)) {
+            set(&mut grids[*grid], *cell)?;
+        }
+        self.grids = grids;
+        Ok(())
+    }
+
+    pub fn freeze(&mut self, grid: usize, cell: CellIndex) {
+        for (grid, cell) in self.layout.locations(self.layout.id(grid, cell)) {
+            self.grids[*grid].freeze(*cell);
+        }
+    }
+
+    pub fn frozen(&self, grid: usize, cell: CellIndex) -> bool {
+        self.grids[grid].frozen(cell)
+    }
+
+    /// Counts the frozen cells, counting shared cells only once.
+    pub fn givens(&self) -> usize {
+        (0..self.layout.cells()).filter(|id| self.frozen_id(*id)).count()
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.grids.iter().all(|grid| grid.is_complete())
+    }
+
+    pub fn is_valid(&self) -> bool {
+        self.grids.iter().all(|grid| grid.is_valid())
+    }
+
+    fn get_id(&self, id: usize) -> u8 {
+        let (grid, cell) = self.layout.locations(id)[0];
+        self.grids[grid].get(cell)
+    }
+
+    fn set_id(&mut self, id: usize, value: u8) -> Result<(), SetError> {
+        let (grid, cell) = self.layout.locations(id)[0];
+        self.set_unchecked(grid, cell, Digit::new(value))
+    }
+
+    fn frozen_id(&self, id: usize) -> bool {
+        let (grid, cell) = self.layout.locations(id)[0];
+        self.grids[grid].frozen(cell)
+    }
+
+    /// Parses one sub-grid per line in the order of the layout offsets.
+    pub fn parse(layout: Layout, string: &str) -> Result<MultiGrid, Box<dyn Error + Sync + Send>> {
+        let lines = string
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .collect::<Vec<&str>>();
+        if lines.len() != layout.grids() {
+            return Err(Box::new(MultiGridError::LineCount(layout.grids(), lines.len())));
+        }
+        let grids = lines
+            .iter()
+            .map(|line| line.trim().parse::<Grid>())
+            .collect::<Result<Vec<Grid>, _>>()?;
+        let mut multigrid = MultiGrid::new(layout);
+        for id in 0..multigrid.layout.cells() {
+            let locations = multigrid.layout.locations(id);
+            let (grid, cell) = locations[0];
+            let value = grids[grid].get(cell);
+            if let Some((grid, cell)) = locations.iter().find(|(grid, cell)| grids[*grid].get(*cell) != value) {
+                return Err(Box::new(MultiGridError::Mismatch(*grid, *cell)));
+            }
+            if value > 0 {
+                multigrid
+                    .set_checked(grid, cell, Digit::new(value))
+                    .map_err(|_| MultiGridError::InvalidSudoku(grid, cell))?;
+                multigrid.freeze(grid, cell);
+            }
+        }
+        Ok(multigrid)
+    }
+}
+
+impl fmt::Display for MultiGrid {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.grids.iter().join("\n"))
+    }
+}
+
+#[derive(PartialEq, Eq, Error, Debug)]
+pub enum MultiGridError {
+    #[error("Expected {0} grids but found {1} lines")]
+    LineCount(usize, usize),
+    #[error("Shared cell {1} of grid {0} does not match the other grids")]
+    Mismatch(usize, CellIndex),
+    #[error("Invalid sudoku at {1} of grid {0}")]
+    InvalidSudoku(usize, CellIndex),
+}
+
+#[derive(PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy)]
+enum Constraint {
+    Cell(usize),
+    Row(usize, usize, u8),
+    Column(usize, usize, u8),
+    Box(usize, usize, u8),
+}
+
+type CandidateType = (usize, u8);
+
+/// Solves all grids of the layout at once as a single exact cover problem.
+pub fn alx_solve(multigrid: &MultiGrid, limit: usize) -> Vec<MultiGrid> {
+    if !multigrid.is_valid() {
+        return vec![];
+    }
+
+    let layout = &multigrid.layout;
+    let mut y: Map<CandidateType, Vec<Constraint>> = collections::map(layout.cells() * 9);
+    for id in 0..layout.cells() {
+        let (row, column) = layout.position(id);
+        for n in 1..10 {
+            let mut constraints = vec![Constraint::Cell(id), Constraint::Box(row / 3, column / 3, n)];
+            for (grid, cell) in layout.locations(id) {
+                constraints.push(Constraint::Row(*grid, cell.row(), n));
+                constraints.push(Constraint::Column(*grid, cell.column(), n));
+            }
+            y.insert((id, n), constraints);
+        }
+    }
+    let mut x: Map<Constraint, Set<CandidateType>> = Map::new();
+    for (i, row) in y.iter() {
+        for j in row {
+            x.entry(*j).or_insert_with(|| collections::set(9)).insert(*i);
+        }
+    }
+
+    for id in 0..layout.cells() {
+        let value = multigrid.get_id(id);
+        if value > 0 {
+            select(&mut x, &y, (id, value));
+        }
+    }
+
+    let solutions = solve(&mut x, &y, &mut vec![], limit);
+
+    solutions
+        .iter()
+        .map(|solution| {
+            let mut multigrid = multigrid.clone();
+            for (id, n) in solution {
+                multigrid.set_id(*id, *n).unwrap();
+            }
+            multigrid
+        })
+        .collect()
+}
+
+/// Generates a layout wide puzzle with an unique solution, where `givens` counts shared cells once. Gives up with
+/// `None` after `attempts` solved multigrids couldn't be carved down to `givens`, which is also the case when the
+/// layout has no solution at all, and right away when there are fewer cells than givens.
+#[cfg(feature = "generator")]
+pub fn try_generate(layout: &Layout, givens: usize, attempts: usize) -> Option<MultiGrid> {
+    if givens > layout.cells() {
+        return None;
+    }
+    let mut rng = thread_rng();
+    for _ in 0..attempts {
+        let mut multigrid = MultiGrid::new(layout.clone());
+        let seed = seed_grid();
+        for cell in CellIndex::all() {
+            multigrid.set_unchecked(0, cell, Digit::new(seed.get(cell))).unwrap();
+        }
+        let Some(solved) = alx_solve(&multigrid, 1).pop() else {
+            continue;
+        };
+        multigrid = solved;
+        let mut not_removed = (0..layout.cells()).collect::<Set<usize>>();
+        let mut stuck = false;
+        'outer: while not_removed.len() > givens {
+            let mut tried = Set::new();
+            loop {
+                let id = *not_removed.iter().choose(&mut rng).unwrap();
+                let old_value = multigrid.get_id(id);
+                multigrid.set_id(id, 0).unwrap();
+                if alx_solve(&multigrid, 2).len() == 1 {
+                    not_removed.remove(&id);
+                    break;
+                } else {
+                    multigrid.set_id(id, old_value).unwrap();
+                    tried.insert(id);
+                    if tried == not_removed {
+                        stuck = true;
+                        break 'outer;
+                    }
+                }
+            }
+        }
+        if stuck {
+            continue;
+        }
+        for id in 0..layout.cells() {
+            let (grid, cell) = layout.locations(id)[0];
+            if not_removed.contains(&id) {
+                multigrid.freeze(grid, cell);
+            } else {
+                multigrid.set_checked(grid, cell, None).unwrap();
+            }
+        }
+        return Some(multigrid);
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::{
+        borrow::ToOwned,
+        string::{String, ToString},
+    };
+
+    fn cell(notation: &str) -> CellIndex {
+        notation.parse().unwrap()
+    }
+
+    const SOLUTION: &str = "534678912672195348198342567859761423426853791713924856961537284287419635345286179";
+
+    // Starting from a filled centre grid keeps the search away from the slow paths of an empty samurai
+    fn solved() -> MultiGrid {
+        let mut multigrid = MultiGrid::new(Layout::samurai());
+        for (cell, value) in CellIndex::all().zip(SOLUTION.bytes()) {
+            multigrid.set_checked(2, cell, Digit::new(value - b'0')).unwrap();
+        }
+        alx_solve(&multigrid, 1).remove(0)
+    }
+
+    #[test]
+    fn samurai_layout() {
+        let layout = Layout::samurai();
+        assert_eq!(layout.grids(), 5);
+        assert_eq!(layout.cells(), 369);
+        assert_eq!(layout.id(0, cell("r7c7")), layout.id(2, cell("r1c1")));
+        assert_eq!(
+            layout.locations(layout.id(2, cell("r7c7"))),
+            &[(2, cell("r7c7")), (4, cell("r1c1"))]
+        );
+    }
+
+    #[test]
+    fn layout_errors() {
+        assert_eq!(Layout::new(vec![]), Err(LayoutError::Empty));
+        assert_eq!(Layout::new(vec![(0, 0), (4, 6)]), Err(LayoutError::Unaligned(4, 6)));
+        assert_eq!(Layout::new(vec![(0, 0), (0, 0)]), Err(LayoutError::Duplicate(0, 0)));
+    }
+
+    #[test]
+    fn shared_set() {
+        let mut multigrid = MultiGrid::new(Layout::samurai());
+        assert_eq!(multigrid.set_checked(0, cell("r7c7"), Digit::new(5)), Ok(()));
+        assert_eq!(multigrid.get(2, cell("r1c1")), 5);
+        assert!(!multigrid.grid(2).candidates(cell("r1c2")).get(4));
+        assert_eq!(
+            multigrid.set_checked(2, cell("r1c2"), Digit::new(5)),
+            Err(SetError::Conflict { with: cell("r7c7") })
+        );
+        assert_eq!(multigrid.get(0, cell("r7c8")), 0);
+    }
+
+    #[test]
+    fn samurai_solve() {
+        let solution = solved();
+        assert!(solution.is_complete());
+        assert!(solution.is_valid());
+    }
+
+    #[test]
+    fn samurai_parse() {
+        let solution = solved();
+        let multigrid = MultiGrid::parse(Layout::samurai(), &solution.to_string()).unwrap();
+        assert_eq!(multigrid, solution);
+        assert_eq!(multigrid.givens(), 369);
+
+        let mut lines = solution.to_string().lines().map(str::to_owned).collect::<Vec<String>>();
+        let result = MultiGrid::parse(Layout::samurai(), &lines[..4].join("\n"));
+        assert_eq!(
+            result.err().unwrap().downcast::<MultiGridError>().unwrap().as_ref(),
+            &MultiGridError::LineCount(5, 4)
+        );
+        lines[2].replace_range(0..1, "0");
+        let result = MultiGrid::parse(Layout::samurai(), &lines.join("\n"));
+        assert_eq!(
+            result.err().unwrap().downcast::<MultiGridError>().unwrap().as_ref(),
+            &MultiGridError::Mismatch(2, cell("r1c1"))
+        );
+    }
+
+    #[cfg(feature = "generator")]
+    #[test]
+    fn samurai_generate() {
+        assert!(try_generate(&Layout::samurai(), 370, 1).is_none());
+        let multigrid = try_generate(&Layout::samurai(), 200, 10).unwrap();
+        assert_eq!(multigrid.givens(), 200);
+        assert_eq!(alx_solve(&multigrid, 2).len(), 1);
+    }
+}
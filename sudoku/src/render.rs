@@ -0,0 +1,508 @@
+use alloc::{format, string::String, vec::Vec};
+use core::fmt::Write;
+
+use super::coordinates::CellIndex;
+use super::grid::Grid;
+
+/// Width and height of a grid rendered on its own.
+pub const SIZE: f32 = 450.0;
+
+/// Decorations of sudoku variants drawn over the grid.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Marking {
+    /// The two main diagonals of an X-sudoku
+    Diagonals,
+    /// Shaded cells, like the extra regions of a windoku
+    Shade(Vec<CellIndex>),
+    /// A dashed outline around the cells of a killer cage, with its sum in the top left corner
+    Cage { cells: Vec<CellIndex>, sum: Option<u16> },
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Style {
+    /// Draws the candidates of empty cells
+    pub candidates: bool,
+    pub markings: Vec<Marking>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Anchor {
+    Start,
+    Middle,
+}
+
+/// Colors are shades of gray from black at 0 to white at 1, which is all printing needs.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Shape {
+    Line {
+        from: (f32, f32),
+        to: (f32, f32),
+        width: f32,
+        gray: f32,
+        dashed: bool,
+    },
+    Rect {
+        x: f32,
+        y: f32,
+        width: f32,
+        height: f32,
+        gray: f32,
+    },
+    /// Text in Helvetica with its baseline at `y`
+    Text {
+        x: f32,
+        y: f32,
+        size: f32,
+        text: String,
+        anchor: Anchor,
+        bold: bool,
+        gray: f32,
+    },
+}
+
+impl Shape {
+    fn place(&self, x: f32, y: f32, scale: f32) -> Shape {
+        let point = |(px, py): (f32, f32)| (x + px * scale, y + py * scale);
+        match self {
+            Shape::Line {
+                from,
+                to,
+                width,
+                gray,
+                dashed,
+            } => Shape::Line {
+                from: point(*from),
+                to: point(*to),
+                width: width * scale,
+                gray: *gray,
+                dashed: *dashed,
+            },
+            Shape::Rect {
+                x: rx,
+                y: ry,
+                width,
+                height,
+                gray,
+            } => Shape::Rect {
+                x: x + rx * scale,
+                y: y + ry * scale,
+                width: width * scale,
+                height: height * scale,
+                gray: *gray,
+            },
+            Shape::Text {
+                x: tx,
+                y: ty,
+                size,
+                text,
+                anchor,
+                bold,
+                gray,
+            } => Shape::Text {
+                x: x + tx * scale,
+                y: y + ty * scale,
+                size: size * scale,
+                text: text.clone(),
+                anchor: *anchor,
+                bold: *bold,
+                gray: *gray,
+            },
+        }
+    }
+}
+
+/// Shapes on a page with the origin at the top left and y growing downwards, like in SVG. Keeping the shapes around
+/// instead of writing SVG right away lets other formats, like PDF, draw the same picture.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Drawing {
+    pub width: f32,
+    pub height: f32,
+    pub shapes: Vec<Shape>,
+}
+
+impl Drawing {
+    pub fn new(width: f32, height: f32) -> Drawing {
+        Drawing {
+            width,
+            height,
+            shapes: Vec::new(),
+        }
+    }
+
+    /// Adds the shapes of another drawing scaled by `scale`, with its origin at (x, y).
+    pub fn place(&mut self, other: &Drawing, x: f32, y: f32, scale: f32) {
+        self.shapes
+            .extend(other.shapes.iter().map(|shape| shape.place(x, y, scale)));
+    }
+
+    pub fn text(&mut self, x: f32, y: f32, size: f32, text: &str, bold: bool) {
+        self.shapes.push(Shape::Text {
+            x,
+            y,
+            size,
+            text: text.into(),
+            anchor: Anchor::Start,
+            bold,
+            gray: 0.0,
+        });
+    }
+
+    pub fn to_svg(&self) -> String {
+        let mut svg = format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{0}\" height=\"{1}\" viewBox=\"0 0 {0} {1}\">\n\
+             <rect width=\"100%\" height=\"100%\" fill=\"#ffffff\"/>\n",
+            number(self.width),
+            number(self.height)
+        );
+        for shape in &self.shapes {
+            match shape {
+                Shape::Line {
+                    from,
+                    to,
+                    width,
+                    gray,
+                    dashed,
+                } => {
+                    write!(
+                        svg,
+                        "<line x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\" stroke=\"{}\" stroke-width=\"{}\" \
+                         stroke-linecap=\"square\"",
+                        number(from.0),
+                        number(from.1),
+                        number(to.0),
+                        number(to.1),
+                        color(*gray),
+                        number(*width)
+                    )
+                    .unwrap();
+                    if *dashed {
+                        write!(svg, " stroke-dasharray=\"{0} {0}\"", number(width * 3.0)).unwrap();
+                    }
+                    svg.push_str("/>\n");
+                }
+                Shape::Rect {
+                    x,
+                    y,
+                    width,
+                    height,
+                    gray,
+                } => writeln!(
+                    svg,
+                    "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"{}\"/>",
+                    number(*x),
+                    number(*y),
+                    number(*width),
+                    number(*height),
+                    color(*gray)
+                )
+                .unwrap(),
+                Shape::Text {
+                    x,
+                    y,
+                    size,
+                    text,
+                    anchor,
+                    bold,
+                    gray,
+                } => writeln!(
+                    svg,
+                    "<text x=\"{}\" y=\"{}\" font-family=\"Helvetica, Arial, sans-serif\" font-size=\"{}\"{}{} \
+                     fill=\"{}\">{}</text>",
+                    number(*x),
+                    number(*y),
+                    number(*size),
+                    if *anchor == Anchor::Middle {
+                        " text-anchor=\"middle\""
+                    } else {
+                        ""
+                    },
+                    if *bold { " font-weight=\"bold\"" } else { "" },
+                    color(*gray),
+                    escape(text)
+                )
+                .unwrap(),
+            }
+        }
+        svg.push_str("</svg>\n");
+        svg
+    }
+}
+
+/// Writes coordinates with at most two decimals, which is plenty for print and keeps the files small.
+fn number(value: f32) -> String {
+    let mut string = format!("{:.2}", value);
+    while string.ends_with('0') {
+        string.pop();
+    }
+    if string.ends_with('.') {
+        string.pop();
+    }
+    if string == "-0" {
+        string.remove(0);
+    }
+    string
+}
+
+fn color(gray: f32) -> String {
+    let level = (gray.clamp(0.0, 1.0) * 255.0 + 0.5) as u8;
+    format!("#{0:02x}{0:02x}{0:02x}", level)
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for character in text.chars() {
+        match character {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            _ => escaped.push(character),
+        }
+    }
+    escaped
+}
+
+fn line(drawing: &mut Drawing, from: (f32, f32), to: (f32, f32), width: f32, gray: f32, dashed: bool) {
+    drawing.shapes.push(Shape::Line {
+        from,
+        to,
+        width,
+        gray,
+        dashed,
+    });
+}
+
+/// Draws the sides of the cage's cells that don't border another cell of the cage, inset from the grid lines.
+fn cage(drawing: &mut Drawing, cells: &[CellIndex], sum: Option<u16>, cell: f32) {
+    let inset = cell * 0.08;
+    let width = cell / 40.0;
+    let inside = |row: usize, column: usize, dr: isize, dc: isize| {
+        let (row, column) = (row as isize + dr, column as isize + dc);
+        (0..9).contains(&row)
+            && (0..9).contains(&column)
+            && cells.contains(&CellIndex::from_coords(row as usize, column as usize).unwrap())
+    };
+    for index in cells {
+        let (row, column) = (index.row(), index.column());
+        let (left, top) = (column as f32 * cell, row as f32 * cell);
+        let (right, bottom) = (left + cell, top + cell);
+        // Sides run all the way to the neighbouring cell when it's part of the cage, so the outline stays connected
+        let start_x = if inside(row, column, 0, -1) { left } else { left + inset };
+        let end_x = if inside(row, column, 0, 1) {
+            right
+        } else {
+            right - inset
+        };
+        let start_y = if inside(row, column, -1, 0) { top } else { top + inset };
+        let end_y = if inside(row, column, 1, 0) {
+            bottom
+        } else {
+            bottom - inset
+        };
+        if !inside(row, column, -1, 0) {
+            line(drawing, (start_x, top + inset), (end_x, top + inset), width, 0.0, true);
+        }
+        if !inside(row, column, 1, 0) {
+            line(
+                drawing,
+                (start_x, bottom - inset),
+                (end_x, bottom - inset),
+                width,
+                0.0,
+                true,
+            );
+        }
+        if !inside(row, column, 0, -1) {
+            line(
+                drawing,
+                (left + inset, start_y),
+                (left + inset, end_y),
+                width,
+                0.0,
+                true,
+            );
+        }
+        if !inside(row, column, 0, 1) {
+            line(
+                drawing,
+                (right - inset, start_y),
+                (right - inset, end_y),
+                width,
+                0.0,
+                true,
+            );
+        }
+    }
+    if let (Some(sum), Some(first)) = (sum, cells.iter().min()) {
+        let size = cell * 0.22;
+        let text = format!("{}", sum);
+        let (x, y) = (first.column() as f32 * cell + inset, first.row() as f32 * cell + inset);
+        // The sum covers the outline in its corner
+        drawing.shapes.push(Shape::Rect {
+            x,
+            y,
+            width: size * 0.6 * text.len() as f32,
+            height: size,
+            gray: 1.0,
+        });
+        drawing.text(x + size * 0.05, y + size * 0.85, size, &text, false);
+    }
+}
+
+/// Draws the grid as a square with sides of `size`. Givens are bold, other values gray, and with `style.candidates`
+/// the candidates of empty cells are laid out like on a phone keypad.
+pub fn draw(grid: &Grid, size: f32, style: &Style) -> Drawing {
+    let mut drawing = Drawing::new(size, size);
+    let cell = size / 9.0;
+    for marking in &style.markings {
+        if let Marking::Shade(cells) = marking {
+            for index in cells {
+                drawing.shapes.push(Shape::Rect {
+                    x: index.column() as f32 * cell,
+                    y: index.row() as f32 * cell,
+                    width: cell,
+                    height: cell,
+                    gray: 0.85,
+                });
+            }
+        }
+    }
+    if style.markings.contains(&Marking::Diagonals) {
+        line(&mut drawing, (0.0, 0.0), (size, size), size / 300.0, 0.6, false);
+        line(&mut drawing, (size, 0.0), (0.0, size), size / 300.0, 0.6, false);
+    }
+    for index in CellIndex::all() {
+        let (x, y) = (index.column() as f32 * cell, index.row() as f32 * cell);
+        match grid.get(index) {
+            0 if style.candidates => {
+                let font = cell * 0.25;
+                for candidate in grid.candidates(index).iter() {
+                    let (row, column) = ((candidate - 1) / 3, (candidate - 1) % 3);
+                    drawing.shapes.push(Shape::Text {
+                        x: x + cell * (column as f32 + 0.5) / 3.0,
+                        y: y + cell * (row as f32 + 0.5) / 3.0 + font * 0.35,
+                        size: font,
+                        text: format!("{}", candidate),
+                        anchor: Anchor::Middle,
+                        bold: false,
+                        gray: 0.4,
+                    });
+                }
+            }
+            0 => {}
+            value => {
+                let font = cell * 0.65;
+                let given = grid.frozen(index);
+                drawing.shapes.push(Shape::Text {
+                    x: x + cell / 2.0,
+                    y: y + cell / 2.0 + font * 0.36,
+                    size: font,
+                    text: format!("{}", value),
+                    anchor: Anchor::Middle,
+                    bold: given,
+                    gray: if given { 0.0 } else { 0.35 },
+                });
+            }
+        }
+    }
+    for line_index in 0..=9 {
+        if line_index % 3 != 0 {
+            let at = line_index as f32 * cell;
+            line(&mut drawing, (at, 0.0), (at, size), size / 450.0, 0.0, false);
+            line(&mut drawing, (0.0, at), (size, at), size / 450.0, 0.0, false);
+        }
+    }
+    for marking in &style.markings {
+        if let Marking::Cage { cells, sum } = marking {
+            cage(&mut drawing, cells, *sum, cell);
+        }
+    }
+    for line_index in (0..=9).step_by(3) {
+        let at = line_index as f32 * cell;
+        line(&mut drawing, (at, 0.0), (at, size), size / 150.0, 0.0, false);
+        line(&mut drawing, (0.0, at), (size, at), size / 150.0, 0.0, false);
+    }
+    drawing
+}
+
+/// Renders the grid on its own as an SVG document.
+pub fn to_svg(grid: &Grid, style: &Style) -> String {
+    draw(grid, SIZE, style).to_svg()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    const SUDOKU: &str = "060000300400700000000000080000008012500600000000000050082000700000500600000010000";
+
+    fn cell(index: usize) -> CellIndex {
+        CellIndex::new(index).unwrap()
+    }
+
+    #[test]
+    fn svg() {
+        let grid: Grid = SUDOKU.parse().unwrap();
+        let svg = to_svg(&grid, &Style::default());
+        assert!(svg.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"450\" height=\"450\""));
+        assert!(svg.ends_with("</svg>\n"));
+        assert_eq!(svg.matches("<text").count(), grid.givens());
+        assert_eq!(svg.matches("font-weight=\"bold\"").count(), grid.givens());
+        assert_eq!(svg.matches("<line").count(), 20);
+        assert!(svg.contains(">6</text>"));
+    }
+
+    #[test]
+    fn candidates_and_markings() {
+        let mut grid: Grid = SUDOKU.parse().unwrap();
+        grid.recompute_all_candidates();
+        let candidates = CellIndex::all()
+            .filter(|index| grid.get(*index) == 0)
+            .map(|index| grid.candidates(index).count())
+            .sum::<usize>();
+        let style = Style {
+            candidates: true,
+            markings: vec![
+                Marking::Diagonals,
+                Marking::Shade(vec![cell(10), cell(11)]),
+                Marking::Cage {
+                    cells: vec![cell(0), cell(1), cell(9)],
+                    sum: Some(15),
+                },
+            ],
+        };
+        let drawing = draw(&grid, SIZE, &style);
+        let texts = drawing
+            .shapes
+            .iter()
+            .filter(|shape| matches!(shape, Shape::Text { .. }))
+            .count();
+        assert_eq!(texts, grid.givens() + candidates + 1);
+        let dashed = drawing
+            .shapes
+            .iter()
+            .filter(|shape| matches!(shape, Shape::Line { dashed: true, .. }))
+            .count();
+        // An L of three cells has eight cell sides on its outline
+        assert_eq!(dashed, 8);
+        assert!(drawing.to_svg().contains("stroke-dasharray"));
+    }
+
+    #[test]
+    fn place() {
+        let grid: Grid = SUDOKU.parse().unwrap();
+        let mut page = Drawing::new(1000.0, 1000.0);
+        page.place(&draw(&grid, 90.0, &Style::default()), 100.0, 200.0, 2.0);
+        assert!(page.shapes.contains(&Shape::Line {
+            from: (100.0, 200.0),
+            to: (100.0, 380.0),
+            width: 1.2,
+            gray: 0.0,
+            dashed: false,
+        }));
+        assert_eq!(number(1.0 / 3.0), "0.33");
+        assert_eq!(number(-0.001), "0");
+        assert_eq!(escape("a < b & \"c\""), "a &lt; b &amp; &quot;c&quot;");
+    }
+}
@@ -0,0 +1,188 @@
+use alloc::{format, string::String, vec::Vec};
+use serde::de::{self, Deserializer};
+use serde::ser::Serializer;
+use serde::{Deserialize, Serialize};
+
+use super::candidates::Candidates;
+use super::coordinates::{CellIndex, Digit};
+use super::grid::Grid;
+
+#[derive(Serialize, Deserialize)]
+struct Cell {
+    value: u8,
+    candidates: Candidates,
+    frozen: bool,
+}
+
+fn build<E: de::Error>(cells: impl ExactSizeIterator<Item = Cell>) -> Result<Grid, E> {
+    if cells.len() != 81 {
+        return Err(E::invalid_length(cells.len(), &"81 cells"));
+    }
+    let mut grid = Grid::new();
+    for (index, cell) in CellIndex::all().zip(cells) {
+        if cell.value > 9 {
+            return Err(E::custom(format!("invalid value {} in {}", cell.value, index)));
+        }
+        grid.set_unchecked(index, Digit::new(cell.value)).unwrap();
+        *grid.candidates_mut(index) = cell.candidates;
+        if cell.frozen {
+            grid.freeze(index);
+        }
+    }
+    Ok(grid)
+}
+
+/// Serializes a grid as a list of 81 cells with their value, candidates and frozen flag.
+impl Serialize for Grid {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(CellIndex::all().map(|index| Cell {
+            value: self.get(index),
+            candidates: *self.candidates(index),
+            frozen: self.frozen(index),
+        }))
+    }
+}
+
+impl<'de> Deserialize<'de> for Grid {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        build(Vec::<Cell>::deserialize(deserializer)?.into_iter())
+    }
+}
+
+/// Serializes a grid as a single string for use with `#[serde(with = "rustdoku_sudoku::serialization::compact")]`.
+///
+/// The string has three `:` separated parts: the 81 values, 81 frozen flags as `0` or `1`, and the candidates of every
+/// cell as three hex digits.
+pub mod compact {
+    use super::*;
+    use itertools::Itertools;
+
+    pub fn to_string(grid: &Grid) -> String {
+        let frozen = CellIndex::all()
+            .map(|index| if grid.frozen(index) { '1' } else { '0' })
+            .join("");
+        let candidates = CellIndex::all()
+            .map(|index| format!("{:03x}", grid.candidates(index).value()))
+            .join("");
+        format!("{}:{}:{}", grid, frozen, candidates)
+    }
+
+    pub fn from_str<E: de::Error>(string: &str) -> Result<Grid, E> {
+        let (values, frozen, candidates) = string
+            .split(':')
+            .collect_tuple()
+            .ok_or_else(|| E::custom("expected values, frozen flags and candidates separated by ':'"))?;
+        if !values.is_ascii() || !frozen.is_ascii() || !candidates.is_ascii() {
+            return Err(E::custom("unexpected non-ascii character"));
+        }
+        if values.len() != 81 || frozen.len() != 81 || candidates.len() != 243 {
+            return Err(E::invalid_length(
+                string.len(),
+                &"81 values, 81 frozen flags and 243 candidate digits",
+            ));
+        }
+        let cells = values
+            .chars()
+            .zip(frozen.chars())
+            .zip(candidates.as_bytes().chunks(3))
+            .zip(CellIndex::all())
+            .map(|(((value, frozen), candidates), index)| {
+                let invalid = || E::custom(format!("invalid cell {}", index));
+                if !candidates.iter().all(u8::is_ascii_hexdigit) {
+                    return Err(invalid());
+                }
+                let bits = u16::from_str_radix(core::str::from_utf8(candidates).unwrap(), 16).map_err(|_| invalid())?;
+                if bits > 511 {
+                    return Err(invalid());
+                }
+                let mut cell = Candidates::new(false);
+                Digit::all()
+                    .filter(|digit| bits & (1 << digit.candidate()) > 0)
+                    .for_each(|digit| cell.set_digit(digit));
+                Ok(Cell {
+                    value: value.to_digit(10).ok_or_else(invalid)? as u8,
+                    candidates: cell,
+                    frozen: match frozen {
+                        '0' => false,
+                        '1' => true,
+                        _ => return Err(invalid()),
+                    },
+                })
+            })
+            .collect::<Result<Vec<Cell>, E>>()?;
+        build(cells.into_iter())
+    }
+
+    pub fn serialize<S: Serializer>(grid: &Grid, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&to_string(grid))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Grid, D::Error> {
+        from_str(&String::deserialize(deserializer)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::grid::ParseError;
+
+    #[derive(Serialize, Deserialize)]
+    struct Game {
+        #[serde(with = "compact")]
+        grid: Grid,
+    }
+
+    fn grid() -> Grid {
+        let mut grid: Grid = "060000300400700000000000080000008012500600000000000050082000700000500600000010000"
+            .parse()
+            .unwrap();
+        grid.set_checked(CellIndex::at(0), Digit::new(9)).unwrap();
+        grid.candidates_mut(CellIndex::at(2)).unset(0);
+        grid
+    }
+
+    fn assert_same(a: &Grid, b: &Grid) {
+        for index in CellIndex::all() {
+            assert_eq!(a.get(index), b.get(index));
+            assert_eq!(a.frozen(index), b.frozen(index));
+            assert_eq!(a.candidates(index).value(), b.candidates(index).value());
+        }
+    }
+
+    #[test]
+    fn structured() {
+        let grid = grid();
+        let json = serde_json::to_string(&grid).unwrap();
+        assert!(json.starts_with(r#"[{"value":9,"candidates":0,"frozen":false},{"value":6,"#));
+        assert_same(&serde_json::from_str(&json).unwrap(), &grid);
+        assert!(serde_json::from_str::<Grid>(r#"[{"value":9,"candidates":0,"frozen":false}]"#).is_err());
+        let json = json.replacen(r#""candidates":0"#, r#""candidates":512"#, 1);
+        assert!(serde_json::from_str::<Grid>(&json).is_err());
+    }
+
+    #[test]
+    fn compact() {
+        let grid = grid();
+        let json = serde_json::to_string(&Game { grid }).unwrap();
+        assert!(json.starts_with(r#"{"grid":"960000300"#));
+        assert_eq!(json.len(), 9 + 81 + 1 + 81 + 1 + 243 + 2);
+        assert_same(&serde_json::from_str::<Game>(&json).unwrap().grid, &grid);
+        assert!(serde_json::from_str::<Game>(r#"{"grid":"960000300"}"#).is_err());
+        let string = compact::to_string(&grid);
+        let (head, tail) = string.split_at(string.len() - 3);
+        assert!(tail.bytes().all(|byte| byte.is_ascii_hexdigit()));
+        assert!(compact::from_str::<serde_json::Error>(&format!("{}+1f", head)).is_err());
+        assert!(compact::from_str::<serde_json::Error>(&format!("{}1ff", head)).is_ok());
+    }
+
+    #[test]
+    fn parse_error() {
+        let json = serde_json::to_string(&ParseError::InvalidDigit('a', 0)).unwrap();
+        assert_eq!(json, r#"{"InvalidDigit":["a",0]}"#);
+        assert_eq!(
+            serde_json::from_str::<ParseError>(&json).unwrap(),
+            ParseError::InvalidDigit('a', 0)
+        );
+    }
+}
@@ -0,0 +1,524 @@
+use alloc::{borrow::ToOwned, boxed::Box, string::String, vec, vec::Vec};
+use core::{error::Error, fmt, str::FromStr};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use super::coordinates::{CellIndex, Digit};
+use super::grid::{Grid, SetError};
+
+/// A single player action.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Move {
+    /// Places a digit, or clears the cell when `value` is `None`
+    Set {
+        cell: CellIndex,
+        value: Option<Digit>,
+        checked: bool,
+    },
+    ToggleCandidate {
+        cell: CellIndex,
+        digit: Digit,
+    },
+    /// Fills in the candidates of every empty cell
+    FillCandidates,
+}
+
+#[derive(PartialEq, Eq, Error, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum SessionError {
+    #[error("Invalid move '{1}' on line {0}")]
+    InvalidMove(usize, String),
+    #[error("Move '{1}' on line {0} can't be played: {2}")]
+    RejectedMove(usize, String, SetError),
+}
+
+/// A move that doesn't follow the notation printed by `Display`.
+#[derive(PartialEq, Eq, Error, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[error("Invalid move '{0}'")]
+pub struct ParseMoveError(pub String);
+
+impl Move {
+    /// Plays the move on the grid, which is left untouched when the move is rejected.
+    fn apply(&self, grid: &mut Grid) -> Result<(), SetError> {
+        match *self {
+            Move::Set {
+                cell,
+                value,
+                checked: true,
+            } => grid.set_checked(cell, value),
+            Move::Set {
+                cell,
+                value,
+                checked: false,
+            } => grid.set_unchecked(cell, value),
+            Move::ToggleCandidate { cell, digit } => {
+                if grid.frozen(cell) {
+                    return Err(SetError::Frozen);
+                }
+                grid.candidates_mut(cell).toggle_digit(digit);
+                Ok(())
+            }
+            Move::FillCandidates => {
+                grid.recompute_all_candidates();
+                Ok(())
+            }
+        }
+    }
+}
+
+impl fmt::Display for Move {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Move::Set { cell, value, checked } => write!(
+                f,
+                "{} {} {}",
+                if *checked { "set-checked" } else { "set" },
+                cell,
+                value.map_or(0, Digit::get)
+            ),
+            Move::ToggleCandidate { cell, digit } => write!(f, "toggle {} {}", cell, digit),
+            Move::FillCandidates => write!(f, "fill"),
+        }
+    }
+}
+
+/// Parses the moves printed by `Display`, such as `set r2c4 5`, `set-checked r2c4 5`, `toggle r2c4 3` or `fill`. A
+/// value of 0 clears the cell.
+impl FromStr for Move {
+    type Err = ParseMoveError;
+
+    fn from_str(string: &str) -> Result<Self, Self::Err> {
+        let invalid = || ParseMoveError(string.to_owned());
+        let parts = string.split_whitespace().collect::<Vec<&str>>();
+        let cell = |part: &str| part.parse::<CellIndex>().map_err(|_| invalid());
+        let digit = |part: &str| part.parse::<Digit>().map_err(|_| invalid());
+        let mv = match parts.as_slice() {
+            [name @ ("set" | "set-checked"), cell_part, value] => Move::Set {
+                cell: cell(cell_part)?,
+                value: if *value == "0" { None } else { Some(digit(value)?) },
+                checked: *name == "set-checked",
+            },
+            ["toggle", cell_part, digit_part] => Move::ToggleCandidate {
+                cell: cell(cell_part)?,
+                digit: digit(digit_part)?,
+            },
+            ["fill"] => Move::FillCandidates,
+            _ => return Err(invalid()),
+        };
+        Ok(mv)
+    }
+}
+
+/// A line of play: the moves played from the puzzle, of which the ones from `position` on were undone.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Branch {
+    moves: Vec<Move>,
+    position: usize,
+}
+
+/// How many moves apart the grids kept for undo are.
+const SNAPSHOT_INTERVAL: usize = 32;
+
+/// A game in progress: the puzzle and the moves played on it, with unlimited undo and redo.
+///
+/// Every `SNAPSHOT_INTERVAL` moves the grid is kept, so undoing replays at most that many moves instead of the whole
+/// game.
+///
+/// Checkpoints remember the moves played so far. Restoring one returns to that point, so a guess can be explored and
+/// abandoned without losing the position before it. The abandoned position becomes a checkpoint of its own, so every
+/// branch explored stays reachable.
+#[derive(Clone, Debug)]
+pub struct Session {
+    initial: Grid,
+    grid: Grid,
+    moves: Vec<Move>,
+    position: usize,
+    /// The grid after every `SNAPSHOT_INTERVAL` moves, starting with the puzzle.
+    snapshots: Vec<Grid>,
+    checkpoints: Vec<Branch>,
+}
+
+impl Session {
+    pub fn new(puzzle: Grid) -> Self {
+        Session {
+            initial: puzzle,
+            grid: puzzle,
+            moves: vec![],
+            position: 0,
+            snapshots: vec![puzzle],
+            checkpoints: vec![],
+        }
+    }
+
+    pub fn initial(&self) -> &Grid {
+        &self.initial
+    }
+
+    pub fn grid(&self) -> &Grid {
+        &self.grid
+    }
+
+    /// The moves leading to the current grid, without the undone ones.
+    pub fn moves(&self) -> &[Move] {
+        &self.moves[..self.position]
+    }
+
+    /// Plays a move, discarding any undone moves. Rejected moves aren't recorded and leave the grid untouched.
+    pub fn play(&mut self, mv: Move) -> Result<(), SetError> {
+        mv.apply(&mut self.grid)?;
+        self.moves.truncate(self.position);
+        self.snapshots.truncate(self.position / SNAPSHOT_INTERVAL + 1);
+        self.moves.push(mv);
+        self.position += 1;
+        self.snapshot(self.position);
+        Ok(())
+    }
+
+    pub fn set(&mut self, cell: CellIndex, value: Option<Digit>, checked: bool) -> Result<(), SetError> {
+        self.play(Move::Set { cell, value, checked })
+    }
+
+    pub fn toggle_candidate(&mut self, cell: CellIndex, digit: Digit) -> Result<(), SetError> {
+        self.play(Move::ToggleCandidate { cell, digit })
+    }
+
+    pub fn fill_candidates(&mut self) {
+        self.play(Move::FillCandidates)
+            .expect("Filling in candidates can't fail");
+    }
+
+    pub fn can_undo(&self) -> bool {
+        self.position > 0
+    }
+
+    pub fn can_redo(&self) -> bool {
+        self.position < self.moves.len()
+    }
+
+    pub fn undo(&mut self) -> bool {
+        if !self.can_undo() {
+            return false;
+        }
+        self.position -= 1;
+        self.replay();
+        true
+    }
+
+    pub fn redo(&mut self) -> bool {
+        if !self.can_redo() {
+            return false;
+        }
+        self.moves[self.position]
+            .apply(&mut self.grid)
+            .expect("Recorded moves can be replayed");
+        self.position += 1;
+        self.snapshot(self.position);
+        true
+    }
+
+    /// Remembers the current position, along with the moves that can be redone from it, and returns its id for
+    /// `restore`.
+    pub fn checkpoint(&mut self) -> usize {
+        self.checkpoints.push(self.branch());
+        self.checkpoints.len() - 1
+    }
+
+    /// Returns to a checkpoint, or `None` when there's no such checkpoint. The position being left is kept as a
+    /// checkpoint too, unless it already is one, and its id is returned so that branch can be restored later.
+    pub fn restore(&mut self, checkpoint: usize) -> Option<usize> {
+        let branch = self.checkpoints.get(checkpoint)?.clone();
+        let current = self.branch();
+        let left = match self.checkpoints.iter().position(|checkpoint| *checkpoint == current) {
+            Some(id) => id,
+            None => {
+                self.checkpoints.push(current);
+                self.checkpoints.len() - 1
+            }
+        };
+        self.moves = branch.moves;
+        self.position = branch.position;
+        self.snapshots.truncate(1);
+        self.replay();
+        Some(left)
+    }
+
+    /// How many checkpoints were made, including the positions left by `restore`.
+    pub fn checkpoints(&self) -> usize {
+        self.checkpoints.len()
+    }
+
+    fn branch(&self) -> Branch {
+        Branch {
+            moves: self.moves.clone(),
+            position: self.position,
+        }
+    }
+
+    /// Rebuilds the grid from the last snapshot at or before the current position.
+    fn replay(&mut self) {
+        let snapshot = (self.position / SNAPSHOT_INTERVAL).min(self.snapshots.len() - 1);
+        self.grid = self.snapshots[snapshot];
+        for played in snapshot * SNAPSHOT_INTERVAL + 1..=self.position {
+            self.moves[played - 1]
+                .apply(&mut self.grid)
+                .expect("Recorded moves can be replayed");
+            self.snapshot(played);
+        }
+    }
+
+    /// Keeps the grid reached after `played` moves when that ends an interval without a snapshot yet.
+    fn snapshot(&mut self, played: usize) {
+        if played.is_multiple_of(SNAPSHOT_INTERVAL) && self.snapshots.len() == played / SNAPSHOT_INTERVAL {
+            self.snapshots.push(self.grid);
+        }
+    }
+}
+
+/// Writes the moves of a branch, with the undone ones after a `redo` line.
+fn write_branch(f: &mut fmt::Formatter, branch: &Branch) -> fmt::Result {
+    for (index, mv) in branch.moves.iter().enumerate() {
+        if index == branch.position {
+            writeln!(f, "redo")?;
+        }
+        writeln!(f, "{}", mv)?;
+    }
+    Ok(())
+}
+
+/// Writes the move log: the puzzle as a candidate string with content flags, which keeps its candidates and tells the
+/// givens from the other values, followed by one move per line. The moves that can be redone follow a `redo` line, and each
+/// checkpoint follows as a `checkpoint` line and its own moves.
+impl fmt::Display for Session {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{}", self.initial.to_candidate_content_string())?;
+        write_branch(f, &self.branch())?;
+        for checkpoint in &self.checkpoints {
+            writeln!(f, "checkpoint")?;
+            write_branch(f, checkpoint)?;
+        }
+        Ok(())
+    }
+}
+
+/// Replays a move log written by `Display`. The puzzle can also be a plain sudoku or a candidate string without
+/// flags, whose filled cells are read back as givens.
+impl FromStr for Session {
+    type Err = Box<dyn Error + Sync + Send>;
+
+    fn from_str(string: &str) -> Result<Self, Self::Err> {
+        let mut lines = string
+            .lines()
+            .enumerate()
+            .map(|(line, text)| (line + 1, text.trim()))
+            .filter(|(_, text)| !text.is_empty());
+        let puzzle = lines.next().map(|(_, text)| text).unwrap_or_default();
+        let initial = if puzzle.contains(':') {
+            Grid::from_candidate_content_string(puzzle)?
+        } else if puzzle.len() >= 729 {
+            Grid::from_candidate_string(puzzle)?
+        } else {
+            puzzle.parse()?
+        };
+        let mut branches = vec![vec![]];
+        for (line, text) in lines {
+            match text {
+                "checkpoint" => branches.push(vec![]),
+                _ => branches.last_mut().unwrap().push((line, text)),
+            }
+        }
+        let mut branches = branches
+            .into_iter()
+            .map(|lines| replay(initial, &lines))
+            .collect::<Result<Vec<Branch>, SessionError>>()?
+            .into_iter();
+        let mut session = Session::new(initial);
+        let current = branches.next().unwrap();
+        session.moves = current.moves;
+        session.position = current.position;
+        session.replay();
+        session.checkpoints = branches.collect();
+        Ok(session)
+    }
+}
+
+/// Plays the moves of a branch from the puzzle, checking that every one of them can be played.
+fn replay(initial: Grid, lines: &[(usize, &str)]) -> Result<Branch, SessionError> {
+    let mut session = Session::new(initial);
+    let mut redo = None;
+    for (line, text) in lines {
+        if *text == "redo" && redo.is_none() {
+            redo = Some(session.position);
+            continue;
+        }
+        let mv = text
+            .parse::<Move>()
+            .map_err(|ParseMoveError(text)| SessionError::InvalidMove(*line, text))?;
+        session
+            .play(mv)
+            .map_err(|error| SessionError::RejectedMove(*line, (*text).to_owned(), error))?;
+    }
+    Ok(Branch {
+        moves: session.moves,
+        position: redo.unwrap_or(session.position),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::{format, string::ToString};
+
+    const SUDOKU: &str = "060000300400700000000000080000008012500600000000000050082000700000500600000010000";
+
+    fn cell(notation: &str) -> CellIndex {
+        notation.parse().unwrap()
+    }
+
+    fn digit(value: u8) -> Digit {
+        Digit::new(value).unwrap()
+    }
+
+    #[test]
+    fn undo_redo() {
+        let grid: Grid = SUDOKU.parse().unwrap();
+        let mut session = Session::new(grid);
+        assert_eq!(session.set(cell("r1c1"), Some(digit(9)), true), Ok(()));
+        assert_eq!(session.set(cell("r1c2"), Some(digit(9)), true), Err(SetError::Frozen));
+        assert_eq!(
+            session.set(cell("r1c3"), Some(digit(9)), true),
+            Err(SetError::Conflict { with: cell("r1c1") })
+        );
+        assert_eq!(session.toggle_candidate(cell("r1c3"), digit(1)), Ok(()));
+        assert_eq!(session.moves().len(), 2);
+        assert!(session.grid().candidates(cell("r1c3")).get(0) != grid.candidates(cell("r1c3")).get(0));
+
+        assert!(session.undo());
+        assert!(session.undo());
+        assert!(!session.undo());
+        assert!(session.grid().eq_full(&grid));
+        assert!(session.redo());
+        assert_eq!(session.grid().get(cell("r1c1")), 9);
+
+        assert_eq!(session.set(cell("r1c4"), Some(digit(1)), true), Ok(()));
+        assert!(!session.can_redo());
+        assert_eq!(session.moves().len(), 2);
+    }
+
+    #[test]
+    fn long_game() {
+        let mut session = Session::new(SUDOKU.parse().unwrap());
+        session.fill_candidates();
+        let mut grids = vec![*session.grid()];
+        for round in 0..100 {
+            let empty = CellIndex::all()
+                .filter(|&index| session.grid().get(index) == 0)
+                .nth(round % 7)
+                .unwrap();
+            session.toggle_candidate(empty, digit(round as u8 % 9 + 1)).unwrap();
+            grids.push(*session.grid());
+        }
+        assert_eq!(session.snapshots.len(), 4);
+        for grid in grids.iter().rev().skip(1) {
+            assert!(session.undo());
+            assert!(session.grid().eq_full(grid));
+        }
+        assert_eq!(session.snapshots.len(), 4);
+        for grid in &grids[1..70] {
+            assert!(session.redo());
+            assert!(session.grid().eq_full(grid));
+        }
+        session.set(cell("r1c1"), Some(digit(9)), true).unwrap();
+        assert_eq!(session.snapshots.len(), 3);
+        assert!(session.undo());
+        assert!(session.grid().eq_full(&grids[69]));
+    }
+
+    #[test]
+    fn parse_move() {
+        assert_eq!(
+            "set-checked r2c4 5".parse::<Move>(),
+            Ok(Move::Set {
+                cell: cell("r2c4"),
+                value: Some(digit(5)),
+                checked: true
+            })
+        );
+        assert_eq!("fill".parse::<Move>(), Ok(Move::FillCandidates));
+        assert_eq!(
+            "toggle r2c4".parse::<Move>(),
+            Err(ParseMoveError("toggle r2c4".to_owned()))
+        );
+    }
+
+    #[test]
+    fn checkpoints() {
+        let mut session = Session::new(SUDOKU.parse().unwrap());
+        session.set(cell("r1c1"), Some(digit(9)), true).unwrap();
+        let checkpoint = session.checkpoint();
+        session.set(cell("r1c3"), Some(digit(1)), true).unwrap();
+        session.fill_candidates();
+        let abandoned = session.restore(checkpoint).unwrap();
+        assert_eq!(session.grid().get(cell("r1c3")), 0);
+        assert_eq!(session.grid().get(cell("r1c1")), 9);
+        assert!(!session.can_redo());
+        assert_eq!(session.checkpoints(), 2);
+
+        session.set(cell("r1c3"), Some(digit(7)), true).unwrap();
+        assert_eq!(session.restore(abandoned), Some(2));
+        assert_eq!(session.grid().get(cell("r1c3")), 1);
+        assert_eq!(session.moves().len(), 3);
+        assert_eq!(session.restore(2), Some(abandoned));
+        assert_eq!(session.grid().get(cell("r1c3")), 7);
+        assert_eq!(session.checkpoints(), 3);
+        assert_eq!(session.restore(3), None);
+    }
+
+    #[test]
+    fn log() {
+        let mut puzzle: Grid = SUDOKU.parse().unwrap();
+        puzzle.set_checked(cell("r9c9"), Some(digit(8))).unwrap();
+        puzzle.candidates_mut(cell("r9c8")).unset(2);
+        let mut session = Session::new(puzzle);
+        session.set(cell("r1c1"), Some(digit(9)), true).unwrap();
+        session.checkpoint();
+        session.toggle_candidate(cell("r1c3"), digit(5)).unwrap();
+        session.fill_candidates();
+        session.set(cell("r1c1"), None, false).unwrap();
+        session.undo();
+        let log = session.to_string();
+        assert_eq!(
+            log,
+            format!(
+                "{}\nset-checked r1c1 9\ntoggle r1c3 5\nfill\nredo\nset r1c1 0\ncheckpoint\nset-checked r1c1 9\n",
+                puzzle.to_candidate_content_string()
+            )
+        );
+        let replayed: Session = log.parse().unwrap();
+        assert!(replayed.initial().eq_full(&puzzle));
+        assert!(replayed.grid().eq_full(session.grid()));
+        assert_eq!(replayed.moves(), session.moves());
+        assert!(replayed.can_redo());
+        assert_eq!(replayed.checkpoints(), 1);
+        assert_eq!(replayed.to_string(), log);
+
+        let replayed: Session = format!("{}\nset-checked r1c1 9\n", SUDOKU).parse().unwrap();
+        assert_eq!(replayed.initial().givens(), 17);
+        assert_eq!(replayed.grid().get(cell("r1c1")), 9);
+
+        let result = format!("{}\nset-checked r1c2 9\n", SUDOKU).parse::<Session>();
+        assert_eq!(
+            *result.err().unwrap().downcast::<SessionError>().unwrap(),
+            SessionError::RejectedMove(2, "set-checked r1c2 9".to_owned(), SetError::Frozen)
+        );
+        let result = format!("{}\ncheckpoint\ntoggle r1c3 0\n", SUDOKU).parse::<Session>();
+        assert_eq!(
+            *result.err().unwrap().downcast::<SessionError>().unwrap(),
+            SessionError::InvalidMove(3, "toggle r1c3 0".to_owned())
+        );
+        assert!(format!("{}\nset r10c1 1\n", SUDOKU).parse::<Session>().is_err());
+        assert!(format!("{}\nredo\nredo\n", SUDOKU).parse::<Session>().is_err());
+    }
+}
@@ -1,10 +1,12 @@
-use ahash::{AHashMap, AHashSet};
+use alloc::{boxed::Box, vec, vec::Vec};
 use itertools::iproduct;
-use once_cell::sync::Lazy;
+use once_cell::race::OnceBox;
 
+use super::collections::{self, Key, Map, Set};
+use super::coordinates::{CellIndex, Digit};
 use super::grid::Grid;
 
-#[derive(PartialEq, Eq, Hash, Clone, Copy)]
+#[derive(PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy)]
 enum Constraint {
     RC,
     RN,
@@ -15,82 +17,112 @@ enum Constraint {
 type ConstraintType = (Constraint, (u8, u8));
 type RCNType = (u8, u8, u8);
 
-static Y: Lazy<AHashMap<RCNType, [ConstraintType; 4]>> = Lazy::new(|| {
-    let mut y = AHashMap::with_capacity(729);
-    for (r, c, n) in iproduct!(0..9, 0..9, 1..10) {
-        let b = r / 3 * 3 + c / 3;
-        y.insert(
-            (r, c, n),
-            [
-                (Constraint::RC, (r, c)),
-                (Constraint::RN, (r, n)),
-                (Constraint::CN, (c, n)),
-                (Constraint::BN, (b, n)),
-            ],
-        );
-    }
-    y
-});
-
-static X: Lazy<AHashMap<ConstraintType, AHashSet<RCNType>>> = Lazy::new(|| {
-    let x = iproduct!(0..9, 0..9)
-        .map(|rc| (Constraint::RC, rc))
-        .chain(iproduct!(0..9, 1..10).map(|rn| (Constraint::RN, rn)))
-        .chain(iproduct!(0..9, 1..10).map(|cn| (Constraint::CN, cn)))
-        .chain(iproduct!(0..9, 1..10).map(|bn| (Constraint::BN, bn)));
-    let mut exact_cover: AHashMap<ConstraintType, AHashSet<RCNType>> = AHashMap::with_capacity(324);
-    for j in x {
-        exact_cover.insert(j, AHashSet::with_capacity(9));
-    }
-    for (i, row) in Y.iter() {
-        for j in *row {
-            exact_cover.get_mut(&j).unwrap().insert(*i);
+static Y: OnceBox<Map<RCNType, [ConstraintType; 4]>> = OnceBox::new();
+static X: OnceBox<Map<ConstraintType, Set<RCNType>>> = OnceBox::new();
+
+fn y() -> &'static Map<RCNType, [ConstraintType; 4]> {
+    Y.get_or_init(|| {
+        let mut y = collections::map(729);
+        for (r, c, n) in iproduct!(0..9, 0..9, 1..10) {
+            let b = r / 3 * 3 + c / 3;
+            y.insert(
+                (r, c, n),
+                [
+                    (Constraint::RC, (r, c)),
+                    (Constraint::RN, (r, n)),
+                    (Constraint::CN, (c, n)),
+                    (Constraint::BN, (b, n)),
+                ],
+            );
         }
-    }
-    exact_cover
-});
+        Box::new(y)
+    })
+}
+
+fn x() -> &'static Map<ConstraintType, Set<RCNType>> {
+    X.get_or_init(|| {
+        let x = iproduct!(0..9, 0..9)
+            .map(|rc| (Constraint::RC, rc))
+            .chain(iproduct!(0..9, 1..10).map(|rn| (Constraint::RN, rn)))
+            .chain(iproduct!(0..9, 1..10).map(|cn| (Constraint::CN, cn)))
+            .chain(iproduct!(0..9, 1..10).map(|bn| (Constraint::BN, bn)));
+        let mut exact_cover: Map<ConstraintType, Set<RCNType>> = collections::map(324);
+        for j in x {
+            exact_cover.insert(j, collections::set(9));
+        }
+        for (i, row) in y().iter() {
+            for j in *row {
+                exact_cover.get_mut(&j).unwrap().insert(*i);
+            }
+        }
+        Box::new(exact_cover)
+    })
+}
 
 pub fn alx_solve(grid: &Grid, limit: usize) -> Vec<Grid> {
-    let mut x = X.clone();
+    if !grid.is_valid() {
+        return vec![];
+    }
 
-    for row in 0..9 {
-        for column in 0..9 {
-            let value = grid.get(row * 9 + column);
-            if value > 0 {
-                select(&mut x, &Y, (row as u8, column as u8, value));
-            }
+    let mut x = x().clone();
+
+    for cell in CellIndex::all() {
+        let value = grid.get(cell);
+        if value > 0 {
+            select(&mut x, y(), (cell.row() as u8, cell.column() as u8, value));
         }
     }
 
-    let solutions = solve(&mut x, &Y, &mut vec![], limit);
+    let solutions = solve(&mut x, y(), &mut vec![], limit);
 
     solutions
         .iter()
         .map(|solution| {
             let mut grid = *grid;
             for (r, c, n) in solution {
-                grid.set((r * 9 + c) as usize, *n, false);
+                grid.set_unchecked(CellIndex::at((r * 9 + c) as usize), Digit::new(*n))
+                    .unwrap();
             }
             grid
         })
         .collect()
 }
 
-fn solve(
-    x: &mut AHashMap<ConstraintType, AHashSet<RCNType>>,
-    y: &AHashMap<RCNType, [ConstraintType; 4]>,
-    solution: &mut Vec<RCNType>,
-    limit: usize,
-) -> Vec<Vec<RCNType>> {
+/// Lists the givens of a sudoku with a unique solution that can be removed with the solution staying unique. A sudoku
+/// without any is minimal. Values placed after the givens are left out, as they aren't part of the puzzle.
+pub fn redundant_givens(grid: &Grid) -> Vec<CellIndex> {
+    // Frozen cells can't be emptied, so the givens are copied onto a fresh grid
+    let mut givens = Grid::new();
+    for cell in CellIndex::all().filter(|cell| grid.frozen(*cell)) {
+        givens.set_unchecked(cell, Digit::new(grid.get(cell))).unwrap();
+    }
+    CellIndex::all()
+        .filter(|cell| grid.frozen(*cell))
+        .filter(|cell| {
+            let mut reduced = givens;
+            reduced.set_unchecked(*cell, None).unwrap();
+            alx_solve(&reduced, 2).len() == 1
+        })
+        .collect()
+}
+
+pub(crate) fn solve<C, R, Y>(x: &mut Map<C, Set<R>>, y: &Map<R, Y>, solution: &mut Vec<R>, limit: usize) -> Vec<Vec<R>>
+where
+    C: Key,
+    R: Key,
+    Y: AsRef<[C]>,
+{
     if x.is_empty() {
         return vec![solution.clone()];
     }
     let (c, _) = x.iter().min_by_key(|(_k, v)| v.len()).unwrap();
     let mut solutions = vec![];
-    for r in x[c].iter().cloned().collect::<Vec<RCNType>>() {
+    for r in x[c].iter().cloned().collect::<Vec<R>>() {
         solution.push(r);
         let mut cols = select(x, y, r);
-        solutions.append(&mut solve(x, y, solution, limit));
+        // The deeper searches only look for the solutions still missing, or the limit would be overshot
+        let remaining = if limit > 0 { limit - solutions.len() } else { 0 };
+        solutions.append(&mut solve(x, y, solution, remaining));
         deselect(x, y, r, &mut cols);
         solution.pop();
         if limit > 0 && solutions.len() == limit {
@@ -100,18 +132,19 @@ fn solve(
     solutions
 }
 
-fn select(
-    x: &mut AHashMap<ConstraintType, AHashSet<RCNType>>,
-    y: &AHashMap<RCNType, [ConstraintType; 4]>,
-    r: RCNType,
-) -> Vec<AHashSet<RCNType>> {
+pub(crate) fn select<C, R, Y>(x: &mut Map<C, Set<R>>, y: &Map<R, Y>, r: R) -> Vec<Set<R>>
+where
+    C: Key,
+    R: Key,
+    Y: AsRef<[C]>,
+{
     // This capacity is based on the max length of this vector running against the sudoku17 file
-    let mut cols: Vec<AHashSet<RCNType>> = Vec::with_capacity(4);
-    for j in y[&r] {
+    let mut cols: Vec<Set<R>> = Vec::with_capacity(4);
+    for &j in y[&r].as_ref() {
         // This capacity is based on the max length of this vector running against the sudoku17 file
         let mut remove_set = Vec::with_capacity(27);
         for i in &x[&j] {
-            for k in y[i] {
+            for &k in y[i].as_ref() {
                 if k != j {
                     remove_set.push((k, *i));
                 }
@@ -125,18 +158,18 @@ fn select(
     cols
 }
 
-fn deselect(
-    x: &mut AHashMap<ConstraintType, AHashSet<RCNType>>,
-    y: &AHashMap<RCNType, [ConstraintType; 4]>,
-    r: RCNType,
-    cols: &mut Vec<AHashSet<RCNType>>,
-) {
-    for j in y[&r].iter().rev() {
+fn deselect<C, R, Y>(x: &mut Map<C, Set<R>>, y: &Map<R, Y>, r: R, cols: &mut Vec<Set<R>>)
+where
+    C: Key,
+    R: Key,
+    Y: AsRef<[C]>,
+{
+    for j in y[&r].as_ref().iter().rev() {
         x.insert(*j, cols.pop().unwrap());
         // This capacity is based on the max length of this vector running against the sudoku17 file
         let mut insert_set = Vec::with_capacity(24);
         for i in &x[j] {
-            for k in y[i] {
+            for &k in y[i].as_ref() {
                 if k != *j {
                     insert_set.push((k, *i));
                 }
@@ -151,6 +184,7 @@ fn deselect(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use alloc::{format, string::ToString};
 
     #[test]
     fn alx_solver_test() {
@@ -169,6 +203,16 @@ mod tests {
         )
     }
 
+    #[test]
+    fn alx_solver_conflicts_test() {
+        let mut grid: Grid = "060000300400700000000000080000008012500600000000000050082000700000500600000010000"
+            .parse()
+            .unwrap();
+        grid.set_unchecked(CellIndex::at(0), Digit::new(6)).unwrap();
+        assert!(alx_solve(&grid, 0).is_empty());
+        assert!(alx_solve(&grid, 2).is_empty());
+    }
+
     #[test]
     fn alx_solver_multiple_solutions_test() {
         let grid = "060000000400700000000000080000008012500600000000000050082000700000500600000010000"
@@ -176,5 +220,22 @@ mod tests {
             .unwrap();
         let grid = &alx_solve(&grid, 0);
         assert!(grid.len() > 1);
+        for limit in 1..5 {
+            assert_eq!(alx_solve(&Grid::new(), limit).len(), limit);
+        }
+    }
+
+    #[test]
+    fn redundant_givens_test() {
+        let grid: Grid = "060000300400700000000000080000008012500600000000000050082000700000500600000010000"
+            .parse()
+            .unwrap();
+        assert_eq!(redundant_givens(&grid), vec![]);
+        let solution = alx_solve(&grid, 1)[0];
+        assert_eq!(redundant_givens(&solution), vec![]);
+        let solved: Grid = solution.to_string().parse().unwrap();
+        assert_eq!(redundant_givens(&solved).len(), 81);
+        let grid: Grid = format!("..{}", &solution.to_string()[2..]).parse().unwrap();
+        assert_eq!(redundant_givens(&grid).len(), 79);
     }
 }
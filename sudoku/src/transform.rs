@@ -0,0 +1,464 @@
+use alloc::{
+    borrow::ToOwned,
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+use core::{fmt, str::FromStr};
+use itertools::Itertools;
+#[cfg(feature = "generator")]
+use rand::{seq::SliceRandom, thread_rng, Rng};
+use thiserror::Error;
+
+use super::candidates::Candidates;
+use super::coordinates::{CellIndex, Digit};
+use super::grid::Grid;
+
+/// A symmetry of the sudoku rules. Applying one to a valid grid keeps it valid, and solutions map onto solutions.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Transform {
+    /// Quarter turn clockwise
+    Rotate,
+    /// Reverses the columns
+    MirrorHorizontal,
+    /// Reverses the rows
+    MirrorVertical,
+    /// Swaps rows with columns
+    Transpose,
+    /// Replaces each digit `d` with `digits[d - 1]`
+    PermuteDigits([u8; 9]),
+    SwapBands(usize, usize),
+    SwapStacks(usize, usize),
+    /// Swaps two rows of the same band
+    SwapRows(usize, usize),
+    /// Swaps two columns of the same stack
+    SwapColumns(usize, usize),
+}
+
+/// A symmetry of the pattern of givens, regardless of their values.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Symmetry {
+    /// Unchanged by a half turn
+    Rotational,
+    /// Unchanged by a quarter turn, which implies `Rotational`
+    QuarterTurn,
+    /// Unchanged by reversing the columns
+    Horizontal,
+    /// Unchanged by reversing the rows
+    Vertical,
+    /// Unchanged by transposing
+    Diagonal,
+    /// Unchanged by reflecting across the diagonal from the top right to the bottom left
+    AntiDiagonal,
+}
+
+#[derive(PartialEq, Eq, Error, Debug)]
+pub enum TransformError {
+    #[error("Unknown transform '{0}'")]
+    Unknown(String),
+    #[error("Invalid digit permutation '{0}', expected each digit 1-9 once")]
+    InvalidDigits(String),
+    #[error(
+        "Invalid swap '{0}', expected two different bands or stacks 0-2, or two different rows or columns 0-8 in the \
+         same band or stack"
+    )]
+    InvalidSwap(String),
+    #[error("Unknown symmetry '{0}'")]
+    UnknownSymmetry(String),
+}
+
+fn swap(index: usize, a: usize, b: usize) -> usize {
+    if index == a {
+        b
+    } else if index == b {
+        a
+    } else {
+        index
+    }
+}
+
+impl Transform {
+    /// Checks that the digits are a permutation and that the swapped lines are different ones that can be swapped.
+    pub fn validate(&self) -> Result<(), TransformError> {
+        let valid = match *self {
+            Transform::PermuteDigits(digits) => digits.iter().sorted().copied().eq(1..10),
+            Transform::SwapBands(a, b) | Transform::SwapStacks(a, b) => a < 3 && b < 3 && a != b,
+            Transform::SwapRows(a, b) | Transform::SwapColumns(a, b) => a < 9 && b < 9 && a / 3 == b / 3 && a != b,
+            _ => true,
+        };
+        match self {
+            _ if valid => Ok(()),
+            Transform::PermuteDigits(digits) => Err(TransformError::InvalidDigits(digits.iter().join(""))),
+            _ => Err(TransformError::InvalidSwap(self.to_string())),
+        }
+    }
+
+    /// The cell whose contents move to `cell`.
+    fn source(&self, cell: CellIndex) -> CellIndex {
+        let (row, column) = (cell.row(), cell.column());
+        let (row, column) = match *self {
+            Transform::Rotate => (8 - column, row),
+            Transform::MirrorHorizontal => (row, 8 - column),
+            Transform::MirrorVertical => (8 - row, column),
+            Transform::Transpose => (column, row),
+            Transform::PermuteDigits(_) => (row, column),
+            Transform::SwapBands(a, b) => (swap(row / 3, a, b) * 3 + row % 3, column),
+            Transform::SwapStacks(a, b) => (row, swap(column / 3, a, b) * 3 + column % 3),
+            Transform::SwapRows(a, b) => (swap(row, a, b), column),
+            Transform::SwapColumns(a, b) => (row, swap(column, a, b)),
+        };
+        CellIndex::at(row * 9 + column)
+    }
+
+    /// Applies the transform to a grid, carrying the frozen flags and candidates along with the values.
+    pub fn apply(&self, grid: &Grid) -> Result<Grid, TransformError> {
+        self.validate()?;
+        let digits = match *self {
+            Transform::PermuteDigits(digits) => digits,
+            _ => [1, 2, 3, 4, 5, 6, 7, 8, 9],
+        };
+        Ok(remap(grid, |cell| self.source(cell), &digits))
+    }
+}
+
+fn remap(grid: &Grid, source: impl Fn(CellIndex) -> CellIndex, digits: &[u8; 9]) -> Grid {
+    let mut transformed = Grid::new();
+    for cell in CellIndex::all() {
+        let source = source(cell);
+        let value = match grid.get(source) {
+            0 => 0,
+            value => digits[value as usize - 1],
+        };
+        let candidates = grid
+            .candidates(source)
+            .iter()
+            .filter_map(|digit| Digit::new(digits[digit as usize - 1]))
+            .collect::<Candidates>();
+        transformed.set_unchecked(cell, Digit::new(value)).unwrap();
+        *transformed.candidates_mut(cell) = candidates;
+        if grid.frozen(source) {
+            transformed.freeze(cell);
+        }
+    }
+    transformed
+}
+
+impl fmt::Display for Transform {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Transform::Rotate => write!(f, "rotate"),
+            Transform::MirrorHorizontal => write!(f, "mirror-horizontal"),
+            Transform::MirrorVertical => write!(f, "mirror-vertical"),
+            Transform::Transpose => write!(f, "transpose"),
+            Transform::PermuteDigits(digits) => write!(f, "digits={}", digits.iter().join("")),
+            Transform::SwapBands(a, b) => write!(f, "bands={},{}", a, b),
+            Transform::SwapStacks(a, b) => write!(f, "stacks={},{}", a, b),
+            Transform::SwapRows(a, b) => write!(f, "rows={},{}", a, b),
+            Transform::SwapColumns(a, b) => write!(f, "columns={},{}", a, b),
+        }
+    }
+}
+
+/// Parses the names printed by `Display`, such as `rotate`, `digits=912345678` or `rows=0,2`.
+impl FromStr for Transform {
+    type Err = TransformError;
+
+    fn from_str(string: &str) -> Result<Self, Self::Err> {
+        let (name, arguments) = string.split_once('=').unwrap_or((string, ""));
+        let pair = || -> Result<(usize, usize), TransformError> {
+            let invalid = || TransformError::InvalidSwap(string.to_owned());
+            let (a, b) = arguments.split_once(',').ok_or_else(invalid)?;
+            Ok((
+                a.trim().parse().map_err(|_| invalid())?,
+                b.trim().parse().map_err(|_| invalid())?,
+            ))
+        };
+        let transform = match (name, arguments.is_empty()) {
+            ("rotate", true) => Transform::Rotate,
+            ("mirror-horizontal", true) => Transform::MirrorHorizontal,
+            ("mirror-vertical", true) => Transform::MirrorVertical,
+            ("transpose", true) => Transform::Transpose,
+            ("digits", false) => {
+                let invalid = || TransformError::InvalidDigits(arguments.to_owned());
+                let digits = arguments
+                    .chars()
+                    .map(|c| c.to_digit(10).map(|digit| digit as u8))
+                    .collect::<Option<Vec<u8>>>()
+                    .ok_or_else(invalid)?;
+                Transform::PermuteDigits(digits.try_into().map_err(|_| invalid())?)
+            }
+            ("bands", false) | ("stacks", false) | ("rows", false) | ("columns", false) => {
+                let (a, b) = pair()?;
+                match name {
+                    "bands" => Transform::SwapBands(a, b),
+                    "stacks" => Transform::SwapStacks(a, b),
+                    "rows" => Transform::SwapRows(a, b),
+                    _ => Transform::SwapColumns(a, b),
+                }
+            }
+            _ => return Err(TransformError::Unknown(string.to_owned())),
+        };
+        transform.validate()?;
+        Ok(transform)
+    }
+}
+
+impl Symmetry {
+    pub const ALL: [Symmetry; 6] = [
+        Symmetry::Rotational,
+        Symmetry::QuarterTurn,
+        Symmetry::Horizontal,
+        Symmetry::Vertical,
+        Symmetry::Diagonal,
+        Symmetry::AntiDiagonal,
+    ];
+
+    /// The cell `cell` maps onto.
+    fn image(self, cell: CellIndex) -> CellIndex {
+        let (row, column) = (cell.row(), cell.column());
+        let (row, column) = match self {
+            Symmetry::Rotational => (8 - row, 8 - column),
+            Symmetry::QuarterTurn => (column, 8 - row),
+            Symmetry::Horizontal => (row, 8 - column),
+            Symmetry::Vertical => (8 - row, column),
+            Symmetry::Diagonal => (column, row),
+            Symmetry::AntiDiagonal => (8 - column, 8 - row),
+        };
+        CellIndex::at(row * 9 + column)
+    }
+
+    /// Whether every filled cell of the grid maps onto a filled cell.
+    pub fn holds(self, grid: &Grid) -> bool {
+        CellIndex::all().all(|cell| (grid.get(cell) > 0) == (grid.get(self.image(cell)) > 0))
+    }
+
+    /// Groups the cells into the sets that map onto each other, which have to be filled or emptied together to keep
+    /// the symmetry.
+    pub fn orbits(self) -> Vec<Vec<CellIndex>> {
+        let mut orbits: Vec<Vec<CellIndex>> = vec![];
+        for cell in CellIndex::all() {
+            if orbits.iter().any(|orbit| orbit.contains(&cell)) {
+                continue;
+            }
+            let mut orbit = vec![cell];
+            let mut image = self.image(cell);
+            while image != cell {
+                orbit.push(image);
+                image = self.image(image);
+            }
+            orbits.push(orbit);
+        }
+        orbits
+    }
+
+    /// Whether some orbits add up to exactly `givens` cells.
+    pub fn allows(self, givens: usize) -> bool {
+        // Bit `n` is set when some orbits add up to `n` cells
+        let reachable = self
+            .orbits()
+            .iter()
+            .fold(1u128, |reachable, orbit| reachable | reachable << orbit.len());
+        givens <= 81 && reachable & 1 << givens != 0
+    }
+
+    /// Lists the symmetries of the grid, which is all of them for an empty or full grid.
+    pub fn of(grid: &Grid) -> Vec<Symmetry> {
+        Symmetry::ALL
+            .into_iter()
+            .filter(|symmetry| symmetry.holds(grid))
+            .collect()
+    }
+}
+
+impl fmt::Display for Symmetry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Symmetry::Rotational => write!(f, "rotational"),
+            Symmetry::QuarterTurn => write!(f, "quarter-turn"),
+            Symmetry::Horizontal => write!(f, "horizontal"),
+            Symmetry::Vertical => write!(f, "vertical"),
+            Symmetry::Diagonal => write!(f, "diagonal"),
+            Symmetry::AntiDiagonal => write!(f, "anti-diagonal"),
+        }
+    }
+}
+
+impl FromStr for Symmetry {
+    type Err = TransformError;
+
+    fn from_str(string: &str) -> Result<Self, Self::Err> {
+        Symmetry::ALL
+            .into_iter()
+            .find(|symmetry| symmetry.to_string() == string)
+            .ok_or_else(|| TransformError::UnknownSymmetry(string.to_owned()))
+    }
+}
+
+/// Returns a random grid equivalent to the given one, by randomly relabeling digits, transposing, and permuting bands,
+/// stacks, and the rows and columns within them.
+#[cfg(feature = "generator")]
+pub fn random_equivalent(grid: &Grid) -> Grid {
+    let mut rng = thread_rng();
+    let mut order = || {
+        let mut groups = [0, 1, 2];
+        groups.shuffle(&mut rng);
+        groups
+            .iter()
+            .flat_map(|group| {
+                let mut lines = [group * 3, group * 3 + 1, group * 3 + 2];
+                lines.shuffle(&mut rng);
+                lines
+            })
+            .collect::<Vec<usize>>()
+    };
+    let rows = order();
+    let columns = order();
+    let transpose = rng.gen_bool(0.5);
+    let mut digits = [1, 2, 3, 4, 5, 6, 7, 8, 9];
+    digits.shuffle(&mut rng);
+    remap(
+        grid,
+        |cell| {
+            let (row, column) = (rows[cell.row()], columns[cell.column()]);
+            if transpose {
+                CellIndex::at(column * 9 + row)
+            } else {
+                CellIndex::at(row * 9 + column)
+            }
+        },
+        &digits,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::canonical::are_equivalent;
+
+    const SUDOKU: &str = "060000300400700000000000080000008012500600000000000050082000700000500600000010000";
+
+    fn transforms() -> Vec<Transform> {
+        vec![
+            Transform::Rotate,
+            Transform::MirrorHorizontal,
+            Transform::MirrorVertical,
+            Transform::Transpose,
+            Transform::PermuteDigits([9, 1, 2, 3, 4, 5, 6, 7, 8]),
+            Transform::SwapBands(0, 2),
+            Transform::SwapStacks(1, 2),
+            Transform::SwapRows(3, 5),
+            Transform::SwapColumns(7, 8),
+        ]
+    }
+
+    #[test]
+    fn transforms_test() {
+        let mut grid: Grid = SUDOKU.parse().unwrap();
+        grid.candidates_mut(CellIndex::at(0)).unset(0);
+        for transform in transforms() {
+            let transformed = transform.apply(&grid).unwrap();
+            assert!(transformed.is_valid(), "{}", transform);
+            assert_eq!(transformed.givens(), 17, "{}", transform);
+            assert!(are_equivalent(&grid, &transformed), "{}", transform);
+            assert_eq!(transform.to_string().parse::<Transform>().unwrap(), transform);
+        }
+        let rotated = Transform::Rotate.apply(&grid).unwrap();
+        assert_eq!(rotated.get(CellIndex::at(17)), 6);
+        assert!(rotated.frozen(CellIndex::at(17)));
+        assert!(!rotated.candidates(CellIndex::at(8)).get(0));
+        assert!(rotated.candidates(CellIndex::at(8)).get(1));
+        let rotated = (0..3).fold(rotated, |grid, _| Transform::Rotate.apply(&grid).unwrap());
+        assert_eq!(rotated, grid);
+
+        let relabeled = Transform::PermuteDigits([9, 1, 2, 3, 4, 5, 6, 7, 8])
+            .apply(&grid)
+            .unwrap();
+        assert_eq!(relabeled.get(CellIndex::at(1)), 5);
+        assert!(relabeled.candidates(CellIndex::at(0)).get(0));
+        assert!(!relabeled.candidates(CellIndex::at(0)).get(8));
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert_eq!(
+            "spin".parse::<Transform>(),
+            Err(TransformError::Unknown("spin".to_owned()))
+        );
+        assert_eq!(
+            "digits=112345678".parse::<Transform>(),
+            Err(TransformError::InvalidDigits("112345678".to_owned()))
+        );
+        assert_eq!(
+            "rows=2,3".parse::<Transform>(),
+            Err(TransformError::InvalidSwap("rows=2,3".to_owned()))
+        );
+        assert_eq!(
+            "bands=0".parse::<Transform>(),
+            Err(TransformError::InvalidSwap("bands=0".to_owned()))
+        );
+        assert_eq!(
+            "bands=0,0".parse::<Transform>(),
+            Err(TransformError::InvalidSwap("bands=0,0".to_owned()))
+        );
+
+        let grid: Grid = SUDOKU.parse().unwrap();
+        assert_eq!(
+            Transform::PermuteDigits([1, 1, 2, 3, 4, 5, 6, 7, 8]).apply(&grid),
+            Err(TransformError::InvalidDigits("112345678".to_owned()))
+        );
+        assert_eq!(
+            Transform::SwapRows(2, 3).apply(&grid),
+            Err(TransformError::InvalidSwap("rows=2,3".to_owned()))
+        );
+    }
+
+    #[test]
+    fn symmetries() {
+        let grid: Grid = SUDOKU.parse().unwrap();
+        assert_eq!(Symmetry::of(&grid), vec![]);
+        assert_eq!(Symmetry::of(&Grid::new()), Symmetry::ALL);
+
+        let mut grid = Grid::new();
+        grid.set_unchecked(CellIndex::at(0), Digit::new(1)).unwrap();
+        grid.set_unchecked(CellIndex::at(80), Digit::new(2)).unwrap();
+        assert_eq!(
+            Symmetry::of(&grid),
+            vec![Symmetry::Rotational, Symmetry::Diagonal, Symmetry::AntiDiagonal]
+        );
+        grid.set_unchecked(CellIndex::at(8), Digit::new(3)).unwrap();
+        grid.set_unchecked(CellIndex::at(72), Digit::new(4)).unwrap();
+        assert_eq!(Symmetry::of(&grid), Symmetry::ALL);
+        grid.set_unchecked(CellIndex::at(1), Digit::new(5)).unwrap();
+        grid.set_unchecked(CellIndex::at(7), Digit::new(6)).unwrap();
+        assert_eq!(Symmetry::of(&grid), vec![Symmetry::Horizontal]);
+
+        for symmetry in Symmetry::ALL {
+            assert_eq!(symmetry.to_string().parse::<Symmetry>().unwrap(), symmetry);
+            let orbits = symmetry.orbits();
+            assert_eq!(orbits.iter().map(Vec::len).sum::<usize>(), 81, "{}", symmetry);
+            let mut grid = Grid::new();
+            for cell in &orbits[orbits.len() / 2] {
+                grid.set_unchecked(*cell, Digit::new(1)).unwrap();
+            }
+            assert!(symmetry.holds(&grid), "{}", symmetry);
+        }
+        assert_eq!(Symmetry::QuarterTurn.orbits()[0], [0, 8, 80, 72].map(CellIndex::at));
+        assert!(Symmetry::QuarterTurn.allows(29));
+        assert!(!Symmetry::QuarterTurn.allows(30));
+        assert!((17..=81).all(|givens| Symmetry::Rotational.allows(givens)));
+        assert_eq!(
+            "spiral".parse::<Symmetry>(),
+            Err(TransformError::UnknownSymmetry("spiral".to_owned()))
+        );
+    }
+
+    #[cfg(feature = "generator")]
+    #[test]
+    fn random_equivalent_test() {
+        let grid: Grid = SUDOKU.parse().unwrap();
+        let equivalent = random_equivalent(&grid);
+        assert!(equivalent.is_valid());
+        assert_eq!(equivalent.givens(), 17);
+        assert!(are_equivalent(&grid, &equivalent));
+    }
+}